@@ -0,0 +1,255 @@
+use std::{
+    collections::HashMap,
+    fmt::{self, Display},
+};
+
+use crate::map::{Terrain, TerrainDisplay, TileMap};
+
+/// An error parsing a [`TileMap`] from ascii art. Rows and columns are counted
+/// from zero starting at the top left of the art.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MapParseError {
+    /// The art has no rows or the rows have no characters.
+    Empty,
+    /// The `character` at `row` and `column` is not in the legend.
+    UnknownCharacter {
+        row: usize,
+        column: usize,
+        character: char,
+    },
+    /// The `row` has `found` characters instead of the `expected` ones the
+    /// first row has.
+    RaggedRow {
+        row: usize,
+        expected: usize,
+        found: usize,
+    },
+}
+
+impl Display for MapParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MapParseError::Empty => write!(f, "the map has no tiles"),
+            MapParseError::UnknownCharacter {
+                row,
+                column,
+                character,
+            } => write!(
+                f,
+                "unknown character {:?} at row {}, column {}",
+                character, row, column
+            ),
+            MapParseError::RaggedRow {
+                row,
+                expected,
+                found,
+            } => write!(
+                f,
+                "row {} has {} tiles but the first row has {}",
+                row, found, expected
+            ),
+        }
+    }
+}
+
+impl std::error::Error for MapParseError {}
+
+/// Returns a legend with `~` water, `.` plains, `f` forest, `^` mountain, `#`
+/// road, `T` town and `C` city, each using the terrain's default sprite.
+pub fn default_legend() -> HashMap<char, TerrainDisplay> {
+    [
+        ('~', Terrain::Water),
+        ('.', Terrain::Plains),
+        ('f', Terrain::Forest),
+        ('^', Terrain::Mountain),
+        ('#', Terrain::Road),
+        ('T', Terrain::Town),
+        ('C', Terrain::City),
+    ]
+    .into_iter()
+    .map(|(character, terrain)| (character, terrain.as_display(terrain.default_sprite())))
+    .collect()
+}
+
+impl TileMap {
+    /// Parses a map where each line of the art is a row of tiles and each
+    /// character is looked up from the legend. The top line is the row with
+    /// the highest y. Whitespace around the lines and blank lines before and
+    /// after the art are ignored.
+    pub fn from_ascii(
+        art: &str,
+        legend: &HashMap<char, TerrainDisplay>,
+    ) -> Result<TileMap, MapParseError> {
+        let lines: Vec<&str> = art.lines().map(str::trim).collect();
+        let first = lines.iter().position(|line| !line.is_empty());
+        let last = lines.iter().rposition(|line| !line.is_empty());
+        let (Some(first), Some(last)) = (first, last) else {
+            return Err(MapParseError::Empty);
+        };
+        let lines = &lines[first..=last];
+
+        let width = lines[0].chars().count();
+        let height = lines.len();
+        let mut map = TileMap::new(width, height);
+        for (row, line) in lines.iter().enumerate() {
+            let found = line.chars().count();
+            if found != width {
+                return Err(MapParseError::RaggedRow {
+                    row,
+                    expected: width,
+                    found,
+                });
+            }
+
+            for (column, character) in line.chars().enumerate() {
+                let display = legend
+                    .get(&character)
+                    .ok_or(MapParseError::UnknownCharacter {
+                        row,
+                        column,
+                        character,
+                    })?;
+                map[(column, height - 1 - row)] = display.clone();
+            }
+        }
+
+        Ok(map)
+    }
+
+    /// Draws the map as ascii art readable by [`TileMap::from_ascii`]. Each
+    /// tile uses the legend character with the same terrain and sprite, or
+    /// failing that the same terrain, or `?` if there is none.
+    pub fn to_ascii(&self, legend: &HashMap<char, TerrainDisplay>) -> String {
+        let character_for = |display: &TerrainDisplay| {
+            let matching = |exact: bool| {
+                legend
+                    .iter()
+                    .filter(|(_, entry)| {
+                        entry.terrain == display.terrain
+                            && (!exact || entry.sprite == display.sprite)
+                    })
+                    .map(|(&character, _)| character)
+                    .min()
+            };
+            matching(true).or_else(|| matching(false)).unwrap_or('?')
+        };
+
+        (0..self.height)
+            .rev()
+            .map(|y| {
+                (0..self.width)
+                    .map(|x| character_for(&self[(x, y)]))
+                    .collect::<String>()
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn top_line_is_the_highest_row() {
+        let map = TileMap::from_ascii(
+            "
+            ~^
+            .#
+            TC
+            ",
+            &default_legend(),
+        )
+        .unwrap();
+
+        assert_eq!((map.width, map.height), (2, 3));
+        assert_eq!(map[(0, 2)].terrain, Terrain::Water);
+        assert_eq!(map[(1, 2)].terrain, Terrain::Mountain);
+        assert_eq!(map[(0, 1)].terrain, Terrain::Plains);
+        assert_eq!(map[(1, 1)].terrain, Terrain::Road);
+        assert_eq!(map[(0, 0)].terrain, Terrain::Town);
+        assert_eq!(map[(1, 0)].terrain, Terrain::City);
+        assert_eq!(map[(1, 0)].sprite, "city.png");
+        assert!(map.features.iter().flatten().all(Option::is_none));
+    }
+
+    #[test]
+    fn round_trip() {
+        let legend = default_legend();
+        let art = "~~.f\n.#T^\nC..~";
+
+        let map = TileMap::from_ascii(art, &legend).unwrap();
+        assert_eq!(map.to_ascii(&legend), art);
+        let again = TileMap::from_ascii(&map.to_ascii(&legend), &legend).unwrap();
+        assert_eq!(again.to_ascii(&legend), art);
+    }
+
+    #[test]
+    fn to_ascii_prefers_the_same_sprite() {
+        let mut legend = default_legend();
+        legend.insert('w', Terrain::Water.as_display("deep_water.png"));
+
+        let mut map = TileMap::new(3, 1);
+        map[(1, 0)] = Terrain::Water.as_display("deep_water.png");
+        map[(2, 0)] = Terrain::Water.as_display("other_water.png");
+        // Without the same sprite the lowest character of the terrain is used.
+        assert_eq!(map.to_ascii(&legend), "~ww");
+
+        // Terrains missing from the legend are drawn as `?`.
+        legend.retain(|&character, _| character != '~');
+        assert_eq!(map.to_ascii(&legend), "www");
+        assert_eq!(map.to_ascii(&HashMap::new()), "???");
+    }
+
+    #[test]
+    fn parse_errors() {
+        let legend = default_legend();
+
+        assert_eq!(
+            TileMap::from_ascii("", &legend).unwrap_err(),
+            MapParseError::Empty
+        );
+        assert_eq!(
+            TileMap::from_ascii("\n   \n", &legend).unwrap_err(),
+            MapParseError::Empty
+        );
+
+        let error = TileMap::from_ascii("...\n.x.\n...", &legend).unwrap_err();
+        assert_eq!(
+            error,
+            MapParseError::UnknownCharacter {
+                row: 1,
+                column: 1,
+                character: 'x'
+            }
+        );
+        assert_eq!(
+            error.to_string(),
+            "unknown character 'x' at row 1, column 1"
+        );
+
+        let error = TileMap::from_ascii("\n  ...\n  ..\n  ...\n", &legend).unwrap_err();
+        assert_eq!(
+            error,
+            MapParseError::RaggedRow {
+                row: 1,
+                expected: 3,
+                found: 2
+            }
+        );
+        assert_eq!(
+            error.to_string(),
+            "row 1 has 2 tiles but the first row has 3"
+        );
+
+        // A blank line inside the art is a row without tiles.
+        assert_eq!(
+            TileMap::from_ascii("..\n\n..", &legend).unwrap_err(),
+            MapParseError::RaggedRow {
+                row: 1,
+                expected: 2,
+                found: 0
+            }
+        );
+    }
+}
@@ -0,0 +1,353 @@
+use std::collections::{HashMap, HashSet};
+
+use bevy::prelude::*;
+
+use crate::map::{Direction, GridKind, MapChanged, MapConfig, Neighbors, Terrain, Tile, TileIndex};
+
+/// The bits of an autotile bitmask set when the neighbor in that direction
+/// has the same terrain.
+pub const NORTH: u8 = 1;
+pub const EAST: u8 = 2;
+pub const SOUTH: u8 = 4;
+pub const WEST: u8 = 8;
+pub const NORTH_EAST: u8 = 16;
+pub const SOUTH_EAST: u8 = 32;
+pub const SOUTH_WEST: u8 = 64;
+pub const NORTH_WEST: u8 = 128;
+
+fn bit(direction: Direction) -> u8 {
+    match direction {
+        Direction::North => NORTH,
+        Direction::NorthEast => NORTH_EAST,
+        Direction::East => EAST,
+        Direction::SouthEast => SOUTH_EAST,
+        Direction::South => SOUTH,
+        Direction::SouthWest => SOUTH_WEST,
+        Direction::West => WEST,
+        Direction::NorthWest => NORTH_WEST,
+    }
+}
+
+/// The sprite variants of a terrain by autotile bitmask.
+#[derive(Clone, Debug, Default)]
+pub struct TerrainDisplaySet {
+    pub sprites: HashMap<u8, String>,
+}
+
+impl TerrainDisplaySet {
+    pub fn with(mut self, mask: u8, sprite: impl Into<String>) -> Self {
+        self.sprites.insert(mask, sprite.into());
+        self
+    }
+
+    pub fn get(&self, mask: u8) -> Option<&str> {
+        self.sprites.get(&mask).map(String::as_str)
+    }
+}
+
+/// How the sprite of a terrain is chosen from its neighbors.
+#[derive(Clone, Debug, Default)]
+pub struct AutotileRule {
+    pub sprites: TerrainDisplaySet,
+    /// Whether the edge of the map counts as the same terrain, for example
+    /// so that water continues past it.
+    pub edge_is_same: bool,
+}
+
+impl AutotileRule {
+    /// Returns the bitmask of the neighbors in the movement directions of the
+    /// grid with the same terrain, `neighbor` returning the terrain in a
+    /// direction or `None` at the edge of the map.
+    pub fn mask(
+        &self,
+        grid: GridKind,
+        terrain: Terrain,
+        neighbor: impl Fn(Direction) -> Option<Terrain>,
+    ) -> u8 {
+        grid.movement_directions()
+            .iter()
+            .filter(|&&direction| match neighbor(direction) {
+                Some(other) => other == terrain,
+                None => self.edge_is_same,
+            })
+            .fold(0, |mask, &direction| mask | bit(direction))
+    }
+}
+
+/// The terrains whose sprites depend on their neighbors.
+#[derive(Resource, Clone, Debug, Default)]
+pub struct AutotileRules {
+    pub rules: HashMap<Terrain, AutotileRule>,
+}
+
+impl AutotileRules {
+    pub fn with(mut self, terrain: Terrain, rule: AutotileRule) -> Self {
+        self.rules.insert(terrain, rule);
+        self
+    }
+
+    pub fn get(&self, terrain: Terrain) -> Option<&AutotileRule> {
+        self.rules.get(&terrain)
+    }
+}
+
+/// The autotile bitmask last chosen for the tile.
+#[derive(Component, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AutotileMask(pub u8);
+
+/// Picks the sprites of the tiles at and around the coordinates in the
+/// [`MapChanged`] events whose terrain has an [`AutotileRule`], keeping the
+/// sprite if the rule has no variant for the bitmask. Must run after
+/// [`update_neighbors`](crate::map::update_neighbors).
+#[allow(clippy::too_many_arguments)]
+pub fn autotile(
+    mut commands: Commands,
+    rules: Res<AutotileRules>,
+    asset_server: Res<AssetServer>,
+    tile_index: Res<TileIndex>,
+    map_config: Option<Res<MapConfig>>,
+    mut map_changed: EventReader<MapChanged>,
+    tiles: Query<&Tile>,
+    mut sprites: Query<(&Tile, &Neighbors, &mut Handle<Image>, Option<&AutotileMask>)>,
+) {
+    let grid = map_config.map_or_else(GridKind::default, |config| config.grid);
+
+    let mut positions = HashSet::new();
+    for event in map_changed.read() {
+        for &position in event.positions.iter() {
+            positions.insert(position);
+            positions.extend(
+                grid.directions()
+                    .iter()
+                    .filter_map(|direction| direction.step_on(grid, position)),
+            );
+        }
+    }
+
+    for (x, y) in positions {
+        let Some(entity) = tile_index.get(x, y) else {
+            continue;
+        };
+        let Ok((tile, neighbors, mut texture, current_mask)) = sprites.get_mut(entity) else {
+            continue;
+        };
+
+        let Some(rule) = rules.get(tile.terrain) else {
+            if current_mask.is_some() {
+                commands.entity(entity).remove::<AutotileMask>();
+            }
+            continue;
+        };
+
+        let mask = rule.mask(grid, tile.terrain, |direction| {
+            let neighbor = neighbors.get(direction)?;
+            tiles.get(neighbor).ok().map(|tile| tile.terrain)
+        });
+        if current_mask != Some(&AutotileMask(mask)) {
+            commands.entity(entity).insert(AutotileMask(mask));
+        }
+
+        if let Some(sprite) = rule.sprites.get(mask) {
+            let handle = asset_server.load(sprite.to_owned());
+            if *texture != handle {
+                *texture = handle;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::system::Command;
+
+    use super::*;
+    use crate::{
+        ascii::default_legend,
+        map::{tests, SetTile, TileMap},
+    };
+
+    fn road_rules() -> AutotileRules {
+        AutotileRules::default().with(
+            Terrain::Road,
+            AutotileRule {
+                sprites: TerrainDisplaySet::default()
+                    .with(NORTH | EAST, "road_north_east.png")
+                    .with(NORTH | SOUTH, "road_north_south.png"),
+                edge_is_same: false,
+            },
+        )
+    }
+
+    fn mask_at(app: &App, x: usize, y: usize) -> Option<u8> {
+        let entity = app.world.resource::<TileIndex>().get(x, y).unwrap();
+        app.world.get::<AutotileMask>(entity).map(|mask| mask.0)
+    }
+
+    fn sprite_at(app: &App, x: usize, y: usize) -> Handle<Image> {
+        let entity = app.world.resource::<TileIndex>().get(x, y).unwrap();
+        app.world.get::<Handle<Image>>(entity).unwrap().clone()
+    }
+
+    #[test]
+    fn mask_of_neighbors() {
+        let rule = AutotileRule::default();
+        let road_to = |directions: &'static [Direction]| {
+            move |direction| {
+                Some(if directions.contains(&direction) {
+                    Terrain::Road
+                } else {
+                    Terrain::Plains
+                })
+            }
+        };
+
+        assert_eq!(rule.mask(GridKind::Square, Terrain::Road, road_to(&[])), 0);
+        assert_eq!(
+            rule.mask(
+                GridKind::Square,
+                Terrain::Road,
+                road_to(&[Direction::North])
+            ),
+            NORTH
+        );
+        assert_eq!(
+            rule.mask(
+                GridKind::Square,
+                Terrain::Road,
+                road_to(&[Direction::East, Direction::West, Direction::NorthEast])
+            ),
+            EAST | WEST
+        );
+        assert_eq!(
+            rule.mask(GridKind::Square, Terrain::Road, |_| Some(Terrain::Road)),
+            15
+        );
+
+        let at_edge = |direction| (direction != Direction::South).then_some(Terrain::Water);
+        assert_eq!(
+            rule.mask(GridKind::Square, Terrain::Water, at_edge),
+            NORTH | EAST | WEST
+        );
+        let edge_is_same = AutotileRule {
+            edge_is_same: true,
+            ..default()
+        };
+        assert_eq!(
+            edge_is_same.mask(GridKind::Square, Terrain::Water, at_edge),
+            15
+        );
+    }
+
+    #[test]
+    fn hex_masks() {
+        let rule = AutotileRule::default();
+        let grid = GridKind::HexPointyTop;
+        let all = NORTH_EAST | EAST | SOUTH_EAST | SOUTH_WEST | WEST | NORTH_WEST;
+        assert_eq!(rule.mask(grid, Terrain::Road, |_| Some(Terrain::Road)), all);
+
+        let mut app = tests::app(autotile);
+        app.insert_resource(road_rules());
+        let mut map = TileMap::from_ascii(
+            "
+            #.
+            .#
+            ",
+            &default_legend(),
+        )
+        .unwrap();
+        map.grid = grid;
+        map.apply(&mut app.world);
+        app.update();
+
+        // The odd row is shifted east, so the roads touch diagonally.
+        assert_eq!(mask_at(&app, 1, 0), Some(NORTH_WEST));
+        assert_eq!(mask_at(&app, 0, 1), Some(SOUTH_EAST));
+
+        SetTile {
+            x: 0,
+            y: 1,
+            display: Terrain::Plains.as_display("grass.png"),
+        }
+        .apply(&mut app.world);
+        app.update();
+        assert_eq!(mask_at(&app, 1, 0), Some(0));
+    }
+
+    #[test]
+    fn l_shaped_road() {
+        let mut app = tests::app(autotile);
+        app.insert_resource(road_rules());
+        let map = TileMap::from_ascii(
+            "
+            #..
+            #..
+            ###
+            ",
+            &default_legend(),
+        )
+        .unwrap();
+        map.apply(&mut app.world);
+        app.update();
+
+        assert_eq!(mask_at(&app, 0, 2), Some(SOUTH));
+        assert_eq!(mask_at(&app, 0, 1), Some(NORTH | SOUTH));
+        assert_eq!(mask_at(&app, 0, 0), Some(NORTH | EAST));
+        assert_eq!(mask_at(&app, 1, 0), Some(EAST | WEST));
+        assert_eq!(mask_at(&app, 2, 0), Some(WEST));
+        assert_eq!(mask_at(&app, 1, 1), None);
+
+        let asset_server = app.world.resource::<AssetServer>().clone();
+        let load = |path: &'static str| asset_server.load::<Image>(path);
+        assert_eq!(sprite_at(&app, 0, 0), load("road_north_east.png"));
+        assert_eq!(sprite_at(&app, 0, 1), load("road_north_south.png"));
+        // Without a variant for the mask the sprite stays.
+        assert_eq!(sprite_at(&app, 1, 0), load("road.png"));
+
+        // Cutting the road updates the tiles around the cut.
+        SetTile {
+            x: 0,
+            y: 1,
+            display: Terrain::Plains.as_display("grass.png"),
+        }
+        .apply(&mut app.world);
+        app.update();
+
+        assert_eq!(mask_at(&app, 0, 1), None);
+        assert_eq!(mask_at(&app, 0, 2), Some(0));
+        assert_eq!(mask_at(&app, 0, 0), Some(EAST));
+        assert_eq!(sprite_at(&app, 0, 1), load("grass.png"));
+        // The old variant is kept as there is none for the new mask.
+        assert_eq!(sprite_at(&app, 0, 0), load("road_north_east.png"));
+    }
+
+    #[test]
+    fn edge_of_the_map() {
+        let mut app = tests::app(autotile);
+        app.insert_resource(
+            AutotileRules::default()
+                .with(
+                    Terrain::Water,
+                    AutotileRule {
+                        edge_is_same: true,
+                        ..default()
+                    },
+                )
+                .with(Terrain::Road, AutotileRule::default()),
+        );
+        let map = TileMap::from_ascii(
+            "
+            ~~
+            ~#
+            ",
+            &default_legend(),
+        )
+        .unwrap();
+        map.apply(&mut app.world);
+        app.update();
+
+        assert_eq!(mask_at(&app, 0, 1), Some(15));
+        assert_eq!(mask_at(&app, 1, 1), Some(NORTH | EAST | WEST));
+        assert_eq!(mask_at(&app, 0, 0), Some(NORTH | SOUTH | WEST));
+        assert_eq!(mask_at(&app, 1, 0), Some(0));
+    }
+}
@@ -0,0 +1,255 @@
+use bevy::{
+    input::mouse::{MouseMotion, MouseScrollUnit, MouseWheel},
+    prelude::*,
+    window::PrimaryWindow,
+};
+
+#[derive(Component)]
+pub struct PrimaryCamera;
+
+#[derive(States, Default, Debug, PartialEq, Eq, Hash, Clone)]
+pub enum DragState {
+    #[default]
+    NotDragging,
+    Dragging,
+}
+
+/// How far the cursor has to move in pixels with the left mouse button held
+/// down before the press counts as dragging instead of a click.
+pub const DRAG_THRESHOLD: f32 = 4.0;
+
+/// How far the cursor moved in pixels since the left mouse button was last
+/// pressed.
+#[derive(Resource, Default, Debug)]
+pub struct DragDistance(pub f32);
+
+impl DragDistance {
+    /// Whether the cursor moved too far for the press to be a click.
+    pub fn is_drag(&self) -> bool {
+        self.0 > DRAG_THRESHOLD
+    }
+}
+
+/// Starts dragging once the cursor moved more than [`DRAG_THRESHOLD`] with
+/// the left mouse button held down, and stops when it is released.
+pub fn set_drag_state(
+    mouse_button: Res<Input<MouseButton>>,
+    mut mouse_motion: EventReader<MouseMotion>,
+    mut drag_distance: ResMut<DragDistance>,
+    drag_state: Res<State<DragState>>,
+    mut next_drag_state: ResMut<NextState<DragState>>,
+) {
+    if mouse_button.just_pressed(MouseButton::Left) {
+        drag_distance.0 = 0.0;
+    }
+
+    let held = mouse_button.pressed(MouseButton::Left);
+    let motion: f32 = mouse_motion.read().map(|event| event.delta.length()).sum();
+    if held || mouse_button.just_released(MouseButton::Left) {
+        drag_distance.0 += motion;
+    }
+
+    if held && drag_distance.is_drag() && *drag_state.get() != DragState::Dragging {
+        next_drag_state.set(DragState::Dragging);
+    }
+
+    if mouse_button.just_released(MouseButton::Left) {
+        next_drag_state.set(DragState::NotDragging);
+    }
+}
+
+/// The world position under the cursor, `None` while the cursor is outside
+/// the window.
+#[derive(Resource, Default, Debug)]
+pub struct CursorWorldPosition(pub Option<Vec2>);
+
+pub fn update_cursor_world_position(
+    mut cursor_world_position: ResMut<CursorWorldPosition>,
+    camera: Query<(&Camera, &GlobalTransform), With<PrimaryCamera>>,
+    window: Query<&Window, With<PrimaryWindow>>,
+) {
+    let (camera, camera_transform) = camera.single();
+    let window = window.single();
+
+    let position = window
+        .cursor_position()
+        .and_then(|cursor_position| camera.viewport_to_world_2d(camera_transform, cursor_position));
+    if cursor_world_position.0 != position {
+        cursor_world_position.0 = position;
+    }
+}
+
+/// Limits and speed of zooming the [`PrimaryCamera`].
+#[derive(Resource, Debug, Clone)]
+pub struct CameraSettings {
+    pub min_scale: f32,
+    pub max_scale: f32,
+    /// How much one step of the scroll wheel or keyboard changes the scale,
+    /// as a fraction of the current scale.
+    pub zoom_step: f32,
+}
+
+impl Default for CameraSettings {
+    fn default() -> Self {
+        Self {
+            min_scale: 0.25,
+            max_scale: 4.0,
+            zoom_step: 0.1,
+        }
+    }
+}
+
+impl CameraSettings {
+    /// Returns the scale after zooming in by `steps`, or out if negative,
+    /// clamped to the allowed range.
+    pub fn zoomed_scale(&self, scale: f32, steps: f32) -> f32 {
+        (scale * (1.0 - self.zoom_step).powf(steps)).clamp(self.min_scale, self.max_scale)
+    }
+}
+
+/// Returns where the camera has to move for `anchor` to stay at the same
+/// place on the screen when the scale changes from `old_scale` to
+/// `new_scale`.
+pub fn zoom_translation(camera: Vec2, anchor: Vec2, old_scale: f32, new_scale: f32) -> Vec2 {
+    anchor + (camera - anchor) * (new_scale / old_scale)
+}
+
+/// Pixel scroll events are counted as a step per this many pixels.
+const PIXELS_PER_ZOOM_STEP: f32 = 16.0;
+
+/// Returns the world position under the cursor with the camera at `camera`,
+/// given where the cursor was in the world with the camera at `rendered`
+/// when it was last drawn. Without a cursor the camera position is used.
+fn zoom_anchor(camera: Vec2, rendered: Vec2, rendered_cursor: Option<Vec2>) -> Vec2 {
+    rendered_cursor.map_or(camera, |cursor| camera + (cursor - rendered))
+}
+
+/// Zooms the [`PrimaryCamera`] with the scroll wheel or the plus and minus
+/// keys, keeping the point under the cursor in place.
+pub fn zoom_camera(
+    settings: Res<CameraSettings>,
+    keys: Res<Input<KeyCode>>,
+    mut mouse_wheel: EventReader<MouseWheel>,
+    window: Query<&Window, With<PrimaryWindow>>,
+    mut camera: Query<
+        (
+            &Camera,
+            &GlobalTransform,
+            &mut Transform,
+            &mut OrthographicProjection,
+        ),
+        With<PrimaryCamera>,
+    >,
+) {
+    let mut steps: f32 = mouse_wheel
+        .read()
+        .map(|event| match event.unit {
+            MouseScrollUnit::Line => event.y,
+            MouseScrollUnit::Pixel => event.y / PIXELS_PER_ZOOM_STEP,
+        })
+        .sum();
+    if keys.any_just_pressed([KeyCode::Equals, KeyCode::NumpadAdd]) {
+        steps += 1.0;
+    }
+    if keys.any_just_pressed([KeyCode::Minus, KeyCode::NumpadSubtract]) {
+        steps -= 1.0;
+    }
+
+    if steps == 0.0 {
+        return;
+    }
+
+    let (camera, global_transform, mut transform, mut projection) = camera.single_mut();
+    let old_scale = projection.scale;
+    let new_scale = settings.zoomed_scale(old_scale, steps);
+    if new_scale == old_scale {
+        return;
+    }
+
+    // The global transform is only updated after this system runs, so the
+    // cursor is found with the camera where it was last drawn and then moved
+    // along with any panning done since.
+    let camera_position = transform.translation.truncate();
+    let rendered_cursor = window
+        .get_single()
+        .ok()
+        .and_then(Window::cursor_position)
+        .and_then(|cursor| camera.viewport_to_world_2d(global_transform, cursor));
+    let anchor = zoom_anchor(
+        camera_position,
+        global_transform.translation().truncate(),
+        rendered_cursor,
+    );
+    let translation = zoom_translation(camera_position, anchor, old_scale, new_scale);
+    transform.translation = translation.extend(transform.translation.z);
+    projection.scale = new_scale;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn zoomed_scale_is_clamped() {
+        let settings = CameraSettings::default();
+
+        assert_eq!(settings.zoomed_scale(1.0, 0.0), 1.0);
+        assert!((settings.zoomed_scale(1.0, 1.0) - 0.9).abs() < 1e-6);
+        assert!((settings.zoomed_scale(1.0, -1.0) - 1.0 / 0.9).abs() < 1e-6);
+        assert!((settings.zoomed_scale(1.0, 2.0) - 0.81).abs() < 1e-6);
+        assert!((settings.zoomed_scale(0.81, -2.0) - 1.0).abs() < 1e-6);
+
+        assert_eq!(settings.zoomed_scale(1.0, 100.0), settings.min_scale);
+        assert_eq!(settings.zoomed_scale(1.0, -100.0), settings.max_scale);
+        assert_eq!(settings.zoomed_scale(0.25, 1.0), 0.25);
+        assert_eq!(settings.zoomed_scale(4.0, -1.0), 4.0);
+
+        let narrow = CameraSettings {
+            min_scale: 0.5,
+            max_scale: 2.0,
+            zoom_step: 0.5,
+        };
+        assert_eq!(narrow.zoomed_scale(1.0, 1.0), 0.5);
+        assert_eq!(narrow.zoomed_scale(1.0, 2.0), 0.5);
+        assert_eq!(narrow.zoomed_scale(1.0, -1.0), 2.0);
+        assert_eq!(narrow.zoomed_scale(1.0, -0.5), 2.0_f32.sqrt());
+    }
+
+    #[test]
+    fn zoom_keeps_the_anchor_in_place() {
+        let camera = Vec2::new(100.0, -40.0);
+        let anchor = Vec2::new(160.0, 20.0);
+
+        for (old_scale, new_scale) in [(1.0, 0.5), (1.0, 2.0), (0.25, 4.0), (3.0, 3.0)] {
+            let moved = zoom_translation(camera, anchor, old_scale, new_scale);
+            // The anchor is the same number of pixels away from the center
+            // of the screen before and after.
+            let before = (anchor - camera) / old_scale;
+            let after = (anchor - moved) / new_scale;
+            assert!((before - after).length() < 1e-4, "{before} != {after}");
+        }
+
+        assert_eq!(
+            zoom_translation(camera, anchor, 1.0, 0.5),
+            Vec2::new(130.0, -10.0)
+        );
+        assert_eq!(zoom_translation(camera, camera, 1.0, 0.5), camera);
+    }
+
+    #[test]
+    fn zoom_anchor_follows_the_camera() {
+        let rendered = Vec2::new(10.0, 10.0);
+        let cursor = Vec2::new(25.0, 5.0);
+
+        assert_eq!(zoom_anchor(rendered, rendered, Some(cursor)), cursor);
+        // Panned since the last frame was drawn.
+        assert_eq!(
+            zoom_anchor(Vec2::new(30.0, 0.0), rendered, Some(cursor)),
+            Vec2::new(45.0, -5.0)
+        );
+        assert_eq!(
+            zoom_anchor(Vec2::new(30.0, 0.0), rendered, None),
+            Vec2::new(30.0, 0.0)
+        );
+    }
+}
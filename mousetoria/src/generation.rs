@@ -0,0 +1,289 @@
+use crate::map::{Feature, Terrain, TileMap};
+
+/// Controls how [`TileMap::generate`] shapes the map.
+#[derive(Debug, Clone)]
+pub struct GenerationConfig {
+    /// The size of the largest features in tiles.
+    pub feature_size: f32,
+    /// How many layers of finer noise are added on top of the largest
+    /// features.
+    pub octaves: u32,
+    /// Noise below this becomes water.
+    pub water_level: f32,
+    /// Noise below this, and above the water level, becomes plains.
+    pub forest_level: f32,
+    /// Noise below this, and above the forest level, becomes forest. The rest
+    /// becomes mountains.
+    pub mountain_level: f32,
+    /// How many towns to place on the plains.
+    pub towns: usize,
+}
+
+impl Default for GenerationConfig {
+    fn default() -> Self {
+        Self {
+            feature_size: 8.0,
+            octaves: 3,
+            water_level: 0.35,
+            forest_level: 0.6,
+            mountain_level: 0.7,
+            towns: 4,
+        }
+    }
+}
+
+impl TileMap {
+    /// Generates a map from noise, placing towns on the plains and connecting
+    /// them with road features where they can be reached from each other. The
+    /// same seed and config always generate the same map.
+    pub fn generate(width: usize, height: usize, seed: u64, config: &GenerationConfig) -> Self {
+        let mut map = TileMap::new(width, height);
+        for y in 0..height {
+            for x in 0..width {
+                let noise = fractal_noise(seed, x as f32, y as f32, config);
+                let terrain = if noise < config.water_level {
+                    Terrain::Water
+                } else if noise < config.forest_level {
+                    Terrain::Plains
+                } else if noise < config.mountain_level {
+                    Terrain::Forest
+                } else {
+                    Terrain::Mountain
+                };
+                map[(x, y)] = terrain.as_display(terrain.default_sprite());
+            }
+        }
+
+        let towns = map.place_towns(seed, config.towns);
+        for pair in towns.windows(2) {
+            map.connect_with_road(pair[0], pair[1]);
+        }
+
+        map
+    }
+
+    fn place_towns(&mut self, seed: u64, count: usize) -> Vec<(usize, usize)> {
+        let mut plains: Vec<(usize, usize)> = (0..self.height)
+            .flat_map(|y| (0..self.width).map(move |x| (x, y)))
+            .filter(|&position| self[position].terrain == Terrain::Plains)
+            .collect();
+
+        let mut rng = SplitMix64(seed ^ 0x746f_776e);
+        let mut towns = Vec::with_capacity(count);
+        while towns.len() < count && !plains.is_empty() {
+            let position = plains.swap_remove((rng.next() % plains.len() as u64) as usize);
+            self[position] = Terrain::Town.as_display(Terrain::Town.default_sprite());
+            towns.push(position);
+        }
+
+        towns
+    }
+
+    fn connect_with_road(&mut self, from: (usize, usize), to: (usize, usize)) {
+        let Some(path) = self.find_path(from, to, &|terrain| terrain.move_cost()) else {
+            return;
+        };
+
+        for position in path {
+            if self[position].terrain != Terrain::Town {
+                let road = Feature::Road.as_display(Feature::Road.default_sprite());
+                self.set_feature(position, Some(road));
+            }
+        }
+    }
+}
+
+/// Sums octaves of value noise, returning a value between 0 and 1.
+fn fractal_noise(seed: u64, x: f32, y: f32, config: &GenerationConfig) -> f32 {
+    let mut total = 0.0;
+    let mut amplitude = 1.0;
+    let mut max_total = 0.0;
+    let mut frequency = 1.0 / config.feature_size.max(1.0);
+    for octave in 0..config.octaves.max(1) {
+        total += value_noise(
+            seed.wrapping_add(octave as u64),
+            x * frequency,
+            y * frequency,
+        ) * amplitude;
+        max_total += amplitude;
+        amplitude *= 0.5;
+        frequency *= 2.0;
+    }
+
+    total / max_total
+}
+
+/// Interpolates smoothly between random values at the integer coordinates.
+fn value_noise(seed: u64, x: f32, y: f32) -> f32 {
+    let (x0, y0) = (x.floor(), y.floor());
+    let (tx, ty) = (smoothstep(x - x0), smoothstep(y - y0));
+    let (x0, y0) = (x0 as i64, y0 as i64);
+
+    let lerp = |a: f32, b: f32, t: f32| a + (b - a) * t;
+    let top = lerp(lattice(seed, x0, y0), lattice(seed, x0 + 1, y0), tx);
+    let bottom = lerp(lattice(seed, x0, y0 + 1), lattice(seed, x0 + 1, y0 + 1), tx);
+    lerp(top, bottom, ty)
+}
+
+fn smoothstep(t: f32) -> f32 {
+    t * t * (3.0 - 2.0 * t)
+}
+
+/// Returns a random value between 0 and 1 for the integer coordinates.
+fn lattice(seed: u64, x: i64, y: i64) -> f32 {
+    let hash = SplitMix64(
+        seed ^ (x as u64).wrapping_mul(0x9e37_79b9_7f4a_7c15) ^ (y as u64).rotate_left(32),
+    )
+    .next();
+    (hash >> 40) as f32 / (1u64 << 24) as f32
+}
+
+/// A small deterministic random number generator.
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::*;
+    use crate::ascii::default_legend;
+
+    fn terrains(map: &TileMap) -> HashSet<Terrain> {
+        map.tiles
+            .iter()
+            .flatten()
+            .map(|display| display.terrain)
+            .collect()
+    }
+
+    fn roads(map: &TileMap) -> usize {
+        map.features
+            .iter()
+            .flatten()
+            .filter(|feature| matches!(feature, Some(display) if display.feature == Feature::Road))
+            .count()
+    }
+
+    #[test]
+    fn snapshot() {
+        let map = TileMap::generate(16, 6, 42, &GenerationConfig::default());
+        let expected = "
+            ...T...~~~T.....
+            .......~~~......
+            .......~~~~.....
+            ..T....~~~~~..T~
+            ff.........~~~.~
+            ^^f........~~~~~
+            ";
+        let legend = default_legend();
+        assert_eq!(
+            map.to_ascii(&legend),
+            TileMap::from_ascii(expected, &legend)
+                .unwrap()
+                .to_ascii(&legend)
+        );
+    }
+
+    #[test]
+    fn same_seed_same_map() {
+        let config = GenerationConfig::default();
+        let map = TileMap::generate(40, 30, 7, &config);
+        let again = TileMap::generate(40, 30, 7, &config);
+
+        assert_eq!((map.width, map.height), (40, 30));
+        assert_eq!(map.tiles.len(), 30);
+        assert!(map.tiles.iter().all(|row| row.len() == 40));
+        let legend = default_legend();
+        assert_eq!(map.to_ascii(&legend), again.to_ascii(&legend));
+        for y in 0..30 {
+            for x in 0..40 {
+                assert_eq!(map[(x, y)].sprite, again[(x, y)].sprite);
+                assert_eq!(
+                    map.feature((x, y)).map(|display| display.feature),
+                    again.feature((x, y)).map(|display| display.feature)
+                );
+            }
+        }
+
+        let other = TileMap::generate(40, 30, 8, &config);
+        assert_ne!(map.to_ascii(&legend), other.to_ascii(&legend));
+    }
+
+    #[test]
+    fn every_terrain_is_generated() {
+        let config = GenerationConfig::default();
+        for seed in 0..5 {
+            let map = TileMap::generate(48, 48, seed, &config);
+            assert_eq!(
+                terrains(&map),
+                HashSet::from([
+                    Terrain::Water,
+                    Terrain::Plains,
+                    Terrain::Forest,
+                    Terrain::Mountain,
+                    Terrain::Town,
+                ]),
+                "seed {}",
+                seed
+            );
+
+            let towns = map
+                .tiles
+                .iter()
+                .flatten()
+                .filter(|display| display.terrain == Terrain::Town)
+                .count();
+            assert_eq!(towns, config.towns, "seed {}", seed);
+            assert!(roads(&map) > 0, "seed {}", seed);
+        }
+    }
+
+    #[test]
+    fn levels_pick_the_terrain() {
+        let only_plains = GenerationConfig {
+            water_level: 0.0,
+            forest_level: 2.0,
+            towns: 0,
+            ..GenerationConfig::default()
+        };
+        let map = TileMap::generate(16, 16, 3, &only_plains);
+        assert_eq!(terrains(&map), HashSet::from([Terrain::Plains]));
+        assert_eq!(roads(&map), 0);
+
+        let only_water = GenerationConfig {
+            water_level: 2.0,
+            ..GenerationConfig::default()
+        };
+        let map = TileMap::generate(16, 16, 3, &only_water);
+        assert_eq!(terrains(&map), HashSet::from([Terrain::Water]));
+    }
+
+    #[test]
+    fn towns_are_connected_by_roads() {
+        // Two towns on plains everywhere are joined by a straight road.
+        let config = GenerationConfig {
+            water_level: 0.0,
+            forest_level: 2.0,
+            towns: 2,
+            ..GenerationConfig::default()
+        };
+        let map = TileMap::generate(12, 12, 11, &config);
+
+        let towns: Vec<_> = (0..12)
+            .flat_map(|y| (0..12).map(move |x| (x, y)))
+            .filter(|&position| map[position].terrain == Terrain::Town)
+            .collect();
+        assert_eq!(towns.len(), 2);
+        assert_eq!(roads(&map), map.grid.distance(towns[0], towns[1]) - 1);
+    }
+}
@@ -1 +1,11 @@
+pub mod ascii;
+pub mod autotile;
+pub mod camera;
+pub mod generation;
 pub mod map;
+pub mod pathfinding;
+pub mod picking;
+pub mod region;
+pub mod save;
+pub mod selection;
+pub mod unit;
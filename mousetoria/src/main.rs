@@ -1,11 +1,23 @@
-use std::collections::HashMap;
-
-use bevy::{input::mouse::MouseMotion, prelude::*, window::PrimaryWindow};
-
-use mousetoria::map::{Neighbors, Terrain, Tile, TileMap, TILE_SIZE};
-
-#[derive(Component)]
-struct PrimaryCamera;
+use bevy::{input::mouse::MouseMotion, prelude::*};
+
+use mousetoria::{
+    autotile::{autotile, AutotileRules},
+    camera::{
+        set_drag_state, update_cursor_world_position, zoom_camera, CameraSettings,
+        CursorWorldPosition, DragDistance, DragState, PrimaryCamera,
+    },
+    map::{
+        tile_rect, update_neighbors, update_tile_index, MapChanged, MapConfig, Terrain,
+        TerrainRules, Tile, TileIndex, TileMap,
+    },
+    picking::{pick_tile, HoveredTile, TileClicked, TileHovered},
+    region::{update_regions, Regions},
+    selection::{select_tiles, Selected, SelectedTile},
+    unit::{
+        forget_despawned_units, highlight_movement_range, TileOccupancy, UnitMoveRejected,
+        UnitMoved,
+    },
+};
 
 type QueryPrimaryCameraTransform<'world, 'state, 'transform> =
     Query<'world, 'state, &'transform mut Transform, (With<Camera2d>, With<PrimaryCamera>)>;
@@ -21,58 +33,27 @@ fn add_tilemap(mut commands: Commands) {
     commands.add(map);
 }
 
-fn update_neighbors(mut tiles_query: Query<(Entity, &Tile, &mut Neighbors)>) {
-    let tiles = {
-        let _build_tiles_span = info_span!("build_tiles").entered();
-
-        let mut tiles = HashMap::new();
-        for (entity, tile, _) in &mut tiles_query {
-            tiles.insert((tile.x, tile.y), entity);
-        }
-        tiles
-    };
-
-    let _update_neighbors_span = info_span!("update_neighbors").entered();
-
-    tiles_query
-        .par_iter_mut()
-        .for_each(|(_, tile, mut neighbors)| {
-            neighbors.update_neighbors((tile.x, tile.y), &tiles);
-        });
-}
-
 fn debug_tiles(
     mut gizmos: Gizmos,
-    tilemap_query: Query<(&Tile, &GlobalTransform)>,
-    camera: Query<(&Camera, &GlobalTransform), With<PrimaryCamera>>,
-    window: Query<&Window, With<PrimaryWindow>>,
+    hovered_tile: Res<HoveredTile>,
+    map_config: Res<MapConfig>,
+    tilemap_query: Query<(Entity, &Tile, Has<Selected>)>,
 ) {
-    let (camera, camera_transform) = camera.single();
-    let window = window.single();
-
-    let cursor_position = window
-        .cursor_position()
-        .and_then(|cursor_position| camera.viewport_to_world_2d(camera_transform, cursor_position));
-
-    for (tile, transform) in &tilemap_query {
-        let (scale, _, translation) = transform.to_scale_rotation_translation();
+    for (entity, tile, selected) in &tilemap_query {
         const PADDING: f32 = 0.95;
-        let size = TILE_SIZE * scale.truncate() * PADDING;
-        let color = match cursor_position {
-            Some(cursor_position) => {
-                let tile_position = translation.truncate();
-                let hitbox = Rect::from_center_size(tile_position, size);
-                if hitbox.contains(cursor_position) {
-                    Color::RED
-                } else {
-                    tile.terrain.debug_color()
-                }
-            }
-            None => tile.terrain.debug_color(),
+        let center = map_config.grid.tile_to_world(tile.x, tile.y);
+        let size = tile_rect(tile.x, tile.y).size() * PADDING;
+        let color = if hovered_tile.0 == Some(entity) {
+            Color::RED
+        } else {
+            tile.terrain.debug_color()
         };
 
-        gizmos.rect_2d(translation.truncate(), 0.0, size - 4.0, color);
-        // gizmos.circle_2d(translation.truncate(), radius, color);
+        gizmos.rect_2d(center, 0.0, size - 4.0, color);
+        if selected {
+            gizmos.rect_2d(center, 0.0, size, Color::ORANGE);
+        }
+        // gizmos.circle_2d(center, radius, color);
     }
 }
 
@@ -106,21 +87,11 @@ fn move_camera(
     query.single_mut().translation += translation;
 }
 
-fn set_drag_state(
-    mouse_button: Res<Input<MouseButton>>,
-    mut drag_state: ResMut<NextState<DragState>>,
+fn drag_camera(
+    mut query: Query<(&mut Transform, &OrthographicProjection), With<PrimaryCamera>>,
+    mut mouse_motion: EventReader<MouseMotion>,
 ) {
-    if mouse_button.just_pressed(MouseButton::Left) {
-        drag_state.set(DragState::Dragging);
-    }
-
-    if mouse_button.just_released(MouseButton::Left) {
-        drag_state.set(DragState::NotDragging);
-    }
-}
-
-fn drag_camera(mut query: QueryPrimaryCameraTransform, mut mouse_motion: EventReader<MouseMotion>) {
-    let mut camera = query.single_mut();
+    let (mut camera, projection) = query.single_mut();
 
     for event in mouse_motion.read() {
         let translation = {
@@ -129,22 +100,32 @@ fn drag_camera(mut query: QueryPrimaryCameraTransform, mut mouse_motion: EventRe
             v
         };
 
-        camera.translation += translation;
+        // Move by the same number of world units as the cursor moved pixels
+        // on the screen, whatever the zoom.
+        camera.translation += translation * projection.scale;
     }
 }
 
-#[derive(States, Default, Debug, PartialEq, Eq, Hash, Clone)]
-enum DragState {
-    #[default]
-    NotDragging,
-    Dragging,
-}
-
 fn main() {
     App::new()
         .add_plugins(DefaultPlugins.set(ImagePlugin::default_nearest()))
         .insert_resource(ClearColor(Color::BLACK))
         .insert_resource(Msaa::Sample8)
+        .init_resource::<TerrainRules>()
+        .init_resource::<TileIndex>()
+        .init_resource::<AutotileRules>()
+        .init_resource::<Regions>()
+        .init_resource::<TileOccupancy>()
+        .add_event::<UnitMoved>()
+        .add_event::<UnitMoveRejected>()
+        .add_event::<MapChanged>()
+        .init_resource::<CursorWorldPosition>()
+        .init_resource::<CameraSettings>()
+        .init_resource::<DragDistance>()
+        .init_resource::<HoveredTile>()
+        .init_resource::<SelectedTile>()
+        .add_event::<TileHovered>()
+        .add_event::<TileClicked>()
         .add_state::<DragState>()
         .add_systems(Startup, (add_camera, add_tilemap))
         .add_systems(
@@ -154,8 +135,17 @@ fn main() {
                 (
                     drag_camera.run_if(state_exists_and_equals(DragState::Dragging)),
                     move_camera,
+                    zoom_camera,
                 ),
-                update_neighbors,
+                update_tile_index,
+                update_neighbors.run_if(on_event::<MapChanged>()),
+                autotile.run_if(on_event::<MapChanged>()),
+                update_regions.run_if(on_event::<MapChanged>()),
+                update_cursor_world_position,
+                pick_tile,
+                select_tiles,
+                forget_despawned_units,
+                highlight_movement_range,
                 debug_tiles,
             )
                 .chain(),
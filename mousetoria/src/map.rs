@@ -1,13 +1,12 @@
 use std::{
-    collections::HashMap,
+    collections::{HashMap, HashSet},
     ops::{Index, IndexMut},
 };
 
 use bevy::{ecs::system::Command, prelude::*};
+use serde::{Deserialize, Serialize};
 
-pub struct Region {}
-
-#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Serialize, Deserialize)]
 pub enum Terrain {
     City,
     Town,
@@ -19,6 +18,32 @@ pub enum Terrain {
 }
 
 impl Terrain {
+    pub const ALL: [Terrain; 7] = [
+        Terrain::City,
+        Terrain::Town,
+        Terrain::Forest,
+        Terrain::Mountain,
+        Terrain::Water,
+        Terrain::Plains,
+        Terrain::Road,
+    ];
+
+    /// The cost of moving onto a tile of this terrain, or `None` if it cannot
+    /// be entered. [`TerrainRules`] may override it.
+    pub fn move_cost(&self) -> Option<f32> {
+        use Terrain::*;
+        match self {
+            Road => Some(0.5),
+            City | Town | Plains => Some(1.0),
+            Forest => Some(2.0),
+            Mountain | Water => None,
+        }
+    }
+
+    pub fn is_passable(&self) -> bool {
+        self.move_cost().is_some()
+    }
+
     pub fn debug_color(&self) -> Color {
         use Terrain::*;
         match self {
@@ -32,6 +57,19 @@ impl Terrain {
         }
     }
 
+    pub fn default_sprite(&self) -> &'static str {
+        use Terrain::*;
+        match self {
+            City => "city.png",
+            Town => "town.png",
+            Forest => "forest.png",
+            Mountain => "mountain.png",
+            Water => "water.png",
+            Plains => "grass.png",
+            Road => "road.png",
+        }
+    }
+
     pub fn as_display(self, sprite: impl Into<String>) -> TerrainDisplay {
         TerrainDisplay {
             terrain: self,
@@ -40,55 +78,415 @@ impl Terrain {
     }
 }
 
+/// Something built or grown on top of the terrain of a tile, drawn above it.
+#[derive(Component, Copy, Clone, Eq, PartialEq, Hash, Debug, Serialize, Deserialize)]
+pub enum Feature {
+    Road,
+    Forest,
+}
+
+impl Feature {
+    pub const ALL: [Feature; 2] = [Feature::Road, Feature::Forest];
+
+    /// The cost of moving onto a tile with this feature when its terrain
+    /// costs `base`. A road can be used whatever the terrain below it while a
+    /// forest slows down movement on terrain which can be entered.
+    pub fn move_cost(&self, base: Option<f32>) -> Option<f32> {
+        match self {
+            Feature::Road => Some(0.5),
+            Feature::Forest => base.map(|cost| cost + 1.0),
+        }
+    }
+
+    pub fn default_sprite(&self) -> &'static str {
+        match self {
+            Feature::Road => "road.png",
+            Feature::Forest => "forest.png",
+        }
+    }
+
+    pub fn as_display(self, sprite: impl Into<String>) -> FeatureDisplay {
+        FeatureDisplay {
+            feature: self,
+            sprite: sprite.into(),
+        }
+    }
+}
+
+/// Movement costs used instead of [`Terrain::move_cost`] for some terrains,
+/// read on every path query so that changes apply without respawning tiles.
+#[derive(Resource, Default, Clone, Debug)]
+pub struct TerrainRules {
+    costs: HashMap<Terrain, Option<f32>>,
+}
+
+impl TerrainRules {
+    /// Overrides the cost of moving onto the terrain, `None` making it
+    /// impassable.
+    pub fn set_move_cost(&mut self, terrain: Terrain, cost: Option<f32>) {
+        self.costs.insert(terrain, cost);
+    }
+
+    /// Restores the default cost of the terrain.
+    pub fn reset_move_cost(&mut self, terrain: Terrain) {
+        self.costs.remove(&terrain);
+    }
+
+    pub fn move_cost(&self, terrain: Terrain) -> Option<f32> {
+        match self.costs.get(&terrain) {
+            Some(&cost) => cost,
+            None => terrain.move_cost(),
+        }
+    }
+
+    pub fn is_passable(&self, terrain: Terrain) -> bool {
+        self.move_cost(terrain).is_some()
+    }
+}
+
+/// The shape of the tiles and how they are laid out.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Default, Serialize, Deserialize)]
+pub enum GridKind {
+    /// Square tiles in rows and columns, each touching eight others.
+    #[default]
+    Square,
+    /// Hexagons with a corner at the top, every odd row shifted half a tile
+    /// east, each touching six others.
+    HexPointyTop,
+}
+
+impl GridKind {
+    /// The directions in which tiles have [`Neighbors`].
+    pub fn directions(&self) -> &'static [Direction] {
+        match self {
+            GridKind::Square => &Direction::ALL,
+            GridKind::HexPointyTop => &Direction::HEX,
+        }
+    }
+
+    /// The directions in which paths move from tile to tile.
+    pub fn movement_directions(&self) -> &'static [Direction] {
+        match self {
+            GridKind::Square => &Direction::ORTHOGONAL,
+            GridKind::HexPointyTop => &Direction::HEX,
+        }
+    }
+
+    /// The fewest steps in the movement directions between the tiles.
+    pub fn distance(&self, (ax, ay): (usize, usize), (bx, by): (usize, usize)) -> usize {
+        match self {
+            GridKind::Square => ax.abs_diff(bx) + ay.abs_diff(by),
+            GridKind::HexPointyTop => {
+                let (aq, ar) = hex_offset_to_axial(ax, ay);
+                let (bq, br) = hex_offset_to_axial(bx, by);
+                let (dq, dr) = (aq - bq, ar - br);
+                ((dq.abs() + dr.abs() + (dq + dr).abs()) / 2) as usize
+            }
+        }
+    }
+
+    /// Returns the world position of the center of the tile.
+    pub fn tile_to_world(&self, x: usize, y: usize) -> Vec2 {
+        match self {
+            GridKind::Square => tile_to_world(x, y),
+            GridKind::HexPointyTop => Vec2::new(
+                (x as f32 + 0.5 * (y % 2) as f32) * TILE_WORLD_SIZE,
+                y as f32 * HEX_ROW_HEIGHT,
+            ),
+        }
+    }
+}
+
+/// Converts the coordinates of a hex in a [`GridKind::HexPointyTop`] map to
+/// axial coordinates, in which the neighbors differ by one or two of
+/// `(1, 0)`, `(0, 1)` and `(1, -1)`.
+fn hex_offset_to_axial(x: usize, y: usize) -> (i64, i64) {
+    let (x, y) = (x as i64, y as i64);
+    (x - (y - (y & 1)) / 2, y)
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
 pub enum Direction {
     North,
+    NorthEast,
     East,
+    SouthEast,
     South,
+    SouthWest,
     West,
+    NorthWest,
+}
+
+impl Direction {
+    /// Every direction clockwise starting from north.
+    pub const ALL: [Direction; 8] = [
+        Direction::North,
+        Direction::NorthEast,
+        Direction::East,
+        Direction::SouthEast,
+        Direction::South,
+        Direction::SouthWest,
+        Direction::West,
+        Direction::NorthWest,
+    ];
+
+    /// North, east, south and west.
+    pub const ORTHOGONAL: [Direction; 4] = [
+        Direction::North,
+        Direction::East,
+        Direction::South,
+        Direction::West,
+    ];
+
+    /// The directions of the neighbors of a hex clockwise starting from
+    /// north-east, see [`GridKind::HexPointyTop`].
+    pub const HEX: [Direction; 6] = [
+        Direction::NorthEast,
+        Direction::East,
+        Direction::SouthEast,
+        Direction::SouthWest,
+        Direction::West,
+        Direction::NorthWest,
+    ];
+
+    /// Returns the coordinates one step in this direction on the grid, or
+    /// `None` if the step would go below zero or there is no neighbor in this
+    /// direction.
+    pub fn step_on(&self, grid: GridKind, (x, y): (usize, usize)) -> Option<(usize, usize)> {
+        use self::Direction::*;
+        match grid {
+            GridKind::Square => self.step((x, y)),
+            GridKind::HexPointyTop => {
+                // Odd rows are shifted half a tile east, so going diagonally
+                // from them reaches one column further east than from even
+                // rows.
+                let odd = y % 2 == 1;
+                match self {
+                    North | South => None,
+                    NorthEast if !odd => North.step((x, y)),
+                    NorthWest if odd => North.step((x, y)),
+                    SouthEast if !odd => South.step((x, y)),
+                    SouthWest if odd => South.step((x, y)),
+                    _ => self.step((x, y)),
+                }
+            }
+        }
+    }
+
+    /// Returns the coordinates one step in this direction, or `None` if the
+    /// step would go below zero.
+    pub fn step(&self, (x, y): (usize, usize)) -> Option<(usize, usize)> {
+        use self::Direction::*;
+        let x = match self {
+            NorthEast | East | SouthEast => x + 1,
+            North | South => x,
+            SouthWest | West | NorthWest => x.checked_sub(1)?,
+        };
+        let y = match self {
+            NorthWest | North | NorthEast => y + 1,
+            West | East => y,
+            SouthEast | South | SouthWest => y.checked_sub(1)?,
+        };
+
+        Some((x, y))
+    }
 }
 
-#[derive(Component, Default, Debug)]
+#[derive(Component, Default, Debug, PartialEq, Eq)]
 pub struct Neighbors {
     pub north: Option<Entity>,
+    pub north_east: Option<Entity>,
     pub east: Option<Entity>,
+    pub south_east: Option<Entity>,
     pub south: Option<Entity>,
+    pub south_west: Option<Entity>,
     pub west: Option<Entity>,
+    pub north_west: Option<Entity>,
 }
 
 impl Neighbors {
+    pub fn get(&self, direction: Direction) -> Option<Entity> {
+        *self.field(direction)
+    }
+
+    /// Returns the existing neighbors clockwise starting from north.
+    pub fn iter(&self) -> impl Iterator<Item = (Direction, Entity)> + '_ {
+        Direction::ALL
+            .into_iter()
+            .filter_map(|direction| Some((direction, self.get(direction)?)))
+    }
+
     /// Returns the direction in which the given entity is a neighbor, if any.
     pub fn is_neighbor(&self, other: Entity) -> Option<Direction> {
-        if self.north == Some(other) {
-            Some(Direction::North)
-        } else if self.east == Some(other) {
-            Some(Direction::East)
-        } else if self.south == Some(other) {
-            Some(Direction::South)
-        } else if self.west == Some(other) {
-            Some(Direction::West)
-        } else {
-            None
-        }
+        self.iter()
+            .find(|&(_, entity)| entity == other)
+            .map(|(direction, _)| direction)
     }
 
     pub fn update_neighbors(
         &mut self,
-        (x, y): (usize, usize),
-        map: &HashMap<(usize, usize), Entity>,
+        position: (usize, usize),
+        grid: GridKind,
+        tile_index: &TileIndex,
     ) {
-        self.north = map.get(&(x, y + 1)).copied();
-        self.east = map.get(&(x + 1, y)).copied();
+        for direction in Direction::ALL {
+            *self.field_mut(direction) = direction
+                .step_on(grid, position)
+                .and_then(|(x, y)| tile_index.get(x, y));
+        }
+    }
+
+    fn field(&self, direction: Direction) -> &Option<Entity> {
+        use self::Direction::*;
+        match direction {
+            North => &self.north,
+            NorthEast => &self.north_east,
+            East => &self.east,
+            SouthEast => &self.south_east,
+            South => &self.south,
+            SouthWest => &self.south_west,
+            West => &self.west,
+            NorthWest => &self.north_west,
+        }
+    }
+
+    fn field_mut(&mut self, direction: Direction) -> &mut Option<Entity> {
+        use self::Direction::*;
+        match direction {
+            North => &mut self.north,
+            NorthEast => &mut self.north_east,
+            East => &mut self.east,
+            SouthEast => &mut self.south_east,
+            South => &mut self.south,
+            SouthWest => &mut self.south_west,
+            West => &mut self.west,
+            NorthWest => &mut self.north_west,
+        }
+    }
+}
+
+/// The tile entities by their coordinates.
+#[derive(Resource, Default, Debug)]
+pub struct TileIndex {
+    entities: HashMap<(usize, usize), Entity>,
+    positions: HashMap<Entity, (usize, usize)>,
+}
+
+impl TileIndex {
+    pub fn get(&self, x: usize, y: usize) -> Option<Entity> {
+        self.entities.get(&(x, y)).copied()
+    }
+
+    pub fn neighbors_of(&self, x: usize, y: usize, grid: GridKind) -> Neighbors {
+        let mut neighbors = Neighbors::default();
+        neighbors.update_neighbors((x, y), grid, self);
+        neighbors
+    }
+
+    pub fn len(&self) -> usize {
+        self.entities.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entities.is_empty()
+    }
+
+    /// Iterates over the tile entities and their coordinates.
+    pub fn iter(&self) -> impl Iterator<Item = (Entity, (usize, usize))> + '_ {
+        self.positions
+            .iter()
+            .map(|(&entity, &position)| (entity, position))
+    }
+
+    /// Records the tile at the coordinates, forgetting where it was before.
+    pub fn insert(&mut self, entity: Entity, position: (usize, usize)) {
+        self.remove(entity);
+        if let Some(replaced) = self.entities.insert(position, entity) {
+            self.positions.remove(&replaced);
+        }
+        self.positions.insert(entity, position);
+    }
+
+    /// Forgets the tile, returning where it was.
+    pub fn remove(&mut self, entity: Entity) -> Option<(usize, usize)> {
+        let position = self.positions.remove(&entity)?;
+        self.entities.remove(&position);
+        Some(position)
+    }
+}
+
+/// Sent when tiles were spawned, moved or despawned, listing the coordinates
+/// whose tile changed.
+#[derive(Event, Debug, Clone)]
+pub struct MapChanged {
+    pub positions: Vec<(usize, usize)>,
+}
+
+/// Keeps the [`TileIndex`] in sync with the tiles which were spawned, moved or
+/// despawned since the last run, sending [`MapChanged`] if any were.
+pub fn update_tile_index(
+    mut tile_index: ResMut<TileIndex>,
+    changed_tiles: Query<(Entity, &Tile), Changed<Tile>>,
+    mut removed_tiles: RemovedComponents<Tile>,
+    mut map_changed: EventWriter<MapChanged>,
+) {
+    let mut positions = Vec::new();
+    for entity in removed_tiles.read() {
+        if tile_index.positions.contains_key(&entity) {
+            positions.extend(tile_index.remove(entity));
+        }
+    }
+
+    for (entity, tile) in &changed_tiles {
+        if tile_index.positions.get(&entity) != Some(&(tile.x, tile.y)) {
+            positions.extend(tile_index.remove(entity));
+            tile_index.insert(entity, (tile.x, tile.y));
+            positions.push((tile.x, tile.y));
+        }
+    }
+
+    if !positions.is_empty() {
+        map_changed.send(MapChanged { positions });
+    }
+}
+
+/// Updates the [`Neighbors`] of the tiles at and around the coordinates in the
+/// [`MapChanged`] events, leaving the ones which stayed the same untouched.
+pub fn update_neighbors(
+    tile_index: Res<TileIndex>,
+    map_config: Option<Res<MapConfig>>,
+    mut map_changed: EventReader<MapChanged>,
+    mut neighbors_query: Query<&mut Neighbors>,
+) {
+    let _update_neighbors_span = info_span!("update_neighbors").entered();
+
+    let grid = map_config.map_or_else(GridKind::default, |config| config.grid);
 
-        if y == 0 {
-            self.south = None;
-        } else {
-            self.south = map.get(&(x, y - 1)).copied();
+    // The neighbors of a hex are among the eight around the same coordinates
+    // on a square grid.
+    let mut positions = HashSet::new();
+    for event in map_changed.read() {
+        for &position in event.positions.iter() {
+            positions.insert(position);
+            positions.extend(
+                Direction::ALL
+                    .into_iter()
+                    .filter_map(|direction| direction.step(position)),
+            );
         }
+    }
+
+    for (x, y) in positions {
+        let Some(mut neighbors) = tile_index
+            .get(x, y)
+            .and_then(|entity| neighbors_query.get_mut(entity).ok())
+        else {
+            continue;
+        };
 
-        if x == 0 {
-            self.west = None;
-        } else {
-            self.west = map.get(&(x - 1, y)).copied();
+        let updated = tile_index.neighbors_of(x, y, grid);
+        if *neighbors != updated {
+            *neighbors = updated;
         }
     }
 }
@@ -98,6 +496,19 @@ pub struct Tile {
     pub x: usize,
     pub y: usize,
     pub terrain: Terrain,
+    pub feature: Option<Feature>,
+}
+
+impl Tile {
+    /// The cost of moving onto the tile, combining the cost of its terrain in
+    /// the rules with its feature.
+    pub fn move_cost(&self, rules: &TerrainRules) -> Option<f32> {
+        let base = rules.move_cost(self.terrain);
+        match self.feature {
+            Some(feature) => feature.move_cost(base),
+            None => base,
+        }
+    }
 }
 
 #[derive(Bundle)]
@@ -108,16 +519,26 @@ pub struct TileBundle {
     // pub global_transform: GlobalTransform,
 }
 
-#[derive(Clone)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct TerrainDisplay {
     pub terrain: Terrain,
     pub sprite: String,
 }
 
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct FeatureDisplay {
+    pub feature: Feature,
+    pub sprite: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct TileMap {
     pub width: usize,
     pub height: usize,
     pub tiles: Vec<Vec<TerrainDisplay>>,
+    pub features: Vec<Vec<Option<FeatureDisplay>>>,
+    #[serde(default)]
+    pub grid: GridKind,
 }
 
 impl TileMap {
@@ -140,12 +561,114 @@ impl TileMap {
                 ];
                 height
             ],
+            features: vec![vec![None; width]; height],
+            grid: GridKind::Square,
+        }
+    }
+
+    /// Whether the coordinates are inside the map.
+    pub fn contains(&self, (x, y): (usize, usize)) -> bool {
+        x < self.width && y < self.height
+    }
+
+    /// Returns the feature on top of the terrain at the coordinates.
+    pub fn feature(&self, (x, y): (usize, usize)) -> Option<&FeatureDisplay> {
+        self.features[y][x].as_ref()
+    }
+
+    /// Places the feature on top of the terrain at the coordinates, `None`
+    /// clearing it.
+    pub fn set_feature(&mut self, (x, y): (usize, usize), feature: Option<FeatureDisplay>) {
+        self.features[y][x] = feature;
+    }
+
+    /// The cost of moving onto the tile, combining what `costs` returns for
+    /// its terrain with its feature, see [`Feature::move_cost`].
+    pub fn move_cost(
+        &self,
+        position: (usize, usize),
+        costs: &impl Fn(Terrain) -> Option<f32>,
+    ) -> Option<f32> {
+        let base = costs(self[position].terrain);
+        match self.feature(position) {
+            Some(display) => display.feature.move_cost(base),
+            None => base,
         }
     }
 }
 
 pub const TILE_SIZE: f32 = 16.0;
-const SCALE_FACTOR: f32 = 2.0;
+pub(crate) const SCALE_FACTOR: f32 = 2.0;
+/// How far above its tile a feature is drawn.
+const FEATURE_Z: f32 = 1.0;
+/// The size of a tile in world units.
+const TILE_WORLD_SIZE: f32 = TILE_SIZE * SCALE_FACTOR;
+/// The distance between the centers of rows of hexes, which overlap by a
+/// quarter of their height.
+const HEX_ROW_HEIGHT: f32 = TILE_WORLD_SIZE * 0.866_025_4;
+
+/// Returns the world position of the center of the tile on a square grid,
+/// see [`GridKind::tile_to_world`].
+pub fn tile_to_world(x: usize, y: usize) -> Vec2 {
+    Vec2::new(x as f32, y as f32) * TILE_WORLD_SIZE
+}
+
+/// Returns the area the tile covers in the world on a square grid.
+pub fn tile_rect(x: usize, y: usize) -> Rect {
+    Rect::from_center_size(tile_to_world(x, y), Vec2::splat(TILE_WORLD_SIZE))
+}
+
+/// The dimensions of the spawned map, inserted by [`TileMap`] when it is
+/// applied.
+#[derive(Resource, Clone, Copy, Debug)]
+pub struct MapConfig {
+    pub width: usize,
+    pub height: usize,
+    pub grid: GridKind,
+}
+
+impl MapConfig {
+    /// Returns the tile covering the world position, or `None` outside the
+    /// map. On a square grid a position on the border between two tiles
+    /// belongs to the one to the north or east.
+    pub fn world_to_tile(&self, position: Vec2) -> Option<(usize, usize)> {
+        let (x, y) = match self.grid {
+            GridKind::Square => {
+                let tile = (position / TILE_WORLD_SIZE + 0.5).floor();
+                (tile.x as i64, tile.y as i64)
+            }
+            GridKind::HexPointyTop => world_to_hex(position),
+        };
+        if x < 0 || y < 0 {
+            return None;
+        }
+
+        let (x, y) = (x as usize, y as usize);
+        (x < self.width && y < self.height).then_some((x, y))
+    }
+}
+
+/// Returns the coordinates of the hex covering the world position, which may
+/// be negative outside the map.
+fn world_to_hex(position: Vec2) -> (i64, i64) {
+    // Fractional axial coordinates, rounded to the nearest hex by rounding
+    // the cube coordinates `(q, r, -q - r)` and fixing the one which moved
+    // the most so that they still add up to zero.
+    let r = position.y / HEX_ROW_HEIGHT;
+    let q = position.x / TILE_WORLD_SIZE - r / 2.0;
+    let s = -q - r;
+
+    let (mut rq, mut rr, rs) = (q.round(), r.round(), s.round());
+    let (dq, dr, ds) = ((rq - q).abs(), (rr - r).abs(), (rs - s).abs());
+    if dq > dr && dq > ds {
+        rq = -rr - rs;
+    } else if dr > ds {
+        rr = -rq - rs;
+    }
+
+    let (q, r) = (rq as i64, rr as i64);
+    (q + (r - r.rem_euclid(2)) / 2, r)
+}
 
 impl Command for TileMap {
     fn apply(self, world: &mut World) {
@@ -161,11 +684,9 @@ impl Command for TileMap {
                             custom_size: Some(Vec2::new(TILE_SIZE, TILE_SIZE)),
                             ..default()
                         },
-                        transform: Transform::from_translation(Vec3::new(
-                            x as f32 * TILE_SIZE * SCALE_FACTOR,
-                            y as f32 * TILE_SIZE * SCALE_FACTOR,
-                            0.0,
-                        ))
+                        transform: Transform::from_translation(
+                            self.grid.tile_to_world(x, y).extend(0.0),
+                        )
                         .with_scale(Vec3::splat(SCALE_FACTOR)),
                         ..default()
                     },
@@ -174,6 +695,7 @@ impl Command for TileMap {
                             x,
                             y,
                             terrain: terrain.terrain,
+                            feature: self.features[y][x].as_ref().map(|display| display.feature),
                         },
                         neighbors: default(),
                     },
@@ -181,7 +703,92 @@ impl Command for TileMap {
             }
         }
 
-        world.spawn_batch(bundles);
+        let positions: Vec<_> = bundles
+            .iter()
+            .map(|(_, bundle)| (bundle.tile.x, bundle.tile.y))
+            .collect();
+        let entities: Vec<_> = world.spawn_batch(bundles).collect();
+
+        for (&entity, &(x, y)) in entities.iter().zip(positions.iter()) {
+            let Some(display) = &self.features[y][x] else {
+                continue;
+            };
+
+            let texture = world
+                .resource::<AssetServer>()
+                .load::<Image>(display.sprite.clone());
+            world.entity_mut(entity).with_children(|tile| {
+                tile.spawn((
+                    SpriteBundle {
+                        texture,
+                        sprite: Sprite {
+                            custom_size: Some(Vec2::new(TILE_SIZE, TILE_SIZE)),
+                            ..default()
+                        },
+                        transform: Transform::from_xyz(0.0, 0.0, FEATURE_Z),
+                        ..default()
+                    },
+                    display.feature,
+                ));
+            });
+        }
+
+        world.insert_resource(MapConfig {
+            width: self.width,
+            height: self.height,
+            grid: self.grid,
+        });
+
+        let mut tile_index = world.get_resource_or_insert_with(TileIndex::default);
+        for (entity, &position) in entities.into_iter().zip(positions.iter()) {
+            tile_index.insert(entity, position);
+        }
+
+        world.send_event(MapChanged { positions });
+    }
+}
+
+/// Changes the terrain and sprite of the already spawned tile at the
+/// coordinates.
+pub struct SetTile {
+    pub x: usize,
+    pub y: usize,
+    pub display: TerrainDisplay,
+}
+
+impl Command for SetTile {
+    fn apply(self, world: &mut World) {
+        let Some(entity) = world
+            .get_resource::<TileIndex>()
+            .and_then(|tile_index| tile_index.get(self.x, self.y))
+        else {
+            warn!("no tile at ({}, {}) to set", self.x, self.y);
+            return;
+        };
+
+        let texture = world
+            .resource::<AssetServer>()
+            .load::<Image>(self.display.sprite);
+        let mut tile = world.entity_mut(entity);
+        tile.insert(texture);
+        if let Some(mut tile) = tile.get_mut::<Tile>() {
+            tile.terrain = self.display.terrain;
+        }
+
+        world.send_event(MapChanged {
+            positions: vec![(self.x, self.y)],
+        });
+    }
+}
+
+pub trait SetTileExt {
+    /// Queues a [`SetTile`] command.
+    fn set_tile(&mut self, x: usize, y: usize, display: TerrainDisplay);
+}
+
+impl SetTileExt for Commands<'_, '_> {
+    fn set_tile(&mut self, x: usize, y: usize, display: TerrainDisplay) {
+        self.add(SetTile { x, y, display });
     }
 }
 
@@ -198,3 +805,606 @@ impl IndexMut<(usize, usize)> for TileMap {
         &mut self.tiles[y][x]
     }
 }
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use bevy::ecs::system::RunSystemOnce;
+
+    use super::*;
+
+    /// A headless app keeping the [`TileIndex`] and [`Neighbors`] up to date
+    /// before running `systems`.
+    pub(crate) fn app<M>(systems: impl IntoSystemConfigs<M>) -> App {
+        let mut app = App::new();
+        app.add_plugins((MinimalPlugins, AssetPlugin::default()))
+            .init_asset::<Image>()
+            .init_resource::<TileIndex>()
+            .add_event::<MapChanged>()
+            .add_systems(
+                Update,
+                (update_tile_index, update_neighbors, systems).chain(),
+            );
+        app
+    }
+
+    /// A map of plains of the size.
+    pub(crate) fn plains(width: usize, height: usize) -> TileMap {
+        let mut map = TileMap::new(width, height);
+        for y in 0..height {
+            for x in 0..width {
+                map[(x, y)] = Terrain::Plains.as_display("grass.png");
+            }
+        }
+        map
+    }
+
+    #[test]
+    fn default_move_costs() {
+        let rules = TerrainRules::default();
+        for terrain in Terrain::ALL {
+            assert_eq!(rules.move_cost(terrain), terrain.move_cost());
+            assert_eq!(rules.is_passable(terrain), terrain.is_passable());
+        }
+
+        assert_eq!(Terrain::Road.move_cost(), Some(0.5));
+        assert_eq!(Terrain::Plains.move_cost(), Some(1.0));
+        assert_eq!(Terrain::Forest.move_cost(), Some(2.0));
+        assert!(!Terrain::Water.is_passable());
+        assert!(!Terrain::Mountain.is_passable());
+    }
+
+    #[test]
+    fn overridden_move_costs() {
+        // Plains with a forest in the middle of the bottom row and mountains
+        // in the middle of the top one.
+        let mut map = plains(3, 2);
+        map[(1, 0)] = Terrain::Forest.as_display("forest.png");
+        map[(1, 1)] = Terrain::Mountain.as_display("mountain.png");
+
+        let mut rules = TerrainRules::default();
+        let through_forest = Some(vec![(0, 0), (1, 0), (2, 0)]);
+        let over_mountains = Some(vec![(0, 0), (0, 1), (1, 1), (2, 1), (2, 0)]);
+        assert_eq!(
+            map.find_path_with_rules((0, 0), (2, 0), &rules),
+            through_forest
+        );
+
+        rules.set_move_cost(Terrain::Forest, None);
+        assert!(!rules.is_passable(Terrain::Forest));
+        assert_eq!(map.find_path_with_rules((0, 0), (2, 0), &rules), None);
+
+        // Crossing the mountains costs 1 + 0.5 + 1 + 1, less than the 4 + 1
+        // of going through the forest.
+        rules.set_move_cost(Terrain::Mountain, Some(0.5));
+        rules.set_move_cost(Terrain::Forest, Some(4.0));
+        assert_eq!(rules.move_cost(Terrain::Mountain), Some(0.5));
+        assert_eq!(
+            map.find_path_with_rules((0, 0), (2, 0), &rules),
+            over_mountains
+        );
+
+        rules.reset_move_cost(Terrain::Forest);
+        assert_eq!(rules.move_cost(Terrain::Forest), Some(2.0));
+        assert_eq!(
+            map.find_path_with_rules((0, 0), (2, 0), &rules),
+            through_forest
+        );
+
+        let tile = Tile {
+            x: 1,
+            y: 1,
+            terrain: Terrain::Mountain,
+            feature: None,
+        };
+        assert_eq!(tile.move_cost(&rules), Some(0.5));
+        assert_eq!(tile.move_cost(&TerrainRules::default()), None);
+    }
+
+    #[test]
+    fn all_impassable() {
+        let map = TileMap::new(3, 3);
+        let mut rules = TerrainRules::default();
+        for terrain in Terrain::ALL {
+            rules.set_move_cost(terrain, None);
+        }
+
+        for terrain in Terrain::ALL {
+            assert!(!rules.is_passable(terrain));
+        }
+        assert_eq!(map.find_path_with_rules((0, 0), (2, 2), &rules), None);
+        assert_eq!(map.find_path_with_rules((0, 0), (1, 0), &rules), None);
+        assert!(map.reachable_from((1, 1), 10.0, &|_| None).is_empty());
+    }
+
+    /// A [`TileIndex`] of a map of the size with made up entities.
+    fn tile_index(width: usize, height: usize) -> TileIndex {
+        let mut tile_index = TileIndex::default();
+        for y in 0..height {
+            for x in 0..width {
+                tile_index.insert(Entity::from_raw((y * width + x) as u32), (x, y));
+            }
+        }
+        tile_index
+    }
+
+    #[test]
+    fn diagonal_neighbors() {
+        use self::Direction::*;
+
+        let tile_index = tile_index(3, 3);
+        let at = |x, y| tile_index.get(x, y).unwrap();
+
+        let center = tile_index.neighbors_of(1, 1, GridKind::Square);
+        assert_eq!(
+            center.iter().collect::<Vec<_>>(),
+            [
+                (North, at(1, 2)),
+                (NorthEast, at(2, 2)),
+                (East, at(2, 1)),
+                (SouthEast, at(2, 0)),
+                (South, at(1, 0)),
+                (SouthWest, at(0, 0)),
+                (West, at(0, 1)),
+                (NorthWest, at(0, 2)),
+            ]
+        );
+        assert_eq!(center.is_neighbor(at(0, 0)), Some(SouthWest));
+        assert_eq!(center.is_neighbor(at(2, 2)), Some(NorthEast));
+        assert_eq!(center.is_neighbor(at(1, 1)), None);
+
+        let bottom_left = tile_index.neighbors_of(0, 0, GridKind::Square);
+        assert_eq!(
+            bottom_left.iter().collect::<Vec<_>>(),
+            [(North, at(0, 1)), (NorthEast, at(1, 1)), (East, at(1, 0))]
+        );
+        assert_eq!(bottom_left.get(SouthWest), None);
+        assert_eq!(bottom_left.get(NorthWest), None);
+        assert_eq!(bottom_left.is_neighbor(at(2, 2)), None);
+
+        let top_right = tile_index.neighbors_of(2, 2, GridKind::Square);
+        assert_eq!(
+            top_right.iter().collect::<Vec<_>>(),
+            [(South, at(2, 1)), (SouthWest, at(1, 1)), (West, at(1, 2))]
+        );
+        assert_eq!(top_right.get(NorthEast), None);
+
+        let bottom_right = tile_index.neighbors_of(2, 0, GridKind::Square);
+        assert_eq!(bottom_right.get(NorthWest), Some(at(1, 1)));
+        assert_eq!(bottom_right.is_neighbor(at(1, 1)), Some(NorthWest));
+        assert_eq!(bottom_right.iter().count(), 3);
+
+        let top_left = tile_index.neighbors_of(0, 2, GridKind::Square);
+        assert_eq!(top_left.get(SouthEast), Some(at(1, 1)));
+        assert_eq!(top_left.iter().count(), 3);
+    }
+
+    #[test]
+    fn hex_neighbors() {
+        use self::Direction::*;
+
+        let grid = GridKind::HexPointyTop;
+        let tile_index = tile_index(3, 4);
+        let at = |x, y| tile_index.get(x, y).unwrap();
+
+        // From an even row the diagonals reach the column itself and the one
+        // to the west, from an odd row the column itself and the one to the
+        // east.
+        let even = tile_index.neighbors_of(1, 2, grid);
+        assert_eq!(
+            even.iter().collect::<Vec<_>>(),
+            [
+                (NorthEast, at(1, 3)),
+                (East, at(2, 2)),
+                (SouthEast, at(1, 1)),
+                (SouthWest, at(0, 1)),
+                (West, at(0, 2)),
+                (NorthWest, at(0, 3)),
+            ]
+        );
+        let odd = tile_index.neighbors_of(1, 1, grid);
+        assert_eq!(
+            odd.iter().collect::<Vec<_>>(),
+            [
+                (NorthEast, at(2, 2)),
+                (East, at(2, 1)),
+                (SouthEast, at(2, 0)),
+                (SouthWest, at(1, 0)),
+                (West, at(0, 1)),
+                (NorthWest, at(1, 2)),
+            ]
+        );
+        assert_eq!(odd.get(North), None);
+        assert_eq!(odd.get(South), None);
+
+        let even_west = tile_index.neighbors_of(0, 2, grid);
+        assert_eq!(
+            even_west.iter().collect::<Vec<_>>(),
+            [
+                (NorthEast, at(0, 3)),
+                (East, at(1, 2)),
+                (SouthEast, at(0, 1))
+            ]
+        );
+        let odd_east = tile_index.neighbors_of(2, 1, grid);
+        assert_eq!(
+            odd_east.iter().collect::<Vec<_>>(),
+            [
+                (SouthWest, at(2, 0)),
+                (West, at(1, 1)),
+                (NorthWest, at(2, 2))
+            ]
+        );
+
+        // Every hex is a neighbor of its neighbors.
+        for (entity, (x, y)) in tile_index.iter() {
+            for (direction, neighbor) in tile_index.neighbors_of(x, y, grid).iter() {
+                let (nx, ny) = direction.step_on(grid, (x, y)).unwrap();
+                assert_eq!(neighbor, at(nx, ny));
+                let back = tile_index.neighbors_of(nx, ny, grid);
+                assert!(back.is_neighbor(entity).is_some());
+                assert_eq!(grid.distance((x, y), (nx, ny)), 1);
+            }
+        }
+    }
+
+    #[test]
+    fn steps_stop_at_zero() {
+        use self::Direction::*;
+
+        assert_eq!(NorthEast.step((0, 0)), Some((1, 1)));
+        assert_eq!(SouthEast.step((0, 0)), None);
+        assert_eq!(SouthWest.step((1, 1)), Some((0, 0)));
+        assert_eq!(SouthWest.step((1, 0)), None);
+        assert_eq!(NorthWest.step((0, 1)), None);
+        assert_eq!(NorthWest.step((1, 0)), Some((0, 1)));
+    }
+
+    fn tile_at(app: &App, x: usize, y: usize) -> Option<Entity> {
+        app.world.resource::<TileIndex>().get(x, y)
+    }
+
+    #[test]
+    fn tile_index_follows_spawns_and_despawns() {
+        let mut app = app(|| {});
+        plains(3, 2).apply(&mut app.world);
+        app.update();
+
+        assert_eq!(app.world.resource::<TileIndex>().len(), 6);
+        for y in 0..2 {
+            for x in 0..3 {
+                let tile = app.world.get::<Tile>(tile_at(&app, x, y).unwrap()).unwrap();
+                assert_eq!((tile.x, tile.y), (x, y));
+            }
+        }
+        assert_eq!(tile_at(&app, 3, 0), None);
+
+        let despawned = tile_at(&app, 1, 0).unwrap();
+        app.world.despawn(despawned);
+        app.update();
+        assert_eq!(tile_at(&app, 1, 0), None);
+        assert_eq!(app.world.resource::<TileIndex>().len(), 5);
+        let corner = tile_at(&app, 0, 0).unwrap();
+        assert_eq!(app.world.get::<Neighbors>(corner).unwrap().east, None);
+
+        let spawned = app
+            .world
+            .spawn(TileBundle {
+                tile: Tile {
+                    x: 1,
+                    y: 0,
+                    terrain: Terrain::Forest,
+                    feature: None,
+                },
+                neighbors: default(),
+            })
+            .id();
+        app.update();
+        assert_eq!(tile_at(&app, 1, 0), Some(spawned));
+        assert_eq!(app.world.resource::<TileIndex>().len(), 6);
+        assert_eq!(
+            app.world.get::<Neighbors>(corner).unwrap().east,
+            Some(spawned)
+        );
+        assert_eq!(
+            app.world
+                .resource::<TileIndex>()
+                .neighbors_of(1, 0, GridKind::Square),
+            *app.world.get::<Neighbors>(spawned).unwrap()
+        );
+
+        // Moving the tile frees its old coordinates.
+        app.world.get_mut::<Tile>(spawned).unwrap().x = 3;
+        app.update();
+        assert_eq!(tile_at(&app, 1, 0), None);
+        assert_eq!(tile_at(&app, 3, 0), Some(spawned));
+        assert_eq!(app.world.get::<Neighbors>(corner).unwrap().east, None);
+    }
+
+    #[test]
+    fn neighbors_stay_unchanged_without_edits() {
+        let mut app = app(|| {});
+        plains(4, 4).apply(&mut app.world);
+        app.update();
+
+        let changed_ticks = |app: &App| {
+            let mut ticks: Vec<_> = app
+                .world
+                .resource::<TileIndex>()
+                .iter()
+                .map(|(entity, position)| {
+                    let ticks = app.world.entity(entity).get_change_ticks::<Neighbors>();
+                    (position, ticks.unwrap().last_changed_tick())
+                })
+                .collect();
+            ticks.sort_by_key(|&(position, _)| position);
+            ticks
+        };
+        let before = changed_ticks(&app);
+
+        for _ in 0..3 {
+            app.update();
+        }
+        assert_eq!(changed_ticks(&app), before);
+
+        // Changing the terrain of a tile keeps its neighbors the same.
+        SetTile {
+            x: 1,
+            y: 1,
+            display: Terrain::Forest.as_display("forest.png"),
+        }
+        .apply(&mut app.world);
+        app.update();
+        assert_eq!(changed_ticks(&app), before);
+
+        // Only the tiles around a despawned one change.
+        let corner = tile_at(&app, 3, 3).unwrap();
+        app.world.despawn(corner);
+        app.update();
+        let after = changed_ticks(&app);
+        let changed: Vec<_> = after
+            .iter()
+            .filter(|entry| !before.contains(entry))
+            .map(|&(position, _)| position)
+            .collect();
+        assert_eq!(changed, [(2, 2), (2, 3), (3, 2)]);
+    }
+
+    #[test]
+    fn set_tile_flips_water_to_road() {
+        let mut app = app(|| {});
+        TileMap::new(2, 1).apply(&mut app.world);
+        app.update();
+        let mut map_changed = app.world.resource::<Events<MapChanged>>().get_reader();
+        map_changed.clear(app.world.resource::<Events<MapChanged>>());
+
+        app.world.run_system_once(|mut commands: Commands| {
+            commands.set_tile(1, 0, Terrain::Road.as_display("road.png"));
+            // Outside the map, which only logs a warning.
+            commands.set_tile(5, 5, Terrain::Road.as_display("road.png"));
+        });
+        app.update();
+
+        let entity = tile_at(&app, 1, 0).unwrap();
+        assert_eq!(
+            app.world.get::<Tile>(entity).unwrap().terrain,
+            Terrain::Road
+        );
+        let road = app
+            .world
+            .resource::<AssetServer>()
+            .load::<Image>("road.png");
+        assert_eq!(app.world.get::<Handle<Image>>(entity), Some(&road));
+
+        let positions: Vec<_> = map_changed
+            .read(app.world.resource::<Events<MapChanged>>())
+            .flat_map(|event| event.positions.iter().copied())
+            .collect();
+        assert_eq!(positions, [(1, 0)]);
+
+        let other = tile_at(&app, 0, 0).unwrap();
+        assert_eq!(
+            app.world.get::<Tile>(other).unwrap().terrain,
+            Terrain::Water
+        );
+        assert_eq!(app.world.resource::<TileIndex>().len(), 2);
+    }
+
+    #[test]
+    fn tile_world_round_trip() {
+        let config = MapConfig {
+            width: 5,
+            height: 4,
+            grid: GridKind::Square,
+        };
+
+        for y in 0..4 {
+            for x in 0..5 {
+                let center = tile_to_world(x, y);
+                assert_eq!(config.grid.tile_to_world(x, y), center);
+                assert_eq!(config.world_to_tile(center), Some((x, y)));
+
+                let rect = tile_rect(x, y);
+                assert_eq!(rect.center(), center);
+                assert_eq!(rect.size(), Vec2::splat(TILE_WORLD_SIZE));
+                assert_eq!(config.world_to_tile(rect.min), Some((x, y)));
+                let inside = rect.max - Vec2::splat(0.01);
+                assert_eq!(config.world_to_tile(inside), Some((x, y)));
+            }
+        }
+
+        assert_eq!(tile_to_world(1, 2), Vec2::new(32.0, 64.0));
+        assert_eq!(tile_rect(1, 2), Rect::new(16.0, 48.0, 48.0, 80.0));
+    }
+
+    #[test]
+    fn hex_world_round_trip() {
+        let config = MapConfig {
+            width: 5,
+            height: 4,
+            grid: GridKind::HexPointyTop,
+        };
+
+        assert_eq!(config.grid.tile_to_world(0, 0), Vec2::ZERO);
+        assert_eq!(
+            config.grid.tile_to_world(1, 1),
+            Vec2::new(1.5 * TILE_WORLD_SIZE, HEX_ROW_HEIGHT)
+        );
+
+        for y in 0..4 {
+            for x in 0..5 {
+                let center = config.grid.tile_to_world(x, y);
+                assert_eq!(config.world_to_tile(center), Some((x, y)));
+
+                // Neighboring hexes are a tile apart and the border between
+                // them lies halfway.
+                for direction in config.grid.directions() {
+                    let Some((nx, ny)) = direction.step_on(config.grid, (x, y)) else {
+                        continue;
+                    };
+                    let other = config.grid.tile_to_world(nx, ny);
+                    assert!(((other - center).length() - TILE_WORLD_SIZE).abs() < 0.01);
+                    let near = center.lerp(other, 0.49);
+                    assert_eq!(config.world_to_tile(near), Some((x, y)));
+                    if nx < 5 && ny < 4 {
+                        let far = center.lerp(other, 0.51);
+                        assert_eq!(config.world_to_tile(far), Some((nx, ny)));
+                    }
+                }
+            }
+        }
+
+        assert_eq!(config.world_to_tile(Vec2::new(-20.0, 0.0)), None);
+        assert_eq!(config.world_to_tile(Vec2::new(0.0, -20.0)), None);
+    }
+
+    #[test]
+    fn world_to_tile_on_borders() {
+        let config = MapConfig {
+            width: 5,
+            height: 4,
+            grid: GridKind::Square,
+        };
+        let half = TILE_WORLD_SIZE / 2.0;
+
+        // Between two tiles the one to the north or east wins.
+        assert_eq!(config.world_to_tile(Vec2::new(half, 0.0)), Some((1, 0)));
+        assert_eq!(config.world_to_tile(Vec2::new(0.0, half)), Some((0, 1)));
+        assert_eq!(config.world_to_tile(Vec2::new(half, half)), Some((1, 1)));
+
+        // The south and west edges of the map belong to it, the north and
+        // east ones do not.
+        assert_eq!(config.world_to_tile(Vec2::new(-half, -half)), Some((0, 0)));
+        assert_eq!(config.world_to_tile(Vec2::new(-half - 0.01, 0.0)), None);
+        assert_eq!(config.world_to_tile(Vec2::new(0.0, -half - 0.01)), None);
+        let north_east = tile_rect(4, 3).max;
+        assert_eq!(
+            config.world_to_tile(north_east - Vec2::splat(0.01)),
+            Some((4, 3))
+        );
+        assert_eq!(config.world_to_tile(north_east), None);
+        assert_eq!(config.world_to_tile(Vec2::new(north_east.x, 0.0)), None);
+        assert_eq!(config.world_to_tile(Vec2::new(0.0, north_east.y)), None);
+        assert_eq!(config.world_to_tile(Vec2::splat(-1000.0)), None);
+    }
+
+    #[test]
+    fn feature_costs_combine_with_terrain() {
+        assert_eq!(Feature::Road.move_cost(Some(2.0)), Some(0.5));
+        assert_eq!(Feature::Road.move_cost(None), Some(0.5));
+        assert_eq!(Feature::Forest.move_cost(Some(1.0)), Some(2.0));
+        assert_eq!(Feature::Forest.move_cost(Some(2.0)), Some(3.0));
+        assert_eq!(Feature::Forest.move_cost(None), None);
+
+        // A road bridging the water and a forest on the plains.
+        let mut map = plains(3, 2);
+        map[(1, 0)] = Terrain::Water.as_display("water.png");
+        map[(1, 1)] = Terrain::Water.as_display("water.png");
+        map.set_feature((1, 0), Some(Feature::Road.as_display("road.png")));
+        map.set_feature((2, 1), Some(Feature::Forest.as_display("forest.png")));
+
+        let costs = |terrain: Terrain| terrain.move_cost();
+        assert_eq!(map[(1, 0)].terrain, Terrain::Water);
+        assert_eq!(
+            map.feature((1, 0)).map(|display| display.feature),
+            Some(Feature::Road)
+        );
+        assert_eq!(map.move_cost((1, 0), &costs), Some(0.5));
+        assert_eq!(map.move_cost((1, 1), &costs), None);
+        assert_eq!(map.move_cost((2, 1), &costs), Some(2.0));
+        assert_eq!(map.move_cost((0, 0), &costs), Some(1.0));
+        assert_eq!(
+            map.find_path((0, 1), (2, 1), &costs),
+            Some(vec![(0, 1), (0, 0), (1, 0), (2, 0), (2, 1)])
+        );
+
+        let mut rules = TerrainRules::default();
+        rules.set_move_cost(Terrain::Plains, Some(3.0));
+        let tile = |terrain, feature| Tile {
+            x: 0,
+            y: 0,
+            terrain,
+            feature,
+        };
+        assert_eq!(
+            tile(Terrain::Plains, Some(Feature::Forest)).move_cost(&rules),
+            Some(4.0)
+        );
+        assert_eq!(
+            tile(Terrain::Mountain, Some(Feature::Road)).move_cost(&rules),
+            Some(0.5)
+        );
+        assert_eq!(
+            tile(Terrain::Mountain, Some(Feature::Forest)).move_cost(&rules),
+            None
+        );
+
+        map.set_feature((1, 0), None);
+        assert!(map.feature((1, 0)).is_none());
+        assert_eq!(map.find_path((0, 1), (2, 1), &costs), None);
+    }
+
+    #[test]
+    fn features_spawn_as_children() {
+        let mut map = plains(2, 1);
+        map.set_feature((1, 0), Some(Feature::Forest.as_display("pines.png")));
+
+        let mut app = app(|| {});
+        map.apply(&mut app.world);
+        app.update();
+
+        let bare = tile_at(&app, 0, 0).unwrap();
+        assert_eq!(app.world.get::<Tile>(bare).unwrap().feature, None);
+        assert!(app.world.get::<Children>(bare).is_none());
+
+        let forest = tile_at(&app, 1, 0).unwrap();
+        assert_eq!(
+            app.world.get::<Tile>(forest).unwrap().feature,
+            Some(Feature::Forest)
+        );
+        let children = app.world.get::<Children>(forest).unwrap();
+        assert_eq!(children.len(), 1);
+
+        let child = app.world.entity(children[0]);
+        assert_eq!(child.get::<Parent>().map(Parent::get), Some(forest));
+        assert_eq!(child.get::<Feature>(), Some(&Feature::Forest));
+        assert_eq!(child.get::<Tile>().map(|tile| tile.x), None);
+        let pines = app
+            .world
+            .resource::<AssetServer>()
+            .load::<Image>("pines.png");
+        assert_eq!(child.get::<Handle<Image>>(), Some(&pines));
+        // Drawn above the tile.
+        assert_eq!(
+            child.get::<Transform>().unwrap().translation,
+            Vec3::new(0.0, 0.0, FEATURE_Z)
+        );
+        assert!(FEATURE_Z > app.world.get::<Transform>(forest).unwrap().translation.z);
+
+        // Despawning the tile takes its feature along.
+        let child = children[0];
+        app.world.entity_mut(forest).despawn_recursive();
+        app.update();
+        assert!(app.world.get_entity(child).is_none());
+        assert_eq!(tile_at(&app, 1, 0), None);
+    }
+}
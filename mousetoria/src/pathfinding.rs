@@ -0,0 +1,499 @@
+use std::{
+    cmp::Ordering,
+    collections::{BinaryHeap, HashMap},
+};
+
+use crate::map::{Feature, GridKind, Terrain, TerrainRules, TileMap};
+
+/// An entry of the open set, ordered so that the [`BinaryHeap`] pops the
+/// lowest estimate first.
+#[derive(Clone, Copy, PartialEq)]
+struct Node {
+    estimate: f32,
+    cost: f32,
+    index: usize,
+}
+
+impl Eq for Node {}
+
+impl Ord for Node {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other
+            .estimate
+            .total_cmp(&self.estimate)
+            // Among equal estimates prefer the nodes closest to the goal.
+            .then_with(|| self.cost.total_cmp(&other.cost))
+            .then_with(|| other.index.cmp(&self.index))
+    }
+}
+
+impl PartialOrd for Node {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl TileMap {
+    /// Finds the cheapest path between the tiles moving north, east, south or
+    /// west, or to any of the six neighbors on a hex grid, returning the
+    /// coordinates of every tile on it including `from` and `to`.
+    ///
+    /// Moving onto a tile costs what `costs` returns for its terrain combined
+    /// with its feature, `None` meaning that the tile cannot be entered, see
+    /// [`TileMap::move_cost`]. Returns `None` if either tile is outside the map
+    /// or `to` cannot be reached.
+    pub fn find_path(
+        &self,
+        from: (usize, usize),
+        to: (usize, usize),
+        costs: &impl Fn(Terrain) -> Option<f32>,
+    ) -> Option<Vec<(usize, usize)>> {
+        if !self.contains(from) || !self.contains(to) {
+            return None;
+        }
+
+        if from == to {
+            return Some(vec![from]);
+        }
+
+        // The cheapest step times the number of steps never overestimates the
+        // remaining cost, which keeps the found path the cheapest one.
+        let min_cost = Terrain::ALL
+            .into_iter()
+            .flat_map(|terrain| {
+                let base = costs(terrain);
+                Feature::ALL
+                    .into_iter()
+                    .map(move |feature| feature.move_cost(base))
+                    .chain([base])
+            })
+            .flatten()
+            .fold(f32::INFINITY, f32::min);
+        if min_cost == f32::INFINITY {
+            return None;
+        }
+        let min_cost = min_cost.max(0.0);
+        let heuristic = |position| self.grid.distance(position, to) as f32 * min_cost;
+
+        let index = |(x, y): (usize, usize)| y * self.width + x;
+        let mut best_costs = vec![f32::INFINITY; self.width * self.height];
+        let mut came_from = vec![usize::MAX; self.width * self.height];
+        let mut open = BinaryHeap::new();
+
+        best_costs[index(from)] = 0.0;
+        open.push(Node {
+            estimate: heuristic(from),
+            cost: 0.0,
+            index: index(from),
+        });
+
+        while let Some(Node {
+            cost,
+            index: current,
+            ..
+        }) = open.pop()
+        {
+            if cost > best_costs[current] {
+                continue;
+            }
+
+            let position = (current % self.width, current / self.width);
+            if position == to {
+                let mut path = vec![position];
+                let mut current = current;
+                while came_from[current] != usize::MAX {
+                    current = came_from[current];
+                    path.push((current % self.width, current / self.width));
+                }
+                path.reverse();
+
+                return Some(path);
+            }
+
+            for neighbor in self.adjacent(position) {
+                let Some(step_cost) = self.move_cost(neighbor, costs) else {
+                    continue;
+                };
+
+                let next = index(neighbor);
+                let next_cost = cost + step_cost;
+                if next_cost < best_costs[next] {
+                    best_costs[next] = next_cost;
+                    came_from[next] = current;
+                    open.push(Node {
+                        estimate: next_cost + heuristic(neighbor),
+                        cost: next_cost,
+                        index: next,
+                    });
+                }
+            }
+        }
+
+        None
+    }
+
+    /// Like [`TileMap::find_path`] with the costs given by the rules.
+    pub fn find_path_with_rules(
+        &self,
+        from: (usize, usize),
+        to: (usize, usize),
+        rules: &TerrainRules,
+    ) -> Option<Vec<(usize, usize)>> {
+        self.find_path(from, to, &|terrain| rules.move_cost(terrain))
+    }
+
+    /// Finds every tile which can be reached from `origin` moving like in
+    /// [`TileMap::find_path`] without spending more than `budget`, returning the
+    /// cheapest cost of reaching each, `origin` itself costing nothing.
+    ///
+    /// Moving onto a tile costs the same as in [`TileMap::find_path`]. Tiles
+    /// which cannot be entered are never returned, and nothing is if `origin`
+    /// cannot be entered or is outside the map.
+    pub fn reachable_from(
+        &self,
+        origin: (usize, usize),
+        budget: f32,
+        costs: &impl Fn(Terrain) -> Option<f32>,
+    ) -> HashMap<(usize, usize), f32> {
+        reachable(
+            origin,
+            budget,
+            (self.width, self.height),
+            self.grid,
+            |position| self.move_cost(position, costs),
+        )
+    }
+
+    /// Returns the coordinates of the tiles which can be moved to from the
+    /// tile and are inside the map.
+    fn adjacent(&self, position: (usize, usize)) -> impl Iterator<Item = (usize, usize)> + '_ {
+        self.grid
+            .movement_directions()
+            .iter()
+            .filter_map(move |direction| direction.step_on(self.grid, position))
+            .filter(|&position| self.contains(position))
+    }
+}
+
+/// Uniform-cost search from `origin` over a map of the given size and grid
+/// where `cost_at` returns the cost of moving onto a tile inside it.
+pub(crate) fn reachable(
+    origin: (usize, usize),
+    budget: f32,
+    (width, height): (usize, usize),
+    grid: GridKind,
+    cost_at: impl Fn((usize, usize)) -> Option<f32>,
+) -> HashMap<(usize, usize), f32> {
+    let mut reached = HashMap::new();
+    if origin.0 >= width || origin.1 >= height || cost_at(origin).is_none() {
+        return reached;
+    }
+
+    let index = |(x, y): (usize, usize)| y * width + x;
+    let mut best_costs = vec![f32::INFINITY; width * height];
+    let mut open = BinaryHeap::new();
+
+    best_costs[index(origin)] = 0.0;
+    open.push(Node {
+        estimate: 0.0,
+        cost: 0.0,
+        index: index(origin),
+    });
+
+    while let Some(Node {
+        cost,
+        index: current,
+        ..
+    }) = open.pop()
+    {
+        if cost > best_costs[current] {
+            continue;
+        }
+
+        let position = (current % width, current / width);
+        reached.insert(position, cost);
+
+        for direction in grid.movement_directions() {
+            let Some(neighbor) = direction
+                .step_on(grid, position)
+                .filter(|&(x, y)| x < width && y < height)
+            else {
+                continue;
+            };
+            let Some(step_cost) = cost_at(neighbor) else {
+                continue;
+            };
+
+            let next = index(neighbor);
+            let next_cost = cost + step_cost;
+            if next_cost <= budget && next_cost < best_costs[next] {
+                best_costs[next] = next_cost;
+                open.push(Node {
+                    estimate: next_cost,
+                    cost: next_cost,
+                    index: next,
+                });
+            }
+        }
+    }
+
+    reached
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ascii::default_legend, map::tests::plains};
+
+    fn parse(art: &str) -> TileMap {
+        TileMap::from_ascii(art, &default_legend()).unwrap()
+    }
+
+    fn default_costs(terrain: Terrain) -> Option<f32> {
+        terrain.move_cost()
+    }
+
+    /// The cost of moving along the path, leaving out its first tile.
+    fn path_cost(map: &TileMap, path: &[(usize, usize)]) -> f32 {
+        path[1..]
+            .iter()
+            .map(|&position| map.move_cost(position, &default_costs).unwrap())
+            .sum()
+    }
+
+    /// Draws the path on the map as road.
+    fn draw_path(map: &TileMap, path: &[(usize, usize)]) -> String {
+        let mut map = map.clone();
+        for &position in path {
+            map[position] = Terrain::Road.as_display(Terrain::Road.default_sprite());
+        }
+        map.to_ascii(&default_legend())
+    }
+
+    #[test]
+    fn straight_line() {
+        let map = parse("...");
+
+        let path = map.find_path((0, 0), (2, 0), &default_costs).unwrap();
+        assert_eq!(path, [(0, 0), (1, 0), (2, 0)]);
+        assert_eq!(path_cost(&map, &path), 2.0);
+
+        let path = map.find_path((2, 0), (0, 0), &default_costs).unwrap();
+        assert_eq!(path, [(2, 0), (1, 0), (0, 0)]);
+    }
+
+    #[test]
+    fn around_a_wall() {
+        let map = parse(
+            "
+            ...
+            .^.
+            .~.
+            ",
+        );
+
+        let path = map.find_path((0, 0), (2, 0), &default_costs).unwrap();
+        assert_eq!(
+            path,
+            [(0, 0), (0, 1), (0, 2), (1, 2), (2, 2), (2, 1), (2, 0)]
+        );
+        assert_eq!(path_cost(&map, &path), 6.0);
+    }
+
+    #[test]
+    fn through_a_maze() {
+        let map = parse(
+            "
+            .....
+            ~~~~.
+            .....
+            .~~~~
+            .....
+            ",
+        );
+
+        let path = map.find_path((0, 0), (0, 4), &default_costs).unwrap();
+        let expected = "
+            #####
+            ~~~~#
+            #####
+            #~~~~
+            #....
+            ";
+        assert_eq!(
+            draw_path(&map, &path),
+            parse(expected).to_ascii(&default_legend())
+        );
+        assert_eq!(path.first(), Some(&(0, 0)));
+        assert_eq!(path.last(), Some(&(0, 4)));
+        assert_eq!(path_cost(&map, &path), 12.0);
+    }
+
+    #[test]
+    fn cheaper_road_over_shorter_forest() {
+        let map = parse(
+            "
+            ###
+            .f.
+            ",
+        );
+
+        let path = map.find_path((0, 0), (2, 0), &default_costs).unwrap();
+        assert_eq!(path, [(0, 0), (0, 1), (1, 1), (2, 1), (2, 0)]);
+        assert_eq!(path_cost(&map, &path), 2.5);
+
+        // Through the forest it costs 3.
+        let road_blocked = |terrain| match terrain {
+            Terrain::Road => None,
+            terrain => terrain.move_cost(),
+        };
+        let path = map.find_path((0, 0), (2, 0), &road_blocked).unwrap();
+        assert_eq!(path, [(0, 0), (1, 0), (2, 0)]);
+        assert_eq!(path_cost(&map, &path), 3.0);
+    }
+
+    #[test]
+    fn towns_and_cities() {
+        // Towns and cities cost as much as plains, so going around the forest
+        // costs 4 and through it 3.
+        let map = parse(
+            "
+            T.C
+            .f.
+            ",
+        );
+
+        let path = map.find_path((0, 1), (2, 1), &default_costs).unwrap();
+        assert_eq!(path, [(0, 1), (1, 1), (2, 1)]);
+        assert_eq!(path_cost(&map, &path), 2.0);
+
+        let path = map.find_path((0, 0), (2, 0), &default_costs).unwrap();
+        assert_eq!(path_cost(&map, &path), 3.0);
+    }
+
+    #[test]
+    fn start_is_goal() {
+        let map = parse(
+            "
+            .~
+            ..
+            ",
+        );
+
+        assert_eq!(
+            map.find_path((0, 1), (0, 1), &default_costs),
+            Some(vec![(0, 1)])
+        );
+        // Standing on a tile does not require entering it.
+        assert_eq!(
+            map.find_path((1, 1), (1, 1), &default_costs),
+            Some(vec![(1, 1)])
+        );
+    }
+
+    #[test]
+    fn unreachable() {
+        // The plains at (2, 2) are surrounded by water.
+        let map = parse(
+            "
+            ..~.
+            .~.~
+            ..~.
+            ....
+            ",
+        );
+
+        assert_eq!(map.find_path((0, 0), (2, 2), &default_costs), None);
+        assert_eq!(map.find_path((2, 2), (0, 0), &default_costs), None);
+        assert_eq!(map.find_path((0, 0), (2, 1), &default_costs), None);
+        assert_eq!(map.find_path((0, 0), (4, 0), &default_costs), None);
+        assert_eq!(map.find_path((0, 4), (0, 0), &default_costs), None);
+        assert_eq!(map.find_path((0, 0), (3, 3), &|_| None), None);
+    }
+
+    #[test]
+    fn large_map() {
+        let map = plains(300, 300);
+
+        let path = map.find_path((0, 0), (299, 299), &default_costs).unwrap();
+        assert_eq!(path.len(), 599);
+        assert_eq!(path_cost(&map, &path), 598.0);
+        assert!(path
+            .windows(2)
+            .all(|step| map.grid.distance(step[0], step[1]) == 1));
+    }
+
+    #[test]
+    fn reachable_within_budget() {
+        let map = parse(
+            "
+            .f.
+            .~.
+            ...
+            ",
+        );
+
+        assert_eq!(
+            map.reachable_from((0, 0), 3.0, &default_costs),
+            HashMap::from([
+                ((0, 0), 0.0),
+                ((1, 0), 1.0),
+                ((2, 0), 2.0),
+                ((0, 1), 1.0),
+                ((0, 2), 2.0),
+                ((2, 1), 3.0),
+            ])
+        );
+
+        // The forest costs 2 from either side.
+        let reached = map.reachable_from((0, 0), 4.0, &default_costs);
+        assert_eq!(reached.len(), 8);
+        assert_eq!(reached[&(1, 2)], 4.0);
+        assert_eq!(reached[&(2, 2)], 4.0);
+        assert!(!reached.contains_key(&(1, 1)));
+
+        assert_eq!(
+            map.reachable_from((0, 0), 0.0, &default_costs),
+            HashMap::from([((0, 0), 0.0)])
+        );
+        assert_eq!(
+            map.reachable_from((1, 2), 1.0, &default_costs),
+            HashMap::from([((1, 2), 0.0), ((0, 2), 1.0), ((2, 2), 1.0)])
+        );
+    }
+
+    #[test]
+    fn reachable_never_includes_impassable_tiles() {
+        let mut map = parse(
+            "
+            ~~~
+            .~.
+            ",
+        );
+
+        for budget in [0.0, 1.0, 100.0] {
+            assert!(map
+                .reachable_from((1, 0), budget, &default_costs)
+                .is_empty());
+            assert!(map
+                .reachable_from((3, 0), budget, &default_costs)
+                .is_empty());
+        }
+        assert_eq!(
+            map.reachable_from((0, 0), 100.0, &default_costs),
+            HashMap::from([((0, 0), 0.0)])
+        );
+        assert!(map.reachable_from((0, 0), 100.0, &|_| None).is_empty());
+
+        // A road makes the water passable.
+        map.set_feature((1, 0), Some(Feature::Road.as_display("road.png")));
+        assert_eq!(
+            map.reachable_from((0, 0), 1.5, &default_costs),
+            HashMap::from([((0, 0), 0.0), ((1, 0), 0.5), ((2, 0), 1.5)])
+        );
+        assert_eq!(
+            map.reachable_from((0, 0), 1.0, &default_costs),
+            HashMap::from([((0, 0), 0.0), ((1, 0), 0.5)])
+        );
+    }
+}
@@ -0,0 +1,237 @@
+use bevy::prelude::*;
+
+use crate::{
+    camera::{CursorWorldPosition, DragDistance, DragState},
+    map::{MapConfig, TileIndex},
+};
+
+/// The tile under the cursor, if any.
+#[derive(Resource, Default, Debug)]
+pub struct HoveredTile(pub Option<Entity>);
+
+/// Sent when the cursor moves onto a tile.
+#[derive(Event, Debug, Clone, PartialEq, Eq)]
+pub struct TileHovered {
+    pub entity: Entity,
+    pub x: usize,
+    pub y: usize,
+}
+
+/// Sent when a mouse button is released over a tile, unless the left button
+/// was used to drag the camera.
+#[derive(Event, Debug, Clone, PartialEq, Eq)]
+pub struct TileClicked {
+    pub entity: Entity,
+    pub x: usize,
+    pub y: usize,
+    pub button: MouseButton,
+}
+
+/// Finds the tile under the cursor, updating [`HoveredTile`] and sending
+/// [`TileHovered`] and [`TileClicked`]. The gaps drawn between tiles count
+/// as part of the tiles.
+#[allow(clippy::too_many_arguments)]
+pub fn pick_tile(
+    cursor_world_position: Res<CursorWorldPosition>,
+    map_config: Res<MapConfig>,
+    tile_index: Res<TileIndex>,
+    mouse_button: Res<Input<MouseButton>>,
+    drag_state: Res<State<DragState>>,
+    drag_distance: Res<DragDistance>,
+    mut hovered_tile: ResMut<HoveredTile>,
+    mut tile_hovered: EventWriter<TileHovered>,
+    mut tile_clicked: EventWriter<TileClicked>,
+) {
+    let picked = cursor_world_position
+        .0
+        .and_then(|position| map_config.world_to_tile(position))
+        .and_then(|(x, y)| Some((tile_index.get(x, y)?, x, y)));
+
+    let entity = picked.map(|(entity, _, _)| entity);
+    if hovered_tile.0 != entity {
+        hovered_tile.0 = entity;
+        if let Some((entity, x, y)) = picked {
+            tile_hovered.send(TileHovered { entity, x, y });
+        }
+    }
+
+    if *drag_state.get() == DragState::Dragging {
+        return;
+    }
+
+    if let Some((entity, x, y)) = picked {
+        for &button in mouse_button.get_just_released() {
+            if button == MouseButton::Left && drag_distance.is_drag() {
+                continue;
+            }
+
+            tile_clicked.send(TileClicked {
+                entity,
+                x,
+                y,
+                button,
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use bevy::{ecs::event::ManualEventReader, ecs::system::Command, input::mouse::MouseMotion};
+
+    use super::*;
+    use crate::{
+        camera::set_drag_state,
+        map::{tests, tile_to_world},
+    };
+
+    /// A headless app with a 3 by 1 map picking tiles with the cursor at
+    /// [`CursorWorldPosition`] before running `systems`.
+    pub(crate) fn app<M>(systems: impl IntoSystemConfigs<M>) -> App {
+        let mut app = tests::app((set_drag_state, pick_tile, systems).chain());
+        app.init_resource::<Input<MouseButton>>()
+            .init_resource::<CursorWorldPosition>()
+            .init_resource::<DragDistance>()
+            .init_resource::<HoveredTile>()
+            .add_state::<DragState>()
+            .add_event::<MouseMotion>()
+            .add_event::<TileHovered>()
+            .add_event::<TileClicked>();
+        tests::plains(3, 1).apply(&mut app.world);
+        app
+    }
+
+    pub(crate) fn move_cursor(app: &mut App, position: Option<Vec2>) {
+        app.world.resource_mut::<CursorWorldPosition>().0 = position;
+    }
+
+    /// Presses or releases the button and runs a frame.
+    pub(crate) fn press(app: &mut App, button: MouseButton, pressed: bool) {
+        let mut input = app.world.resource_mut::<Input<MouseButton>>();
+        if pressed {
+            input.press(button);
+        } else {
+            input.release(button);
+        }
+        app.update();
+        app.world.resource_mut::<Input<MouseButton>>().clear();
+    }
+
+    /// Presses and releases the button over the tile.
+    pub(crate) fn click(app: &mut App, x: usize, y: usize, button: MouseButton) {
+        move_cursor(app, Some(tile_to_world(x, y)));
+        press(app, button, true);
+        press(app, button, false);
+    }
+
+    fn drag(app: &mut App, delta: Vec2) {
+        app.world.send_event(MouseMotion { delta });
+        app.update();
+    }
+
+    fn read<E: Event + Clone>(app: &App, reader: &mut ManualEventReader<E>) -> Vec<E> {
+        reader
+            .read(app.world.resource::<Events<E>>())
+            .cloned()
+            .collect()
+    }
+
+    #[test]
+    fn hover_and_click() {
+        let mut app = app(|| {});
+        let mut hovered = app.world.resource::<Events<TileHovered>>().get_reader();
+        let mut clicked = app.world.resource::<Events<TileClicked>>().get_reader();
+        let tile = |x| app.world.resource::<TileIndex>().get(x, 0).unwrap();
+        let (first, second) = (tile(1), tile(2));
+
+        move_cursor(&mut app, Some(tile_to_world(1, 0)));
+        app.update();
+        assert_eq!(app.world.resource::<HoveredTile>().0, Some(first));
+        assert_eq!(
+            read(&app, &mut hovered),
+            [TileHovered {
+                entity: first,
+                x: 1,
+                y: 0
+            }]
+        );
+
+        // Moving within the tile, into the gap drawn around it, is no new
+        // hover.
+        move_cursor(&mut app, Some(tile_to_world(1, 0) + Vec2::splat(15.0)));
+        app.update();
+        assert_eq!(read(&app, &mut hovered), []);
+
+        // The click is sent on release.
+        press(&mut app, MouseButton::Left, true);
+        assert_eq!(read(&app, &mut clicked), []);
+        press(&mut app, MouseButton::Left, false);
+        assert_eq!(
+            read(&app, &mut clicked),
+            [TileClicked {
+                entity: first,
+                x: 1,
+                y: 0,
+                button: MouseButton::Left
+            }]
+        );
+
+        click(&mut app, 2, 0, MouseButton::Right);
+        assert_eq!(app.world.resource::<HoveredTile>().0, Some(second));
+        assert_eq!(read(&app, &mut hovered).len(), 1);
+        assert_eq!(
+            read(&app, &mut clicked),
+            [TileClicked {
+                entity: second,
+                x: 2,
+                y: 0,
+                button: MouseButton::Right
+            }]
+        );
+
+        // Off the window nothing is hovered or clicked.
+        move_cursor(&mut app, None);
+        press(&mut app, MouseButton::Left, true);
+        press(&mut app, MouseButton::Left, false);
+        assert_eq!(app.world.resource::<HoveredTile>().0, None);
+        assert_eq!(read(&app, &mut hovered), []);
+        assert_eq!(read(&app, &mut clicked), []);
+    }
+
+    #[test]
+    fn drags_are_no_clicks() {
+        let mut app = app(|| {});
+        let mut clicked = app.world.resource::<Events<TileClicked>>().get_reader();
+        let drag_state = |app: &App| app.world.resource::<State<DragState>>().get().clone();
+
+        move_cursor(&mut app, Some(tile_to_world(0, 0)));
+        press(&mut app, MouseButton::Left, true);
+        drag(&mut app, Vec2::new(3.0, 0.0));
+        assert_eq!(drag_state(&app), DragState::NotDragging);
+        drag(&mut app, Vec2::new(3.0, 0.0));
+        app.update();
+        assert_eq!(drag_state(&app), DragState::Dragging);
+
+        move_cursor(&mut app, Some(tile_to_world(1, 0)));
+        press(&mut app, MouseButton::Left, false);
+        assert_eq!(read(&app, &mut clicked), []);
+        app.update();
+        assert_eq!(drag_state(&app), DragState::NotDragging);
+
+        // Moving past the threshold in the frame of the release is a drag
+        // too.
+        press(&mut app, MouseButton::Left, true);
+        app.world.send_event(MouseMotion {
+            delta: Vec2::new(0.0, 10.0),
+        });
+        press(&mut app, MouseButton::Left, false);
+        assert_eq!(read(&app, &mut clicked), []);
+
+        // A little jitter still clicks.
+        press(&mut app, MouseButton::Left, true);
+        drag(&mut app, Vec2::new(1.0, 1.0));
+        press(&mut app, MouseButton::Left, false);
+        assert_eq!(read(&app, &mut clicked).len(), 1);
+        assert_eq!(drag_state(&app), DragState::NotDragging);
+    }
+}
@@ -0,0 +1,363 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use bevy::prelude::*;
+
+use crate::map::{GridKind, MapChanged, MapConfig, Terrain, Tile, TileIndex, TileMap};
+
+/// The kinds of terrain which together form a region.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub enum TerrainClass {
+    Land,
+    Water,
+}
+
+impl Terrain {
+    pub fn class(&self) -> TerrainClass {
+        match self {
+            Terrain::Water => TerrainClass::Water,
+            _ => TerrainClass::Land,
+        }
+    }
+}
+
+/// Tiles of the same [`TerrainClass`] connected in the movement directions of
+/// the grid, such as a landmass or a lake.
+#[derive(Clone, Debug)]
+pub struct Region {
+    pub id: u32,
+    pub class: TerrainClass,
+    pub positions: Vec<(usize, usize)>,
+}
+
+impl Region {
+    pub fn area(&self) -> usize {
+        self.positions.len()
+    }
+}
+
+/// The region a tile belongs to, see [`Regions`].
+#[derive(Component, Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub struct RegionId(pub u32);
+
+/// The regions of the map by their ids. A region keeps its id until a tile in
+/// or next to it changes.
+#[derive(Resource, Default, Debug)]
+pub struct Regions {
+    regions: HashMap<u32, Region>,
+    by_position: HashMap<(usize, usize), u32>,
+    next_id: u32,
+}
+
+impl Regions {
+    pub fn get(&self, id: u32) -> Option<&Region> {
+        self.regions.get(&id)
+    }
+
+    pub fn region_of(&self, x: usize, y: usize) -> Option<&Region> {
+        self.get(*self.by_position.get(&(x, y))?)
+    }
+
+    /// Returns the region of the class with the largest area, the oldest one
+    /// if several are as large.
+    pub fn largest(&self, class: TerrainClass) -> Option<&Region> {
+        self.regions
+            .values()
+            .filter(|region| region.class == class)
+            .max_by(|a, b| a.area().cmp(&b.area()).then(b.id.cmp(&a.id)))
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Region> {
+        self.regions.values()
+    }
+
+    pub fn len(&self) -> usize {
+        self.regions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.regions.is_empty()
+    }
+
+    /// Flood-fills the regions at and next to the coordinates on the grid
+    /// again, leaving the others untouched, and returns the ids of the new
+    /// regions. `class_at` returns the class of the tile at the coordinates,
+    /// or `None` if there is no tile.
+    pub fn update(
+        &mut self,
+        grid: GridKind,
+        positions: impl IntoIterator<Item = (usize, usize)>,
+        class_at: impl Fn((usize, usize)) -> Option<TerrainClass>,
+    ) -> Vec<u32> {
+        let mut seeds = HashSet::new();
+        for position in positions {
+            seeds.insert(position);
+            seeds.extend(steps(grid, position));
+        }
+
+        let affected: HashSet<u32> = seeds
+            .iter()
+            .filter_map(|position| self.by_position.get(position).copied())
+            .collect();
+        for id in affected {
+            let region = self.regions.remove(&id).expect("regions out of sync");
+            for position in region.positions {
+                self.by_position.remove(&position);
+                seeds.insert(position);
+            }
+        }
+
+        // Filling from the seeds in order keeps the ids the same between runs.
+        let mut seeds: Vec<_> = seeds.into_iter().collect();
+        seeds.sort_unstable_by_key(|&(x, y)| (y, x));
+
+        let mut created = Vec::new();
+        for seed in seeds {
+            if self.by_position.contains_key(&seed) {
+                continue;
+            }
+            let Some(class) = class_at(seed) else {
+                continue;
+            };
+
+            let id = self.next_id;
+            self.next_id += 1;
+
+            let mut positions = Vec::new();
+            let mut queue = VecDeque::from([seed]);
+            self.by_position.insert(seed, id);
+            while let Some(position) = queue.pop_front() {
+                positions.push(position);
+                for next in steps(grid, position) {
+                    if !self.by_position.contains_key(&next) && class_at(next) == Some(class) {
+                        self.by_position.insert(next, id);
+                        queue.push_back(next);
+                    }
+                }
+            }
+
+            self.regions.insert(
+                id,
+                Region {
+                    id,
+                    class,
+                    positions,
+                },
+            );
+            created.push(id);
+        }
+
+        created
+    }
+}
+
+fn steps(grid: GridKind, position: (usize, usize)) -> impl Iterator<Item = (usize, usize)> {
+    grid.movement_directions()
+        .iter()
+        .filter_map(move |direction| direction.step_on(grid, position))
+}
+
+impl TileMap {
+    /// Finds every region of the map.
+    pub fn regions(&self) -> Regions {
+        let mut regions = Regions::default();
+        let positions = (0..self.height).flat_map(|y| (0..self.width).map(move |x| (x, y)));
+        regions.update(self.grid, positions, |position| {
+            self.contains(position)
+                .then(|| self[position].terrain.class())
+        });
+        regions
+    }
+}
+
+/// Recomputes the [`Regions`] affected by the [`MapChanged`] events and tags
+/// the tiles of the new regions with their [`RegionId`]. Must run after
+/// [`update_tile_index`](crate::map::update_tile_index).
+pub fn update_regions(
+    mut commands: Commands,
+    mut regions: ResMut<Regions>,
+    tile_index: Res<TileIndex>,
+    map_config: Option<Res<MapConfig>>,
+    mut map_changed: EventReader<MapChanged>,
+    tiles: Query<&Tile>,
+) {
+    let grid = map_config.map_or_else(GridKind::default, |config| config.grid);
+    let positions: Vec<_> = map_changed
+        .read()
+        .flat_map(|event| event.positions.iter().copied())
+        .collect();
+
+    let created = regions.update(grid, positions, |(x, y)| {
+        let entity = tile_index.get(x, y)?;
+        Some(tiles.get(entity).ok()?.terrain.class())
+    });
+
+    for id in created {
+        for &(x, y) in regions.regions[&id].positions.iter() {
+            if let Some(entity) = tile_index.get(x, y) {
+                commands.entity(entity).insert(RegionId(id));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::system::Command;
+
+    use super::*;
+    use crate::{
+        ascii::default_legend,
+        map::{tests, SetTile},
+    };
+
+    /// Two islands in a lake, the eastern one of forest and mountains.
+    const ISLANDS: &str = "
+        ~~~~~~~
+        ~..~~f~
+        ~..~~^~
+        ~~~~~~~
+        ";
+
+    fn areas(regions: &Regions) -> Vec<(TerrainClass, usize)> {
+        let mut areas: Vec<_> = regions
+            .iter()
+            .map(|region| (region.class, region.area()))
+            .collect();
+        areas.sort_by_key(|&(class, area)| (class == TerrainClass::Water, area));
+        areas
+    }
+
+    #[test]
+    fn two_islands_in_a_lake() {
+        let map = TileMap::from_ascii(ISLANDS, &default_legend()).unwrap();
+        let regions = map.regions();
+
+        assert_eq!(regions.len(), 3);
+        assert_eq!(
+            areas(&regions),
+            [
+                (TerrainClass::Land, 2),
+                (TerrainClass::Land, 4),
+                (TerrainClass::Water, 22)
+            ]
+        );
+
+        let west = regions.region_of(1, 1).unwrap();
+        assert_eq!(west.class, TerrainClass::Land);
+        let mut positions = west.positions.clone();
+        positions.sort_unstable();
+        assert_eq!(positions, [(1, 1), (1, 2), (2, 1), (2, 2)]);
+        assert_eq!(regions.region_of(2, 2).unwrap().id, west.id);
+
+        let east = regions.region_of(5, 2).unwrap();
+        assert_eq!(east.area(), 2);
+        assert_eq!(regions.region_of(5, 1).unwrap().id, east.id);
+        assert_ne!(east.id, west.id);
+
+        let lake = regions.region_of(0, 0).unwrap();
+        assert_eq!(lake.class, TerrainClass::Water);
+        assert_eq!(regions.region_of(3, 1).unwrap().id, lake.id);
+        assert!(regions.region_of(7, 0).is_none());
+
+        assert_eq!(regions.largest(TerrainClass::Land).unwrap().id, west.id);
+        assert_eq!(regions.largest(TerrainClass::Water).unwrap().id, lake.id);
+    }
+
+    #[test]
+    fn diagonals_do_not_connect() {
+        let map = TileMap::from_ascii(
+            "
+            .~
+            ~.
+            ",
+            &default_legend(),
+        )
+        .unwrap();
+
+        assert_eq!(
+            areas(&map.regions()),
+            [
+                (TerrainClass::Land, 1),
+                (TerrainClass::Land, 1),
+                (TerrainClass::Water, 1),
+                (TerrainClass::Water, 1)
+            ]
+        );
+    }
+
+    #[test]
+    fn hex_diagonals_connect() {
+        let mut map = TileMap::from_ascii(
+            "
+            .~
+            ~.
+            ",
+            &default_legend(),
+        )
+        .unwrap();
+        map.grid = GridKind::HexPointyTop;
+        let regions = map.regions();
+
+        // The odd row is shifted east, so the land touches but the water
+        // does not.
+        assert_eq!(
+            areas(&regions),
+            [
+                (TerrainClass::Land, 2),
+                (TerrainClass::Water, 1),
+                (TerrainClass::Water, 1)
+            ]
+        );
+        assert_eq!(
+            regions.region_of(0, 1).unwrap().id,
+            regions.region_of(1, 0).unwrap().id
+        );
+    }
+
+    #[test]
+    fn regions_follow_map_changes() {
+        let mut app = tests::app(update_regions);
+        app.init_resource::<Regions>();
+        TileMap::from_ascii(ISLANDS, &default_legend())
+            .unwrap()
+            .apply(&mut app.world);
+        app.update();
+
+        let region_id = |app: &App, x, y| {
+            let entity = app.world.resource::<TileIndex>().get(x, y).unwrap();
+            app.world.get::<RegionId>(entity).unwrap().0
+        };
+        let regions = app.world.resource::<Regions>();
+        assert_eq!(regions.len(), 3);
+        let lake = regions.region_of(0, 0).unwrap().id;
+        let east = regions.region_of(5, 1).unwrap().id;
+        assert_eq!(region_id(&app, 6, 3), lake);
+        assert_eq!(region_id(&app, 5, 2), east);
+        assert_ne!(region_id(&app, 1, 1), east);
+
+        // Filling in the lake between the islands joins them.
+        for x in [3, 4] {
+            SetTile {
+                x,
+                y: 1,
+                display: Terrain::Plains.as_display("grass.png"),
+            }
+            .apply(&mut app.world);
+        }
+        app.update();
+
+        let regions = app.world.resource::<Regions>();
+        assert_eq!(
+            areas(regions),
+            [(TerrainClass::Land, 8), (TerrainClass::Water, 20)]
+        );
+        let joined = regions.region_of(3, 1).unwrap().id;
+        assert_ne!(joined, east);
+        for (x, y) in [(1, 1), (2, 2), (3, 1), (4, 1), (5, 1), (5, 2)] {
+            assert_eq!(region_id(&app, x, y), joined);
+        }
+        let lake = regions.region_of(0, 0).unwrap().id;
+        assert_eq!(region_id(&app, 3, 2), lake);
+        assert_eq!(region_id(&app, 6, 3), lake);
+    }
+}
@@ -0,0 +1,439 @@
+use std::{
+    fmt::{self, Display},
+    fs,
+    path::{Path, PathBuf},
+};
+
+use bevy::{ecs::system::Command, prelude::*};
+use serde::{Deserialize, Serialize};
+
+use crate::map::{MapChanged, TileIndex, TileMap};
+
+/// The version of the map file format written by [`TileMap::save`]. Files
+/// with any other version are rejected by [`TileMap::load`].
+pub const MAP_FORMAT_VERSION: u32 = 2;
+
+#[derive(Serialize)]
+struct MapFileRef<'a> {
+    version: u32,
+    map: &'a TileMap,
+}
+
+#[derive(Deserialize)]
+struct MapFile {
+    map: TileMap,
+}
+
+#[derive(Deserialize)]
+struct MapFileVersion {
+    version: u32,
+}
+
+/// The formats maps can be saved in, chosen by the file extension.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum MapFormat {
+    Json,
+    Ron,
+}
+
+impl MapFormat {
+    /// Files ending in `.json` are JSON, everything else RON.
+    fn of(path: &Path) -> Self {
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some(extension) if extension.eq_ignore_ascii_case("json") => MapFormat::Json,
+            _ => MapFormat::Ron,
+        }
+    }
+}
+
+/// The layers of tiles stored in a map file.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum MapLayer {
+    Terrain,
+    Features,
+}
+
+impl Display for MapLayer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MapLayer::Terrain => write!(f, "terrain"),
+            MapLayer::Features => write!(f, "features"),
+        }
+    }
+}
+
+/// An error saving or loading a [`TileMap`].
+#[derive(Debug)]
+pub enum MapFileError {
+    /// The file at `path` could not be read or written.
+    Io {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    /// The map could not be serialized.
+    Serialize { path: PathBuf, message: String },
+    /// The file at `path` is not a valid map file.
+    Parse { path: PathBuf, message: String },
+    /// The file at `path` was written in another `version` of the format.
+    UnsupportedVersion { path: PathBuf, version: u32 },
+    /// The `layer` in the file at `path` does not match its `width` and
+    /// `height`, `row` being the first one with the wrong length, or `None` if
+    /// the number of rows is wrong.
+    DimensionMismatch {
+        path: PathBuf,
+        layer: MapLayer,
+        width: usize,
+        height: usize,
+        row: Option<usize>,
+    },
+}
+
+impl Display for MapFileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MapFileError::Io { path, source } => {
+                write!(
+                    f,
+                    "could not access map file {}: {}",
+                    path.display(),
+                    source
+                )
+            }
+            MapFileError::Serialize { path, message } => {
+                write!(
+                    f,
+                    "could not serialize map for {}: {}",
+                    path.display(),
+                    message
+                )
+            }
+            MapFileError::Parse { path, message } => {
+                write!(
+                    f,
+                    "could not parse map file {}: {}",
+                    path.display(),
+                    message
+                )
+            }
+            MapFileError::UnsupportedVersion { path, version } => write!(
+                f,
+                "map file {} has format version {} but only version {} is supported",
+                path.display(),
+                version,
+                MAP_FORMAT_VERSION
+            ),
+            MapFileError::DimensionMismatch {
+                path,
+                layer,
+                width,
+                height,
+                row: Some(row),
+            } => write!(
+                f,
+                "row {} of the {} layer of map file {} does not have {} tiles (map is {}x{})",
+                row,
+                layer,
+                path.display(),
+                width,
+                width,
+                height
+            ),
+            MapFileError::DimensionMismatch {
+                path,
+                layer,
+                width,
+                height,
+                row: None,
+            } => write!(
+                f,
+                "the {} layer of map file {} does not have {} rows (map is {}x{})",
+                layer,
+                path.display(),
+                height,
+                width,
+                height
+            ),
+        }
+    }
+}
+
+impl std::error::Error for MapFileError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            MapFileError::Io { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+impl TileMap {
+    /// Writes the map to the file, as JSON if the path ends in `.json` and as
+    /// RON otherwise.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), MapFileError> {
+        let path = path.as_ref();
+        let file = MapFileRef {
+            version: MAP_FORMAT_VERSION,
+            map: self,
+        };
+
+        let contents = match MapFormat::of(path) {
+            MapFormat::Json => serde_json::to_string_pretty(&file).map_err(|e| e.to_string()),
+            MapFormat::Ron => ron::ser::to_string_pretty(&file, ron::ser::PrettyConfig::default())
+                .map_err(|e| e.to_string()),
+        }
+        .map_err(|message| MapFileError::Serialize {
+            path: path.to_owned(),
+            message,
+        })?;
+
+        fs::write(path, contents).map_err(|source| MapFileError::Io {
+            path: path.to_owned(),
+            source,
+        })
+    }
+
+    /// Reads a map written by [`TileMap::save`] from the file.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, MapFileError> {
+        let path = path.as_ref();
+        let contents = fs::read_to_string(path).map_err(|source| MapFileError::Io {
+            path: path.to_owned(),
+            source,
+        })?;
+
+        let format = MapFormat::of(path);
+        let MapFileVersion { version } = parse(path, format, &contents)?;
+        if version != MAP_FORMAT_VERSION {
+            return Err(MapFileError::UnsupportedVersion {
+                path: path.to_owned(),
+                version,
+            });
+        }
+
+        let MapFile { map } = parse(path, format, &contents)?;
+        check_dimensions(path, &map, MapLayer::Terrain, &map.tiles)?;
+        check_dimensions(path, &map, MapLayer::Features, &map.features)?;
+
+        Ok(map)
+    }
+}
+
+fn check_dimensions<T>(
+    path: &Path,
+    map: &TileMap,
+    layer: MapLayer,
+    rows: &[Vec<T>],
+) -> Result<(), MapFileError> {
+    let dimension_mismatch = |row| MapFileError::DimensionMismatch {
+        path: path.to_owned(),
+        layer,
+        width: map.width,
+        height: map.height,
+        row,
+    };
+    if map.width == 0 || map.height == 0 || rows.len() != map.height {
+        return Err(dimension_mismatch(None));
+    }
+    if let Some(row) = rows.iter().position(|row| row.len() != map.width) {
+        return Err(dimension_mismatch(Some(row)));
+    }
+
+    Ok(())
+}
+
+fn parse<T: for<'de> Deserialize<'de>>(
+    path: &Path,
+    format: MapFormat,
+    contents: &str,
+) -> Result<T, MapFileError> {
+    match format {
+        MapFormat::Json => serde_json::from_str(contents).map_err(|e| e.to_string()),
+        MapFormat::Ron => ron::from_str(contents).map_err(|e| e.to_string()),
+    }
+    .map_err(|message| MapFileError::Parse {
+        path: path.to_owned(),
+        message,
+    })
+}
+
+/// Replaces the spawned tiles with the map loaded from the file. If the map
+/// cannot be loaded an error is logged and the current tiles are kept.
+pub struct LoadMapCommand(pub PathBuf);
+
+impl Command for LoadMapCommand {
+    fn apply(self, world: &mut World) {
+        let map = match TileMap::load(&self.0) {
+            Ok(map) => map,
+            Err(error) => {
+                error!("{}", error);
+                return;
+            }
+        };
+
+        let mut tile_index = world.get_resource_or_insert_with(TileIndex::default);
+        let tiles: Vec<_> = tile_index.iter().collect();
+        for &(entity, _) in tiles.iter() {
+            tile_index.remove(entity);
+        }
+
+        for &(entity, _) in tiles.iter() {
+            if let Some(tile) = world.get_entity_mut(entity) {
+                tile.despawn_recursive();
+            }
+        }
+
+        if !tiles.is_empty() {
+            world.send_event(MapChanged {
+                positions: tiles.into_iter().map(|(_, position)| position).collect(),
+            });
+        }
+
+        map.apply(world);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        generation::GenerationConfig,
+        map::{tests, Terrain, Tile},
+    };
+
+    /// A path in the temporary directory which is removed on drop.
+    struct TempPath(PathBuf);
+
+    impl TempPath {
+        fn new(name: &str) -> Self {
+            TempPath(std::env::temp_dir().join(format!(
+                "mousetoria-{}-{}",
+                std::process::id(),
+                name
+            )))
+        }
+    }
+
+    impl Drop for TempPath {
+        fn drop(&mut self) {
+            let _ = fs::remove_file(&self.0);
+        }
+    }
+
+    fn same(a: &TileMap, b: &TileMap) -> bool {
+        serde_json::to_value(a).unwrap() == serde_json::to_value(b).unwrap()
+    }
+
+    #[test]
+    fn round_trip_generated_map() {
+        let map = TileMap::generate(24, 16, 5, &GenerationConfig::default());
+        assert!(map.features.iter().flatten().any(Option::is_some));
+
+        for name in ["map.ron", "map.json", "map.RON", "map.JSON"] {
+            let path = TempPath::new(name);
+            map.save(&path.0).unwrap();
+            let loaded = TileMap::load(&path.0).unwrap();
+            assert!(same(&map, &loaded), "{}", name);
+        }
+
+        let json = TempPath::new("format.json");
+        map.save(&json.0).unwrap();
+        assert!(fs::read_to_string(&json.0)
+            .unwrap()
+            .trim_start()
+            .starts_with('{'));
+    }
+
+    #[test]
+    fn corrupted_files() {
+        let path = TempPath::new("corrupted.ron");
+        let load = |contents: &str| {
+            fs::write(&path.0, contents).unwrap();
+            TileMap::load(&path.0)
+        };
+
+        let error = load("(version: 2, map: (width: 2").unwrap_err();
+        assert!(matches!(&error, MapFileError::Parse { path: p, .. } if *p == path.0));
+        assert!(error.to_string().starts_with("could not parse map file"));
+        assert!(matches!(
+            load("not a map at all"),
+            Err(MapFileError::Parse { .. })
+        ));
+
+        let mut map = tests::plains(3, 2);
+        map.save(&path.0).unwrap();
+        let stale = fs::read_to_string(&path.0)
+            .unwrap()
+            .replace("version: 2", "version: 1");
+        assert!(matches!(
+            load(&stale),
+            Err(MapFileError::UnsupportedVersion { version: 1, .. })
+        ));
+
+        map.tiles[1].pop();
+        map.save(&path.0).unwrap();
+        let error = TileMap::load(&path.0).unwrap_err();
+        assert!(matches!(
+            error,
+            MapFileError::DimensionMismatch {
+                layer: MapLayer::Terrain,
+                width: 3,
+                height: 2,
+                row: Some(1),
+                ..
+            }
+        ));
+
+        let mut map = tests::plains(3, 2);
+        map.features.pop();
+        map.save(&path.0).unwrap();
+        assert!(matches!(
+            TileMap::load(&path.0),
+            Err(MapFileError::DimensionMismatch {
+                layer: MapLayer::Features,
+                row: None,
+                ..
+            })
+        ));
+
+        let missing = TempPath::new("missing.ron");
+        let error = TileMap::load(&missing.0).unwrap_err();
+        assert!(matches!(error, MapFileError::Io { .. }));
+        assert!(std::error::Error::source(&error).is_some());
+    }
+
+    #[test]
+    fn load_map_command_replaces_the_tiles() {
+        let path = TempPath::new("command.ron");
+        let mut map = tests::plains(4, 2);
+        map[(3, 1)] = Terrain::Mountain.as_display("mountain.png");
+        map.save(&path.0).unwrap();
+
+        let mut app = tests::app(|| {});
+        tests::plains(3, 1).apply(&mut app.world);
+        app.update();
+        let old: Vec<_> = app
+            .world
+            .resource::<TileIndex>()
+            .iter()
+            .map(|(entity, _)| entity)
+            .collect();
+
+        LoadMapCommand(path.0.clone()).apply(&mut app.world);
+        app.update();
+        let tile_index = app.world.resource::<TileIndex>();
+        assert_eq!(tile_index.len(), 8);
+        assert!(old
+            .iter()
+            .all(|&entity| app.world.get_entity(entity).is_none()));
+        let mountain = tile_index.get(3, 1).unwrap();
+        assert_eq!(
+            app.world.get::<Tile>(mountain).unwrap().terrain,
+            Terrain::Mountain
+        );
+
+        // A broken file keeps the tiles.
+        fs::write(&path.0, "broken").unwrap();
+        LoadMapCommand(path.0.clone()).apply(&mut app.world);
+        app.update();
+        assert_eq!(app.world.resource::<TileIndex>().get(3, 1), Some(mountain));
+    }
+}
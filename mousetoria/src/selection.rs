@@ -0,0 +1,163 @@
+use bevy::prelude::*;
+
+use crate::picking::TileClicked;
+
+/// Marks the selected tiles.
+#[derive(Component, Debug)]
+pub struct Selected;
+
+/// The most recently selected tile, `None` once it is deselected.
+#[derive(Resource, Default, Debug)]
+pub struct SelectedTile(pub Option<Entity>);
+
+/// Selects tiles clicked with the left mouse button, replacing the previous
+/// selection. Clicking the selected tile deselects it, and shift-clicking
+/// adds the tile to the selection or removes it from it.
+pub fn select_tiles(
+    mut commands: Commands,
+    mut tile_clicked: EventReader<TileClicked>,
+    keys: Res<Input<KeyCode>>,
+    mut selected_tile: ResMut<SelectedTile>,
+    selected_query: Query<Entity, With<Selected>>,
+) {
+    let extend = keys.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]);
+
+    // Commands are applied after the system, so the selection is tracked here
+    // for when there are several clicks at once.
+    let mut selected: Vec<Entity> = selected_query.iter().collect();
+    for event in tile_clicked.read() {
+        if event.button != MouseButton::Left {
+            continue;
+        }
+
+        let was_selected = selected.contains(&event.entity);
+        if !extend {
+            for entity in selected.drain(..) {
+                commands.entity(entity).remove::<Selected>();
+            }
+            selected_tile.0 = None;
+        }
+
+        if was_selected {
+            selected.retain(|&entity| entity != event.entity);
+            commands.entity(event.entity).remove::<Selected>();
+            if selected_tile.0 == Some(event.entity) {
+                selected_tile.0 = None;
+            }
+        } else {
+            selected.push(event.entity);
+            commands.entity(event.entity).insert(Selected);
+            selected_tile.0 = Some(event.entity);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        map::{Tile, TileIndex},
+        picking::tests::{app, click},
+    };
+
+    fn selection_app() -> App {
+        let mut app = app(select_tiles);
+        app.init_resource::<Input<KeyCode>>()
+            .init_resource::<SelectedTile>();
+        app
+    }
+
+    /// The coordinates of the selected tiles and of [`SelectedTile`].
+    fn selection(app: &mut App) -> (Vec<usize>, Option<usize>) {
+        let mut selected: Vec<_> = app
+            .world
+            .query_filtered::<&Tile, With<Selected>>()
+            .iter(&app.world)
+            .map(|tile| tile.x)
+            .collect();
+        selected.sort_unstable();
+        let last = app
+            .world
+            .resource::<SelectedTile>()
+            .0
+            .map(|entity| app.world.get::<Tile>(entity).unwrap().x);
+        (selected, last)
+    }
+
+    fn shift(app: &mut App, pressed: bool) {
+        let mut keys = app.world.resource_mut::<Input<KeyCode>>();
+        if pressed {
+            keys.press(KeyCode::ShiftLeft);
+        } else {
+            keys.release(KeyCode::ShiftLeft);
+        }
+    }
+
+    #[test]
+    fn click_to_select() {
+        let mut app = selection_app();
+        assert_eq!(selection(&mut app), (vec![], None));
+
+        click(&mut app, 0, 0, MouseButton::Left);
+        assert_eq!(selection(&mut app), (vec![0], Some(0)));
+
+        click(&mut app, 1, 0, MouseButton::Left);
+        assert_eq!(selection(&mut app), (vec![1], Some(1)));
+
+        // Other buttons leave the selection alone.
+        click(&mut app, 2, 0, MouseButton::Right);
+        assert_eq!(selection(&mut app), (vec![1], Some(1)));
+
+        click(&mut app, 1, 0, MouseButton::Left);
+        assert_eq!(selection(&mut app), (vec![], None));
+    }
+
+    #[test]
+    fn shift_click_to_extend() {
+        let mut app = selection_app();
+
+        click(&mut app, 0, 0, MouseButton::Left);
+        shift(&mut app, true);
+        click(&mut app, 2, 0, MouseButton::Left);
+        assert_eq!(selection(&mut app), (vec![0, 2], Some(2)));
+        click(&mut app, 1, 0, MouseButton::Left);
+        assert_eq!(selection(&mut app), (vec![0, 1, 2], Some(1)));
+
+        // Removing another tile keeps the most recently selected one.
+        click(&mut app, 0, 0, MouseButton::Left);
+        assert_eq!(selection(&mut app), (vec![1, 2], Some(1)));
+        click(&mut app, 1, 0, MouseButton::Left);
+        assert_eq!(selection(&mut app), (vec![2], None));
+
+        // Without shift a selected tile of several is deselected with the
+        // others.
+        click(&mut app, 1, 0, MouseButton::Left);
+        shift(&mut app, false);
+        click(&mut app, 2, 0, MouseButton::Left);
+        assert_eq!(selection(&mut app), (vec![], None));
+    }
+
+    #[test]
+    fn several_clicks_in_one_frame() {
+        let mut app = selection_app();
+        let tile_index = app.world.resource::<TileIndex>();
+        let clicks: Vec<_> = [0, 1, 2, 1]
+            .into_iter()
+            .map(|x| TileClicked {
+                entity: tile_index.get(x, 0).unwrap(),
+                x,
+                y: 0,
+                button: MouseButton::Left,
+            })
+            .collect();
+
+        app.world.send_event_batch(clicks.clone());
+        app.update();
+        assert_eq!(selection(&mut app), (vec![1], Some(1)));
+
+        shift(&mut app, true);
+        app.world.send_event_batch(clicks);
+        app.update();
+        assert_eq!(selection(&mut app), (vec![0, 1, 2], Some(1)));
+    }
+}
@@ -0,0 +1,558 @@
+use std::collections::HashMap;
+
+use bevy::{ecs::system::Command, prelude::*};
+
+use crate::{
+    map::{GridKind, MapConfig, TerrainRules, Tile, TileIndex, SCALE_FACTOR, TILE_SIZE},
+    pathfinding::reachable,
+    selection::SelectedTile,
+};
+
+/// How far above the tiles and their features units are drawn.
+const UNIT_Z: f32 = 10.0;
+
+/// Something standing on the tile at the coordinates which can move around
+/// the map.
+#[derive(Component, Clone, Debug)]
+pub struct Unit {
+    pub x: usize,
+    pub y: usize,
+    /// The side the unit fights for.
+    pub owner: u32,
+    /// How much terrain cost the unit can spend on moving.
+    pub movement: f32,
+}
+
+/// The units by the coordinates of the tile they stand on.
+#[derive(Resource, Default, Debug)]
+pub struct TileOccupancy {
+    units: HashMap<(usize, usize), Entity>,
+    positions: HashMap<Entity, (usize, usize)>,
+}
+
+impl TileOccupancy {
+    pub fn get(&self, x: usize, y: usize) -> Option<Entity> {
+        self.units.get(&(x, y)).copied()
+    }
+
+    pub fn is_occupied(&self, x: usize, y: usize) -> bool {
+        self.units.contains_key(&(x, y))
+    }
+
+    pub fn position_of(&self, unit: Entity) -> Option<(usize, usize)> {
+        self.positions.get(&unit).copied()
+    }
+
+    pub fn len(&self) -> usize {
+        self.units.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.units.is_empty()
+    }
+
+    /// Records the unit on the tile, forgetting where it was before.
+    fn insert(&mut self, unit: Entity, position: (usize, usize)) {
+        self.remove(unit);
+        self.units.insert(position, unit);
+        self.positions.insert(unit, position);
+    }
+
+    /// Forgets the unit, returning where it was.
+    fn remove(&mut self, unit: Entity) -> Option<(usize, usize)> {
+        let position = self.positions.remove(&unit)?;
+        self.units.remove(&position);
+        Some(position)
+    }
+}
+
+/// Spawns a unit on the tile at the coordinates, which must be free.
+pub struct SpawnUnit {
+    pub x: usize,
+    pub y: usize,
+    pub owner: u32,
+    pub movement: f32,
+    pub sprite: String,
+}
+
+impl Command for SpawnUnit {
+    fn apply(self, world: &mut World) {
+        let occupancy = world.get_resource_or_insert_with(TileOccupancy::default);
+        if let Some(other) = occupancy.get(self.x, self.y) {
+            warn!(
+                "cannot spawn a unit at ({}, {}) occupied by {:?}",
+                self.x, self.y, other
+            );
+            return;
+        }
+
+        let grid = grid_of(world);
+        let texture = world.resource::<AssetServer>().load::<Image>(self.sprite);
+        let unit = world
+            .spawn((
+                SpriteBundle {
+                    texture,
+                    sprite: Sprite {
+                        custom_size: Some(Vec2::new(TILE_SIZE, TILE_SIZE)),
+                        ..default()
+                    },
+                    transform: Transform::from_translation(
+                        grid.tile_to_world(self.x, self.y).extend(UNIT_Z),
+                    )
+                    .with_scale(Vec3::splat(SCALE_FACTOR)),
+                    ..default()
+                },
+                Unit {
+                    x: self.x,
+                    y: self.y,
+                    owner: self.owner,
+                    movement: self.movement,
+                },
+            ))
+            .id();
+
+        world
+            .resource_mut::<TileOccupancy>()
+            .insert(unit, (self.x, self.y));
+    }
+}
+
+/// Sent when a unit moved `from` one tile `to` another.
+#[derive(Event, Debug, Clone, Copy)]
+pub struct UnitMoved {
+    pub unit: Entity,
+    pub from: (usize, usize),
+    pub to: (usize, usize),
+}
+
+/// Why a [`MoveUnit`] was rejected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MoveRejection {
+    /// The entity is not a unit.
+    NotAUnit,
+    /// There is no tile at the destination.
+    NoTile,
+    /// The terrain at the destination cannot be entered.
+    Impassable,
+    /// Another unit stands at the destination.
+    Occupied(Entity),
+}
+
+/// Sent when a [`MoveUnit`] was rejected, leaving the unit where it was.
+#[derive(Event, Debug, Clone, Copy)]
+pub struct UnitMoveRejected {
+    pub unit: Entity,
+    pub to: (usize, usize),
+    pub reason: MoveRejection,
+}
+
+/// Moves the unit onto the tile at `to` if it can be entered and no other
+/// unit stands on it, sending [`UnitMoved`] or [`UnitMoveRejected`].
+pub struct MoveUnit {
+    pub unit: Entity,
+    pub to: (usize, usize),
+}
+
+impl MoveUnit {
+    fn check(&self, world: &World) -> Result<(usize, usize), MoveRejection> {
+        let unit = world
+            .get::<Unit>(self.unit)
+            .ok_or(MoveRejection::NotAUnit)?;
+        let tile = world
+            .get_resource::<TileIndex>()
+            .and_then(|tile_index| tile_index.get(self.to.0, self.to.1))
+            .and_then(|entity| world.get::<Tile>(entity))
+            .ok_or(MoveRejection::NoTile)?;
+
+        let default_rules = TerrainRules::default();
+        let rules = world
+            .get_resource::<TerrainRules>()
+            .unwrap_or(&default_rules);
+        if tile.move_cost(rules).is_none() {
+            return Err(MoveRejection::Impassable);
+        }
+
+        let occupant = world
+            .get_resource::<TileOccupancy>()
+            .and_then(|occupancy| occupancy.get(self.to.0, self.to.1));
+        match occupant {
+            Some(other) if other != self.unit => Err(MoveRejection::Occupied(other)),
+            _ => Ok((unit.x, unit.y)),
+        }
+    }
+}
+
+impl Command for MoveUnit {
+    fn apply(self, world: &mut World) {
+        let from = match self.check(world) {
+            Ok(from) => from,
+            Err(reason) => {
+                warn!("cannot move {:?} to {:?}: {:?}", self.unit, self.to, reason);
+                world.send_event(UnitMoveRejected {
+                    unit: self.unit,
+                    to: self.to,
+                    reason,
+                });
+                return;
+            }
+        };
+
+        world
+            .get_resource_or_insert_with(TileOccupancy::default)
+            .insert(self.unit, self.to);
+
+        let grid = grid_of(world);
+        let mut unit = world.entity_mut(self.unit);
+        if let Some(mut transform) = unit.get_mut::<Transform>() {
+            let z = transform.translation.z;
+            transform.translation = grid.tile_to_world(self.to.0, self.to.1).extend(z);
+        }
+        if let Some(mut unit) = unit.get_mut::<Unit>() {
+            (unit.x, unit.y) = self.to;
+        }
+
+        world.send_event(UnitMoved {
+            unit: self.unit,
+            from,
+            to: self.to,
+        });
+    }
+}
+
+fn grid_of(world: &World) -> GridKind {
+    world
+        .get_resource::<MapConfig>()
+        .map_or_else(GridKind::default, |config| config.grid)
+}
+
+/// Frees the tiles of despawned units.
+pub fn forget_despawned_units(
+    mut occupancy: ResMut<TileOccupancy>,
+    mut removed_units: RemovedComponents<Unit>,
+) {
+    for unit in removed_units.read() {
+        occupancy.remove(unit);
+    }
+}
+
+/// Marks the tiles the selected unit can move onto.
+#[derive(Component, Debug)]
+pub struct Highlighted {
+    /// The cheapest cost of moving onto the tile.
+    pub cost: f32,
+}
+
+/// The tint of highlighted tiles.
+pub const HIGHLIGHT_COLOR: Color = Color::rgb(0.6, 0.8, 1.0);
+
+/// Highlights the tiles the unit on the selected tile can reach with its
+/// movement and which no other unit stands on, replacing the highlights
+/// whenever the selection changes or a unit moves.
+#[allow(clippy::too_many_arguments)]
+pub fn highlight_movement_range(
+    mut commands: Commands,
+    selected_tile: Res<SelectedTile>,
+    map_config: Option<Res<MapConfig>>,
+    rules: Res<TerrainRules>,
+    tile_index: Res<TileIndex>,
+    occupancy: Res<TileOccupancy>,
+    mut unit_moved: EventReader<UnitMoved>,
+    units: Query<&Unit>,
+    tiles: Query<&Tile>,
+    highlighted: Query<Entity, With<Highlighted>>,
+    mut sprites: Query<&mut Sprite, With<Tile>>,
+) {
+    // Read the events even when the selection changed, or they would trigger
+    // the highlighting again next frame.
+    let any_unit_moved = unit_moved.read().count() > 0;
+    if !selected_tile.is_changed() && !any_unit_moved {
+        return;
+    }
+
+    for entity in &highlighted {
+        commands.entity(entity).remove::<Highlighted>();
+        if let Ok(mut sprite) = sprites.get_mut(entity) {
+            sprite.color = Color::WHITE;
+        }
+    }
+
+    let (Some(map_config), Some(tile)) = (
+        map_config,
+        selected_tile.0.and_then(|entity| tiles.get(entity).ok()),
+    ) else {
+        return;
+    };
+    let Some((unit_entity, unit)) = occupancy
+        .get(tile.x, tile.y)
+        .and_then(|entity| Some((entity, units.get(entity).ok()?)))
+    else {
+        return;
+    };
+
+    let reached = reachable(
+        (unit.x, unit.y),
+        unit.movement,
+        (map_config.width, map_config.height),
+        map_config.grid,
+        |(x, y)| tiles.get(tile_index.get(x, y)?).ok()?.move_cost(&rules),
+    );
+
+    for ((x, y), cost) in reached {
+        if occupancy
+            .get(x, y)
+            .is_some_and(|other| other != unit_entity)
+        {
+            continue;
+        }
+
+        let Some(entity) = tile_index.get(x, y) else {
+            continue;
+        };
+
+        commands.entity(entity).insert(Highlighted { cost });
+        if let Ok(mut sprite) = sprites.get_mut(entity) {
+            sprite.color = HIGHLIGHT_COLOR;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::system::Command;
+
+    use super::*;
+    use crate::map::{tests, tile_to_world, SetTile, Terrain};
+
+    /// A headless app with a 3 by 3 map of plains, running `systems` after
+    /// keeping the tiles up to date.
+    fn app<M>(systems: impl IntoSystemConfigs<M>) -> App {
+        let mut app = tests::app(systems);
+        app.init_resource::<TerrainRules>()
+            .init_resource::<TileOccupancy>()
+            .init_resource::<SelectedTile>()
+            .add_event::<UnitMoved>()
+            .add_event::<UnitMoveRejected>();
+        tests::plains(3, 3).apply(&mut app.world);
+        app.update();
+        app
+    }
+
+    fn spawn_unit(app: &mut App, (x, y): (usize, usize), movement: f32) -> Entity {
+        SpawnUnit {
+            x,
+            y,
+            owner: 0,
+            movement,
+            sprite: "mouse.png".into(),
+        }
+        .apply(&mut app.world);
+        app.world.resource::<TileOccupancy>().get(x, y).unwrap()
+    }
+
+    fn select(app: &mut App, position: Option<(usize, usize)>) {
+        let tile = position.and_then(|(x, y)| app.world.resource::<TileIndex>().get(x, y));
+        app.world.resource_mut::<SelectedTile>().0 = tile;
+    }
+
+    /// The highlighted tiles and their costs, ordered by their coordinates.
+    fn highlighted(app: &mut App) -> Vec<((usize, usize), f32)> {
+        let mut highlighted: Vec<_> = app
+            .world
+            .query::<(&Tile, &Highlighted, &Sprite)>()
+            .iter(&app.world)
+            .map(|(tile, highlighted, sprite)| {
+                assert_eq!(sprite.color, HIGHLIGHT_COLOR);
+                ((tile.x, tile.y), highlighted.cost)
+            })
+            .collect();
+        highlighted.sort_by_key(|&(position, _)| position);
+        highlighted
+    }
+
+    #[test]
+    fn highlights_follow_the_selection() {
+        let mut app = app(highlight_movement_range);
+        let mouse = spawn_unit(&mut app, (0, 0), 1.0);
+        let other = spawn_unit(&mut app, (1, 0), 1.0);
+
+        select(&mut app, Some((0, 0)));
+        app.update();
+        // The other unit stands east of the selected one.
+        assert_eq!(highlighted(&mut app), [((0, 0), 0.0), ((0, 1), 1.0)]);
+
+        MoveUnit {
+            unit: other,
+            to: (2, 2),
+        }
+        .apply(&mut app.world);
+        app.update();
+        assert_eq!(
+            highlighted(&mut app),
+            [((0, 0), 0.0), ((0, 1), 1.0), ((1, 0), 1.0)]
+        );
+
+        // Selecting a tile without a unit clears the highlights.
+        select(&mut app, Some((1, 1)));
+        app.update();
+        assert_eq!(highlighted(&mut app), []);
+
+        select(&mut app, Some((2, 2)));
+        app.update();
+        assert_eq!(
+            highlighted(&mut app),
+            [((1, 2), 1.0), ((2, 1), 1.0), ((2, 2), 0.0)]
+        );
+
+        select(&mut app, None);
+        app.update();
+        assert_eq!(highlighted(&mut app), []);
+        assert!(app
+            .world
+            .query::<&Sprite>()
+            .iter(&app.world)
+            .all(|sprite| sprite.color == Color::WHITE));
+        assert!(app.world.get::<Unit>(mouse).is_some());
+    }
+
+    #[test]
+    fn highlights_stay_without_changes() {
+        let mut app = app(highlight_movement_range);
+        let mouse = spawn_unit(&mut app, (1, 1), 1.0);
+
+        // Moving while selecting reads the movement along with the selection.
+        select(&mut app, Some((1, 1)));
+        MoveUnit {
+            unit: mouse,
+            to: (1, 1),
+        }
+        .apply(&mut app.world);
+        app.update();
+        assert_eq!(highlighted(&mut app).len(), 5);
+
+        let center = app.world.resource::<TileIndex>().get(1, 1).unwrap();
+        let ticks = |app: &App| {
+            app.world
+                .entity(center)
+                .get_change_ticks::<Highlighted>()
+                .unwrap()
+                .last_changed_tick()
+        };
+        let before = ticks(&app);
+        app.update();
+        app.update();
+        assert_eq!(ticks(&app), before);
+        assert_eq!(highlighted(&mut app).len(), 5);
+    }
+
+    #[test]
+    fn occupancy_through_moves() {
+        let mut app = app(forget_despawned_units);
+        let mut moved = app.world.resource::<Events<UnitMoved>>().get_reader();
+        let mut rejected = app
+            .world
+            .resource::<Events<UnitMoveRejected>>()
+            .get_reader();
+        let mut move_unit = |app: &mut App, unit, to| {
+            MoveUnit { unit, to }.apply(&mut app.world);
+            let moved: Vec<_> = moved
+                .read(app.world.resource::<Events<UnitMoved>>())
+                .map(|event| (event.unit, event.from, event.to))
+                .collect();
+            let rejected: Vec<_> = rejected
+                .read(app.world.resource::<Events<UnitMoveRejected>>())
+                .map(|event| (event.unit, event.to, event.reason))
+                .collect();
+            (moved, rejected)
+        };
+        let position = |app: &App, unit| {
+            let occupancy = app.world.resource::<TileOccupancy>();
+            let position = occupancy.position_of(unit);
+            if let Some((x, y)) = position {
+                assert_eq!(occupancy.get(x, y), Some(unit));
+                let component = app.world.get::<Unit>(unit).unwrap();
+                assert_eq!((component.x, component.y), (x, y));
+                let translation = app.world.get::<Transform>(unit).unwrap().translation;
+                assert_eq!(translation, tile_to_world(x, y).extend(UNIT_Z));
+            }
+            position
+        };
+
+        let mouse = spawn_unit(&mut app, (0, 0), 3.0);
+        let cat = spawn_unit(&mut app, (2, 2), 3.0);
+        assert_eq!(position(&app, mouse), Some((0, 0)));
+        assert_eq!(position(&app, cat), Some((2, 2)));
+        assert_eq!(app.world.resource::<TileOccupancy>().len(), 2);
+
+        // Spawning onto a unit is refused.
+        SpawnUnit {
+            x: 2,
+            y: 2,
+            owner: 1,
+            movement: 1.0,
+            sprite: "mouse.png".into(),
+        }
+        .apply(&mut app.world);
+        assert_eq!(app.world.resource::<TileOccupancy>().len(), 2);
+        assert_eq!(app.world.query::<&Unit>().iter(&app.world).count(), 2);
+
+        assert_eq!(
+            move_unit(&mut app, mouse, (1, 0)),
+            (vec![(mouse, (0, 0), (1, 0))], vec![])
+        );
+        assert_eq!(position(&app, mouse), Some((1, 0)));
+        assert!(!app.world.resource::<TileOccupancy>().is_occupied(0, 0));
+
+        assert_eq!(
+            move_unit(&mut app, mouse, (2, 2)),
+            (vec![], vec![(mouse, (2, 2), MoveRejection::Occupied(cat))])
+        );
+        SetTile {
+            x: 2,
+            y: 0,
+            display: Terrain::Water.as_display("water.png"),
+        }
+        .apply(&mut app.world);
+        assert_eq!(
+            move_unit(&mut app, mouse, (2, 0)),
+            (vec![], vec![(mouse, (2, 0), MoveRejection::Impassable)])
+        );
+        assert_eq!(
+            move_unit(&mut app, mouse, (3, 0)),
+            (vec![], vec![(mouse, (3, 0), MoveRejection::NoTile)])
+        );
+        let tile = app.world.resource::<TileIndex>().get(0, 0).unwrap();
+        assert_eq!(
+            move_unit(&mut app, tile, (0, 1)),
+            (vec![], vec![(tile, (0, 1), MoveRejection::NotAUnit)])
+        );
+        assert_eq!(position(&app, mouse), Some((1, 0)));
+        assert_eq!(position(&app, cat), Some((2, 2)));
+
+        // Staying in place is a move.
+        assert_eq!(
+            move_unit(&mut app, cat, (2, 2)),
+            (vec![(cat, (2, 2), (2, 2))], vec![])
+        );
+        assert_eq!(
+            move_unit(&mut app, cat, (1, 1)),
+            (vec![(cat, (2, 2), (1, 1))], vec![])
+        );
+        assert_eq!(
+            move_unit(&mut app, mouse, (2, 2)),
+            (vec![(mouse, (1, 0), (2, 2))], vec![])
+        );
+        assert_eq!(app.world.resource::<TileOccupancy>().len(), 2);
+
+        // Despawned units free their tiles.
+        app.world.despawn(cat);
+        app.update();
+        assert_eq!(position(&app, cat), None);
+        assert_eq!(app.world.resource::<TileOccupancy>().len(), 1);
+        assert_eq!(
+            move_unit(&mut app, mouse, (1, 1)),
+            (vec![(mouse, (2, 2), (1, 1))], vec![])
+        );
+        assert_eq!(position(&app, mouse), Some((1, 1)));
+        assert_eq!(app.world.resource::<TileOccupancy>().len(), 1);
+    }
+}
@@ -0,0 +1,311 @@
+use crate::{Direction, Index, ItemID, Query, QueryOptions, Table, TableError, TypedIndex, Value};
+
+/// Builds a [`Query`] together with its [`QueryOptions`] and runs it against a
+/// [`Table`], see [`Table::query`].
+///
+/// Conditions are combined from left to right, so
+/// `q.eq(a, 1).eq(b, 2).or(|q| q.eq(c, 3))` matches items where either both
+/// `a` and `b` match or `c` matches. A builder without any conditions matches
+/// every item.
+#[derive(Debug)]
+pub struct QueryBuilder<'a, T, I: Index<T>> {
+    table: &'a Table<T, I>,
+    query: Option<Query<I>>,
+    options: QueryOptions<I>,
+}
+
+impl<T, I: Index<T>> Table<T, I> {
+    /// Starts building a query against the [`Table`].
+    pub fn query(&self) -> QueryBuilder<'_, T, I> {
+        QueryBuilder {
+            table: self,
+            query: None,
+            options: QueryOptions::default(),
+        }
+    }
+}
+
+impl<'a, T, I: Index<T>> QueryBuilder<'a, T, I> {
+    /// Adds a condition which must match in addition to the earlier ones.
+    pub fn filter(mut self, query: Query<I>) -> Self {
+        self.query = Some(match self.query.take() {
+            None => query,
+            Some(Query::And(mut children)) => {
+                children.push(query);
+                Query::And(children)
+            }
+            Some(earlier) => Query::and([earlier, query]),
+        });
+
+        self
+    }
+
+    pub fn eq<K: TypedIndex<I, V>, V: Into<Value>>(self, index: K, value: V) -> Self {
+        self.filter(Query::eq(index, value))
+    }
+
+    pub fn in_<K: TypedIndex<I, V>, V: Into<Value>>(
+        self,
+        index: K,
+        values: impl IntoIterator<Item = V>,
+    ) -> Self {
+        self.filter(Query::in_(index, values))
+    }
+
+    pub fn lt<K: TypedIndex<I, V>, V: Into<Value>>(self, index: K, value: V) -> Self {
+        self.filter(Query::lt(index, value))
+    }
+
+    pub fn lte<K: TypedIndex<I, V>, V: Into<Value>>(self, index: K, value: V) -> Self {
+        self.filter(Query::lte(index, value))
+    }
+
+    pub fn gt<K: TypedIndex<I, V>, V: Into<Value>>(self, index: K, value: V) -> Self {
+        self.filter(Query::gt(index, value))
+    }
+
+    pub fn gte<K: TypedIndex<I, V>, V: Into<Value>>(self, index: K, value: V) -> Self {
+        self.filter(Query::gte(index, value))
+    }
+
+    /// Matches values between `min` and `max`, both inclusive.
+    pub fn between<K: TypedIndex<I, V>, V: Into<Value>>(self, index: K, min: V, max: V) -> Self {
+        self.filter(Query::between(index, min, max))
+    }
+
+    pub fn starts_with(self, index: I, prefix: impl ToString) -> Self {
+        self.filter(Query::starts_with(index, prefix))
+    }
+
+    pub fn is_null(self, index: I) -> Self {
+        self.filter(Query::is_null(index))
+    }
+
+    /// Adds the conditions built by `f` as a group which must match in
+    /// addition to the earlier conditions.
+    pub fn and(self, f: impl FnOnce(Self) -> Self) -> Self {
+        match self.group(f) {
+            Some(group) => self.filter(group),
+            None => self,
+        }
+    }
+
+    /// Makes the items matching either the earlier conditions or the ones
+    /// built by `f` match. If there are no earlier conditions only the ones
+    /// built by `f` are used.
+    pub fn or(mut self, f: impl FnOnce(Self) -> Self) -> Self {
+        let group = self.group(f).unwrap_or_else(|| Query::and([]));
+        self.query = Some(match self.query.take() {
+            None => group,
+            Some(Query::Or(mut children)) => {
+                children.push(group);
+                Query::Or(children)
+            }
+            Some(earlier) => Query::or([earlier, group]),
+        });
+
+        self
+    }
+
+    /// Adds a condition which matches the items not matching the conditions
+    /// built by `f`.
+    pub fn not(self, f: impl FnOnce(Self) -> Self) -> Self {
+        let group = self.group(f).unwrap_or_else(|| Query::and([]));
+        self.filter(!group)
+    }
+
+    pub fn order_by(mut self, index: I) -> Self {
+        self.options.order_by = Some((index, Direction::Ascending));
+        self
+    }
+
+    pub fn order_by_desc(mut self, index: I) -> Self {
+        self.options.order_by = Some((index, Direction::Descending));
+        self
+    }
+
+    pub fn limit(mut self, limit: usize) -> Self {
+        self.options.limit = Some(limit);
+        self
+    }
+
+    pub fn offset(mut self, offset: usize) -> Self {
+        self.options.offset = offset;
+        self
+    }
+
+    /// Returns the built [`Query`] and [`QueryOptions`].
+    pub fn build(self) -> (Query<I>, QueryOptions<I>) {
+        (self.query.unwrap_or_else(|| Query::and([])), self.options)
+    }
+
+    /// Runs the query, see [`Table::select`].
+    pub fn fetch(self) -> Result<Vec<(ItemID, T)>, TableError<I>>
+    where
+        T: Clone,
+    {
+        let table = self.table;
+        let (query, options) = self.build();
+        table.select(&query, &options)
+    }
+
+    /// Returns how many items match the conditions, ignoring the limit and
+    /// offset.
+    pub fn count(self) -> Result<usize, TableError<I>> {
+        let table = self.table;
+        let (query, _) = self.build();
+        table.count_where(&query)
+    }
+
+    /// Runs `f` with an empty builder, returning the conditions it built.
+    fn group(&self, f: impl FnOnce(Self) -> Self) -> Option<Query<I>> {
+        f(self.table.query()).query
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ops::Bound;
+
+    use super::*;
+    use crate::table::tests::{names, users, Age, Name, UserIndex};
+
+    #[test]
+    fn builds_the_same_query_as_by_hand() {
+        let table = users([UserIndex::Name, UserIndex::Age]);
+
+        let (query, options) = table
+            .query()
+            .eq(Age, 30)
+            .starts_with(UserIndex::Name, "A")
+            .or(|q| q.in_(Age, [41, 25]).not(|q| q.eq(Name, "Bob")))
+            .and(|q| q.lt(Age, 50).gte(Age, 0))
+            .order_by_desc(UserIndex::Age)
+            .limit(2)
+            .offset(1)
+            .build();
+
+        let by_hand = Query::and([
+            Query::or([
+                Query::and([
+                    Query::eq(UserIndex::Age, 30),
+                    Query::starts_with(UserIndex::Name, "A"),
+                ]),
+                Query::and([
+                    Query::in_(UserIndex::Age, [41, 25]),
+                    !Query::eq(UserIndex::Name, "Bob"),
+                ]),
+            ]),
+            Query::and([Query::lt(UserIndex::Age, 50), Query::gte(UserIndex::Age, 0)]),
+        ]);
+        assert_eq!(query, by_hand);
+        assert_eq!(
+            options,
+            QueryOptions {
+                limit: Some(2),
+                offset: 1,
+                order_by: Some((UserIndex::Age, Direction::Descending)),
+            }
+        );
+
+        let fetched = table
+            .query()
+            .eq(Age, 30)
+            .starts_with(UserIndex::Name, "A")
+            .or(|q| q.in_(Age, [41, 25]).not(|q| q.eq(Name, "Bob")))
+            .and(|q| q.lt(Age, 50).gte(Age, 0))
+            .order_by_desc(UserIndex::Age)
+            .limit(2)
+            .offset(1)
+            .fetch()
+            .unwrap();
+        assert_eq!(fetched, table.select(&by_hand, &options).unwrap());
+        assert_eq!(names(&fetched), ["Ann", "Eve"]);
+    }
+
+    #[test]
+    fn plain_index_takes_literals() {
+        let table = users([UserIndex::Name, UserIndex::Age]);
+
+        let (query, _) = table
+            .query()
+            .eq(UserIndex::Age, 29)
+            .or(|q| q.eq(UserIndex::Name, "Max"))
+            .build();
+        assert_eq!(
+            query,
+            Query::or([
+                Query::Eq(UserIndex::Age, Value::int(29)),
+                Query::Eq(UserIndex::Name, Value::string("Max")),
+            ])
+        );
+        assert_eq!(table.query().eq(UserIndex::Age, 29).count(), Ok(0));
+
+        let (query, _) = table
+            .query()
+            .in_(UserIndex::Age, [25, 41])
+            .between(UserIndex::Age, 20_u8, 40_u8)
+            .gt(UserIndex::Name, String::from("B"))
+            .build();
+        assert_eq!(
+            query,
+            Query::and([
+                Query::In(UserIndex::Age, vec![Value::int(25), Value::int(41)]),
+                Query::Range(
+                    UserIndex::Age,
+                    Bound::Included(Value::int(20)),
+                    Bound::Included(Value::int(40))
+                ),
+                Query::Range(
+                    UserIndex::Name,
+                    Bound::Excluded(Value::string("B")),
+                    Bound::Unbounded
+                ),
+            ])
+        );
+        let fetched = table
+            .query()
+            .in_(UserIndex::Age, [25, 41])
+            .between(UserIndex::Age, 20_u8, 40_u8)
+            .gt(UserIndex::Name, String::from("B"))
+            .fetch()
+            .unwrap();
+        assert_eq!(names(&fetched), ["Bob", "Eve"]);
+    }
+
+    #[test]
+    fn single_conditions() {
+        let table = users([]);
+
+        let (query, options) = table.query().build();
+        assert_eq!(query, Query::and([]));
+        assert_eq!(options, QueryOptions::default());
+
+        let (query, _) = table.query().between(Age, 25, 30).build();
+        assert_eq!(
+            query,
+            Query::Range(
+                UserIndex::Age,
+                Bound::Included(25.into()),
+                Bound::Included(30.into())
+            )
+        );
+
+        let (query, _) = table.query().or(|q| q.is_null(UserIndex::Email)).build();
+        assert_eq!(query, Query::is_null(UserIndex::Email));
+
+        let (query, _) = table
+            .query()
+            .gt(Age, 1)
+            .lte(UserIndex::Age, 2)
+            .order_by(UserIndex::Name)
+            .build();
+        assert_eq!(
+            query,
+            Query::and([Query::gt(UserIndex::Age, 1), Query::lte(UserIndex::Age, 2),])
+        );
+
+        assert_eq!(table.query().eq(Age, 30).count(), Ok(2));
+        assert_eq!(table.query().not(|q| q).count(), Ok(0));
+    }
+}
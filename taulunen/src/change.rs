@@ -0,0 +1,166 @@
+use std::fmt::{self, Debug};
+
+use crate::ItemID;
+
+/// A change made to the items of a [`Table`](crate::Table), passed to the
+/// handlers registered with [`Table::on_change`](crate::Table::on_change).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ChangeEvent<T> {
+    Inserted { id: ItemID, item: T },
+    Updated { id: ItemID, old: T, new: T },
+    Removed { id: ItemID, item: T },
+}
+
+/// Identifies a handler registered with
+/// [`Table::on_change`](crate::Table::on_change).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct SubscriptionId(u64);
+
+type Handler<T> = Box<dyn FnMut(&ChangeEvent<T>) + Send + Sync>;
+
+pub(crate) struct Subscribers<T> {
+    next_id: u64,
+    handlers: Vec<(SubscriptionId, Handler<T>)>,
+    /// Subscribing requires `T: Clone`, keeping its `clone` here allows
+    /// building the events without every change to the table requiring it.
+    clone_item: Option<fn(&T) -> T>,
+}
+
+impl<T> Default for Subscribers<T> {
+    fn default() -> Self {
+        Subscribers {
+            next_id: 0,
+            handlers: Vec::new(),
+            clone_item: None,
+        }
+    }
+}
+
+impl<T> Debug for Subscribers<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list()
+            .entries(self.handlers.iter().map(|(id, _)| id))
+            .finish()
+    }
+}
+
+impl<T> Subscribers<T> {
+    pub fn subscribe(&mut self, handler: Handler<T>) -> SubscriptionId
+    where
+        T: Clone,
+    {
+        let id = SubscriptionId(self.next_id);
+        self.next_id += 1;
+        self.handlers.push((id, handler));
+        self.clone_item = Some(T::clone);
+
+        id
+    }
+
+    pub fn unsubscribe(&mut self, id: SubscriptionId) -> bool {
+        let len = self.handlers.len();
+        self.handlers.retain(|(handler_id, _)| *handler_id != id);
+        self.handlers.len() != len
+    }
+
+    /// Calls every handler with the event in the order they were registered.
+    /// The event is only built if there are handlers to receive it, `event`
+    /// being given a function for cloning the items.
+    pub fn notify(&mut self, event: impl FnOnce(fn(&T) -> T) -> ChangeEvent<T>) {
+        if self.handlers.is_empty() {
+            return;
+        }
+
+        let event = event(self.clone_item.expect("set when subscribing"));
+        for (_, handler) in self.handlers.iter_mut() {
+            handler(&event);
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use super::*;
+    use crate::table::tests::{user, users, User, UserIndex};
+    use crate::{Query, Table};
+
+    /// Collects the events of the table as they are notified.
+    pub(crate) fn record(table: &mut Table<User, UserIndex>) -> Arc<Mutex<Vec<ChangeEvent<User>>>> {
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let recorded = events.clone();
+        table.on_change(move |event| recorded.lock().unwrap().push(event.clone()));
+
+        events
+    }
+
+    #[test]
+    fn events_of_mixed_workload() {
+        let mut table = users([UserIndex::Name, UserIndex::Age]);
+        let events = record(&mut table);
+
+        let fay = table.insert(user("Fay", 19));
+        table.update(ItemID(1), |user| user.age = 26);
+        assert!(table
+            .try_update(ItemID(0), |user| user.name = "Dan".to_string())
+            .is_err());
+        assert!(table.try_insert(user("Eve", 99)).is_err());
+        table.remove(ItemID(2));
+        assert_eq!(table.remove(ItemID(2)), None);
+        table
+            .update_where(&Query::eq(UserIndex::Age, 41), |user| user.age = 42)
+            .unwrap();
+        table.remove_where(&Query::eq(UserIndex::Age, 25)).unwrap();
+
+        assert_eq!(
+            *events.lock().unwrap(),
+            [
+                ChangeEvent::Inserted {
+                    id: fay,
+                    item: user("Fay", 19),
+                },
+                ChangeEvent::Updated {
+                    id: ItemID(1),
+                    old: user("Bob", 25),
+                    new: user("Bob", 26),
+                },
+                ChangeEvent::Removed {
+                    id: ItemID(2),
+                    item: user("Cid", 30),
+                },
+                ChangeEvent::Updated {
+                    id: ItemID(3),
+                    old: user("Dan", 41),
+                    new: user("Dan", 42),
+                },
+                ChangeEvent::Removed {
+                    id: ItemID(4),
+                    item: user("Eve", 25),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn handlers_in_order_until_unsubscribed() {
+        let mut table = users([]);
+        let calls = Arc::new(Mutex::new(Vec::new()));
+        let subscriptions: Vec<_> = ["first", "second"]
+            .into_iter()
+            .map(|name| {
+                let calls = calls.clone();
+                table.on_change(move |_| calls.lock().unwrap().push(name))
+            })
+            .collect();
+
+        table.insert(user("Fay", 19));
+        assert!(table.unsubscribe(subscriptions[0]));
+        assert!(!table.unsubscribe(subscriptions[0]));
+        table.insert(user("Gus", 19));
+        assert!(table.unsubscribe(subscriptions[1]));
+        table.insert(user("Hal", 19));
+
+        assert_eq!(*calls.lock().unwrap(), ["first", "second", "second"]);
+    }
+}
@@ -0,0 +1,199 @@
+use std::sync::{RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+use crate::{Index, ItemID, Query, QueryOptions, Table, TableError, TypedIndex, Value};
+
+/// A [`Table`] which can be shared between threads, allowing any number of
+/// concurrent readers or a single writer at a time.
+///
+/// # Panics
+///
+/// A panic while the [`Table`] is being changed, for example in a handler
+/// registered with [`Table::on_change`] or in [`Index::extract`], can leave
+/// the items and indices out of step. The lock is then poisoned and every
+/// later access panics instead of returning possibly wrong results.
+#[derive(Debug)]
+pub struct ConcurrentTable<T, I: Index<T>>(RwLock<Table<T, I>>);
+
+const POISONED: &str = "a thread panicked while changing the table";
+
+impl<T, I: Index<T>> Default for ConcurrentTable<T, I> {
+    fn default() -> Self {
+        ConcurrentTable::new(Table::default())
+    }
+}
+
+impl<T, I: Index<T>> From<Table<T, I>> for ConcurrentTable<T, I> {
+    fn from(table: Table<T, I>) -> Self {
+        ConcurrentTable::new(table)
+    }
+}
+
+impl<T, I: Index<T>> ConcurrentTable<T, I> {
+    pub fn new(table: Table<T, I>) -> Self {
+        ConcurrentTable(RwLock::new(table))
+    }
+
+    pub fn into_inner(self) -> Table<T, I> {
+        self.0.into_inner().expect(POISONED)
+    }
+
+    /// Runs `f` with shared access to the [`Table`], blocking while a writer
+    /// holds it.
+    pub fn read<R>(&self, f: impl FnOnce(&Table<T, I>) -> R) -> R {
+        f(&self.read_lock())
+    }
+
+    /// Runs `f` with exclusive access to the [`Table`], blocking while any
+    /// reader or writer holds it.
+    pub fn write<R>(&self, f: impl FnOnce(&mut Table<T, I>) -> R) -> R {
+        f(&mut self.write_lock())
+    }
+
+    fn read_lock(&self) -> RwLockReadGuard<'_, Table<T, I>> {
+        self.0.read().expect(POISONED)
+    }
+
+    fn write_lock(&self) -> RwLockWriteGuard<'_, Table<T, I>> {
+        self.0.write().expect(POISONED)
+    }
+}
+
+impl<T, I: Index<T>> ConcurrentTable<T, I> {
+    pub fn len(&self) -> usize {
+        self.read_lock().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.read_lock().is_empty()
+    }
+
+    pub fn contains(&self, item_id: ItemID) -> bool {
+        self.read_lock().contains(item_id)
+    }
+
+    pub fn get(&self, item_id: ItemID) -> Option<T>
+    where
+        T: Clone,
+    {
+        self.read_lock().get(item_id)
+    }
+
+    /// See [`Table::where_eq`].
+    pub fn where_eq<K: TypedIndex<I, V>, V: Into<Value>>(
+        &self,
+        index: K,
+        value: V,
+    ) -> Vec<(ItemID, T)>
+    where
+        T: Clone,
+    {
+        self.read_lock().where_eq(index, value)
+    }
+
+    /// See [`Table::get_by_unique`].
+    pub fn get_by_unique(
+        &self,
+        index: I,
+        value: &Value,
+    ) -> Result<Option<(ItemID, T)>, TableError<I>>
+    where
+        T: Clone,
+    {
+        self.read_lock().get_by_unique(index, value)
+    }
+
+    /// See [`Table::where_query`].
+    pub fn where_query(&self, query: &Query<I>) -> Result<Vec<(ItemID, T)>, TableError<I>>
+    where
+        T: Clone,
+    {
+        self.read_lock().where_query(query)
+    }
+
+    /// See [`Table::select`].
+    pub fn select(
+        &self,
+        query: &Query<I>,
+        options: &QueryOptions<I>,
+    ) -> Result<Vec<(ItemID, T)>, TableError<I>>
+    where
+        T: Clone,
+    {
+        self.read_lock().select(query, options)
+    }
+
+    /// See [`Table::count_where`].
+    pub fn count_where(&self, query: &Query<I>) -> Result<usize, TableError<I>> {
+        self.read_lock().count_where(query)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        panic::{self, AssertUnwindSafe},
+        thread,
+    };
+
+    use super::*;
+    use crate::table::tests::{user, users, UserIndex};
+
+    #[test]
+    fn readers_see_whole_writes() {
+        let table = ConcurrentTable::new(users([UserIndex::Name, UserIndex::Age]));
+
+        thread::scope(|scope| {
+            for writer in 0..4 {
+                let table = &table;
+                scope.spawn(move || {
+                    for i in 0..100 {
+                        // Every write adds a pair of users of the same age.
+                        table.write(|table| {
+                            table.insert(user(&format!("{}-{}-a", writer, i), i));
+                            table.insert(user(&format!("{}-{}-b", writer, i), i));
+                        });
+                    }
+                });
+            }
+
+            for _ in 0..4 {
+                let table = &table;
+                scope.spawn(move || {
+                    for _ in 0..100 {
+                        table.read(|table| {
+                            assert_eq!(table.len() % 2, 1);
+                            assert_eq!(table.verify_indices(), Ok(()));
+                        });
+                        let query = Query::gte(UserIndex::Age, 50);
+                        assert_eq!(table.count_where(&query).unwrap() % 2, 0);
+                    }
+                });
+            }
+        });
+
+        assert_eq!(table.len(), 805);
+        let table = table.into_inner();
+        assert_eq!(table.count_eq(UserIndex::Age, &99.into()), 8);
+        assert_eq!(table.verify_indices(), Ok(()));
+    }
+
+    #[test]
+    fn panicking_writer_poisons_the_table() {
+        let table = ConcurrentTable::new(users([UserIndex::Name]));
+
+        thread::scope(|scope| {
+            let writer = scope.spawn(|| {
+                table.write(|table| {
+                    table.insert(user("Fay", 19));
+                    panic!("in the middle of a write");
+                })
+            });
+            assert!(writer.join().is_err());
+        });
+
+        let read = panic::catch_unwind(AssertUnwindSafe(|| table.len()));
+        assert!(read.is_err());
+        let write = panic::catch_unwind(AssertUnwindSafe(|| table.write(|_| ())));
+        assert!(write.is_err());
+    }
+}
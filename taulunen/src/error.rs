@@ -0,0 +1,135 @@
+use std::fmt::{self, Debug, Display};
+
+use crate::{DataType, ItemID, Value};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TableError<I> {
+    /// The item would have had the same value in an unique `index` as the
+    /// already `existing` item.
+    UniqueViolation {
+        index: I,
+        existing: ItemID,
+        conflicting: ItemID,
+    },
+    /// A value of the `actual` type was used with an `index` of the
+    /// `expected` type.
+    TypeMismatch {
+        index: I,
+        expected: DataType,
+        actual: DataType,
+    },
+    /// The item with `item_id` has no value in the `index` which is not
+    /// nullable.
+    NullNotAllowed { index: I, item_id: ItemID },
+    /// An item with `item_id` already exists in the table.
+    ItemIDExists { item_id: ItemID },
+    /// The operation requires an unique index but `index` is not unique.
+    NotUnique { index: I },
+    /// The operation requires an index which has not been added to the table.
+    UnknownIndex { index: I },
+}
+
+impl<I: Debug> Display for TableError<I> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TableError::UniqueViolation {
+                index,
+                existing,
+                conflicting,
+            } => write!(
+                f,
+                "value of {:?} in unique index {:?} conflicts with {:?}",
+                conflicting, index, existing
+            ),
+            TableError::TypeMismatch {
+                index,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "index {:?} expected a value of type {:?} but got {:?}",
+                index, expected, actual
+            ),
+            TableError::NullNotAllowed { index, item_id } => write!(
+                f,
+                "{:?} has no value in index {:?} which is not nullable",
+                item_id, index
+            ),
+            TableError::ItemIDExists { item_id } => {
+                write!(f, "an item with {:?} already exists", item_id)
+            }
+            TableError::NotUnique { index } => write!(f, "index {:?} is not unique", index),
+            TableError::UnknownIndex { index } => {
+                write!(f, "index {:?} has not been added to the table", index)
+            }
+        }
+    }
+}
+
+impl<I: Debug> std::error::Error for TableError<I> {}
+
+/// A [`Value`] of the `actual` type could not be converted to a Rust type
+/// corresponding to the `expected` type.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ConversionError {
+    pub expected: DataType,
+    pub actual: DataType,
+}
+
+impl Display for ConversionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "expected a value of type {:?} but got {:?}",
+            self.expected, self.actual
+        )
+    }
+}
+
+impl std::error::Error for ConversionError {}
+
+/// A disagreement between the items of a table and the contents of one of its
+/// indices, `None` values standing for the item being null in the index.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConsistencyError<I> {
+    /// The `index` has no entry with the `value` extracted from the item.
+    MissingEntry {
+        index: I,
+        item_id: ItemID,
+        value: Option<Value>,
+    },
+    /// The `index` has an entry with `value` for an item which does not exist
+    /// or from which a different value is extracted.
+    DanglingEntry {
+        index: I,
+        item_id: ItemID,
+        value: Option<Value>,
+    },
+}
+
+impl<I: Debug> Display for ConsistencyError<I> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConsistencyError::MissingEntry {
+                index,
+                item_id,
+                value,
+            } => write!(
+                f,
+                "index {:?} is missing the value {:?} of {:?}",
+                index, value, item_id
+            ),
+            ConsistencyError::DanglingEntry {
+                index,
+                item_id,
+                value,
+            } => write!(
+                f,
+                "index {:?} has a dangling value {:?} for {:?}",
+                index, value, item_id
+            ),
+        }
+    }
+}
+
+impl<I: Debug> std::error::Error for ConsistencyError<I> {}
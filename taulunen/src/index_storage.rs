@@ -1,59 +1,141 @@
 use std::{
-    collections::{btree_map::Entry, BTreeMap},
+    collections::{btree_map::Entry, BTreeMap, BTreeSet},
     fmt::Debug,
+    iter,
     ops::Bound,
 };
 
 use crate::{ItemID, Value};
 
-pub trait IndexStorage: Debug {
+/// Entries of an [`IndexStorage`] ordered by value, with entries of equal
+/// value ordered by [`ItemID`].
+pub type Entries<'a> = Box<dyn DoubleEndedIterator<Item = (&'a Value, ItemID)> + 'a>;
+
+pub trait IndexStorage: Debug + Send + Sync {
     fn add(&mut self, item_id: ItemID, value: Value) -> bool;
     fn remove(&mut self, item_id: ItemID, value: Value) -> bool;
     fn get(&self, value: &Value) -> Vec<ItemID>;
+    /// Returns the lowest [`ItemID`] with the value.
+    fn first(&self, value: &Value) -> Option<ItemID>;
+    fn range(&self, min: Bound<&Value>, max: Bound<&Value>) -> Entries<'_>;
+    fn iter(&self) -> Entries<'_>;
+
+    fn add_null(&mut self, item_id: ItemID);
+    fn remove_null(&mut self, item_id: ItemID) -> bool;
+    /// Returns the [`ItemID`]s of the items which have no value.
+    fn nulls(&self) -> &BTreeSet<ItemID>;
+
+    /// Returns an independent copy of the storage.
+    fn boxed_clone(&self) -> Box<dyn IndexStorage>;
 
-    fn update(&mut self, item_id: ItemID, old_value: Value, new_value: Value) {
-        self.remove(item_id, old_value);
-        self.add(item_id, new_value);
+    /// Adds the item with the value or, if there is none, as null.
+    fn add_entry(&mut self, item_id: ItemID, value: Option<Value>) -> bool {
+        match value {
+            Some(value) => self.add(item_id, value),
+            None => {
+                self.add_null(item_id);
+                true
+            }
+        }
+    }
+
+    fn remove_entry(&mut self, item_id: ItemID, value: Option<Value>) -> bool {
+        match value {
+            Some(value) => self.remove(item_id, value),
+            None => self.remove_null(item_id),
+        }
+    }
+
+    fn update(&mut self, item_id: ItemID, old_value: Option<Value>, new_value: Option<Value>) {
+        self.remove_entry(item_id, old_value);
+        self.add_entry(item_id, new_value);
     }
 }
 
-#[derive(Debug, Default)]
-pub struct NonUniqueIndexStorage(BTreeMap<(Value, ItemID), ()>);
+#[derive(Debug, Default, Clone)]
+pub struct NonUniqueIndexStorage {
+    values: BTreeMap<Value, BTreeSet<ItemID>>,
+    nulls: BTreeSet<ItemID>,
+}
 
 impl IndexStorage for NonUniqueIndexStorage {
     fn add(&mut self, item_id: ItemID, value: Value) -> bool {
-        self.0.insert((value, item_id), ());
+        self.values.entry(value).or_default().insert(item_id);
         true
     }
 
     fn get(&self, value: &Value) -> Vec<ItemID> {
-        let mut cursor = self
-            .0
-            .lower_bound(Bound::Included(&(value.clone(), ItemID::new(0))));
-
-        let mut out = Vec::new();
-        while let Some(((next_value, next_item_id), _)) = cursor.next() {
-            if next_value != value {
-                break;
-            }
+        match self.values.get(value) {
+            Some(item_ids) => item_ids.iter().copied().collect(),
+            None => vec![],
+        }
+    }
+
+    fn first(&self, value: &Value) -> Option<ItemID> {
+        self.values.get(value)?.first().copied()
+    }
 
-            out.push(*next_item_id);
+    fn range(&self, min: Bound<&Value>, max: Bound<&Value>) -> Entries<'_> {
+        if is_empty_range(min, max) {
+            return Box::new(iter::empty());
         }
 
-        out
+        Box::new(
+            self.values.range((min, max)).flat_map(|(value, item_ids)| {
+                item_ids.iter().map(move |item_id| (value, *item_id))
+            }),
+        )
+    }
+
+    fn iter(&self) -> Entries<'_> {
+        Box::new(
+            self.values.iter().flat_map(|(value, item_ids)| {
+                item_ids.iter().map(move |item_id| (value, *item_id))
+            }),
+        )
     }
 
+    /// Removes the set of the value along with its last [`ItemID`].
     fn remove(&mut self, item_id: ItemID, value: Value) -> bool {
-        self.0.remove(&(value, item_id)).is_some()
+        let Some(item_ids) = self.values.get_mut(&value) else {
+            return false;
+        };
+
+        let removed = item_ids.remove(&item_id);
+        if item_ids.is_empty() {
+            self.values.remove(&value);
+        }
+
+        removed
+    }
+
+    fn add_null(&mut self, item_id: ItemID) {
+        self.nulls.insert(item_id);
+    }
+
+    fn remove_null(&mut self, item_id: ItemID) -> bool {
+        self.nulls.remove(&item_id)
+    }
+
+    fn nulls(&self) -> &BTreeSet<ItemID> {
+        &self.nulls
+    }
+
+    fn boxed_clone(&self) -> Box<dyn IndexStorage> {
+        Box::new(self.clone())
     }
 }
 
-#[derive(Debug, Default)]
-pub struct UniqueIndexStorage(BTreeMap<Value, ItemID>);
+/// Only the values are unique, any number of items can be null.
+#[derive(Debug, Default, Clone)]
+pub struct UniqueIndexStorage {
+    values: BTreeMap<Value, ItemID>,
+    nulls: BTreeSet<ItemID>,
+}
 
 impl IndexStorage for UniqueIndexStorage {
     fn add(&mut self, item_id: ItemID, value: Value) -> bool {
-        match self.0.entry(value) {
+        match self.values.entry(value) {
             Entry::Vacant(e) => {
                 e.insert(item_id);
                 true
@@ -63,14 +145,34 @@ impl IndexStorage for UniqueIndexStorage {
     }
 
     fn get(&self, value: &Value) -> Vec<ItemID> {
-        match self.0.get(value) {
+        match self.values.get(value) {
             Some(item_id) => vec![*item_id],
             None => vec![],
         }
     }
 
+    fn first(&self, value: &Value) -> Option<ItemID> {
+        self.values.get(value).copied()
+    }
+
+    fn range(&self, min: Bound<&Value>, max: Bound<&Value>) -> Entries<'_> {
+        if is_empty_range(min, max) {
+            return Box::new(iter::empty());
+        }
+
+        Box::new(
+            self.values
+                .range((min, max))
+                .map(|(value, item_id)| (value, *item_id)),
+        )
+    }
+
+    fn iter(&self) -> Entries<'_> {
+        Box::new(self.values.iter().map(|(value, item_id)| (value, *item_id)))
+    }
+
     fn remove(&mut self, item_id: ItemID, value: Value) -> bool {
-        match self.0.remove(&value) {
+        match self.values.remove(&value) {
             Some(old_item_id) => {
                 assert_eq!(item_id, old_item_id);
                 true
@@ -78,6 +180,33 @@ impl IndexStorage for UniqueIndexStorage {
             None => false,
         }
     }
+
+    fn add_null(&mut self, item_id: ItemID) {
+        self.nulls.insert(item_id);
+    }
+
+    fn remove_null(&mut self, item_id: ItemID) -> bool {
+        self.nulls.remove(&item_id)
+    }
+
+    fn nulls(&self) -> &BTreeSet<ItemID> {
+        &self.nulls
+    }
+
+    fn boxed_clone(&self) -> Box<dyn IndexStorage> {
+        Box::new(self.clone())
+    }
+}
+
+/// Returns true if no value can be within the bounds, in which case
+/// [`BTreeMap::range`] would panic.
+fn is_empty_range(min: Bound<&Value>, max: Bound<&Value>) -> bool {
+    match (min, max) {
+        (Bound::Included(min), Bound::Included(max)) => min > max,
+        (Bound::Included(min) | Bound::Excluded(min), Bound::Excluded(max))
+        | (Bound::Excluded(min), Bound::Included(max)) => min >= max,
+        _ => false,
+    }
 }
 
 pub fn new_index_storage(unique: bool) -> Box<dyn IndexStorage> {
@@ -87,3 +216,75 @@ pub fn new_index_storage(unique: bool) -> Box<dyn IndexStorage> {
         Box::new(NonUniqueIndexStorage::default()) as Box<dyn IndexStorage>
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::table::tests::{user, UserIndex};
+    use crate::Table;
+
+    #[test]
+    fn many_items_sharing_few_values() {
+        let mut storage = NonUniqueIndexStorage::default();
+        for i in 0..10_000 {
+            assert!(storage.add(ItemID(i), Value::int((i % 10) as i64)));
+        }
+
+        assert_eq!(storage.values.len(), 10);
+        let sevens = storage.get(&Value::int(7));
+        assert_eq!(sevens.len(), 1_000);
+        assert!(sevens.windows(2).all(|pair| pair[0] < pair[1]));
+        assert_eq!(storage.first(&Value::int(7)), Some(ItemID(7)));
+
+        let range: Vec<_> = storage
+            .range(
+                Bound::Excluded(&Value::int(2)),
+                Bound::Included(&Value::int(4)),
+            )
+            .map(|(value, item_id)| (value.clone(), item_id))
+            .collect();
+        assert_eq!(range.len(), 2_000);
+        assert_eq!(range[0], (Value::int(3), ItemID(3)));
+        assert_eq!(range[1_999], (Value::int(4), ItemID(9_994)));
+
+        // Removing every item with a value drops the value.
+        for i in (0..10_000).filter(|i| i % 10 == 7) {
+            assert!(storage.remove(ItemID(i), Value::int(7)));
+        }
+        assert!(!storage.remove(ItemID(7), Value::int(7)));
+        assert!(storage.get(&Value::int(7)).is_empty());
+        assert_eq!(storage.values.len(), 9);
+        assert_eq!(storage.iter().count(), 9_000);
+        assert_eq!(
+            storage.iter().next_back(),
+            Some((&Value::int(9), ItemID(9_999)))
+        );
+    }
+
+    #[test]
+    fn table_with_many_items_sharing_few_values() {
+        let mut table = Table::with_indices([UserIndex::Age]);
+        for i in 0..10_000 {
+            table.insert(user(&i.to_string(), i % 10));
+        }
+
+        assert_eq!(table.count_eq(UserIndex::Age, &3.into()), 1_000);
+        let removed = table.retain(|item_id, _| item_id.0 % 2 == 0);
+        assert_eq!(removed, 5_000);
+        assert_eq!(table.count_eq(UserIndex::Age, &3.into()), 0);
+        assert_eq!(table.count_eq(UserIndex::Age, &4.into()), 1_000);
+        assert_eq!(table.verify_indices(), Ok(()));
+    }
+
+    #[test]
+    fn unique_storage_rejects_duplicates() {
+        let mut storage = UniqueIndexStorage::default();
+        assert!(storage.add(ItemID(0), Value::int(1)));
+        assert!(!storage.add(ItemID(1), Value::int(1)));
+        assert_eq!(storage.get(&Value::int(1)), [ItemID(0)]);
+
+        storage.add_null(ItemID(2));
+        storage.add_null(ItemID(3));
+        assert_eq!(storage.nulls().len(), 2);
+    }
+}
@@ -1,7 +1,8 @@
 use std::sync::atomic::{AtomicU64, Ordering};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
-pub struct ItemID(u64);
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ItemID(pub(crate) u64);
 
 impl ItemID {
     pub fn new(value: u64) -> ItemID {
@@ -9,6 +10,12 @@ impl ItemID {
     }
 }
 
+impl From<u64> for ItemID {
+    fn from(value: u64) -> Self {
+        ItemID(value)
+    }
+}
+
 #[derive(Debug, Default)]
 pub struct ItemIDGenerator(AtomicU64);
 
@@ -17,7 +24,74 @@ impl ItemIDGenerator {
         ItemIDGenerator(AtomicU64::new(first_value))
     }
 
-    pub fn next(&mut self) -> ItemID {
+    /// Returns a generator whose first [`ItemID`] is the one after `item_id`,
+    /// for resuming after the [`ItemID`]s of loaded items.
+    pub fn starting_after(item_id: ItemID) -> ItemIDGenerator {
+        ItemIDGenerator::new(item_id.0.saturating_add(1))
+    }
+
+    /// Returns the [`ItemID`] which the next call to `next` will return.
+    pub fn peek(&self) -> ItemID {
+        ItemID(self.0.load(Ordering::SeqCst))
+    }
+
+    /// Returns a new [`ItemID`]. Each call returns a distinct one, also when
+    /// called from multiple threads at the same time.
+    pub fn next(&self) -> ItemID {
         ItemID(self.0.fetch_add(1, Ordering::SeqCst))
     }
+
+    /// Makes sure that the [`ItemID`]s generated from now on are greater
+    /// than `item_id`.
+    pub fn skip_past(&self, item_id: ItemID) {
+        self.0
+            .fetch_max(item_id.0.saturating_add(1), Ordering::SeqCst);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashSet, thread};
+
+    use super::*;
+
+    #[test]
+    fn distinct_ids_across_threads() {
+        let generator = ItemIDGenerator::new(100);
+
+        let item_ids: Vec<ItemID> = thread::scope(|scope| {
+            let threads: Vec<_> = (0..8)
+                .map(|_| scope.spawn(|| (0..1_000).map(|_| generator.next()).collect::<Vec<_>>()))
+                .collect();
+            threads
+                .into_iter()
+                .flat_map(|thread| thread.join().unwrap())
+                .collect()
+        });
+
+        let distinct: HashSet<ItemID> = item_ids.iter().copied().collect();
+        assert_eq!(distinct.len(), 8_000);
+        assert_eq!(item_ids.iter().min(), Some(&ItemID(100)));
+        assert_eq!(item_ids.iter().max(), Some(&ItemID(8_099)));
+        assert_eq!(generator.peek(), ItemID(8_100));
+    }
+
+    #[test]
+    fn skip_past_only_moves_forward() {
+        let generator = ItemIDGenerator::new(5);
+        generator.skip_past(ItemID(2));
+        assert_eq!(generator.next(), ItemID(5));
+
+        generator.skip_past(ItemID(10));
+        assert_eq!(generator.peek(), ItemID(11));
+        assert_eq!(generator.next(), ItemID(11));
+    }
+
+    #[test]
+    fn starting_after() {
+        assert_eq!(ItemIDGenerator::starting_after(ItemID(7)).next(), ItemID(8));
+
+        let generator = ItemIDGenerator::starting_after(ItemID(u64::MAX));
+        assert_eq!(generator.peek(), ItemID(u64::MAX));
+    }
 }
@@ -0,0 +1,274 @@
+use std::borrow::Cow;
+
+use crate::{Index, ItemID, Table, TableError, Value};
+
+type Joined<A, B> = (ItemID, A, ItemID, B);
+type LeftJoined<A, B> = (ItemID, A, Option<(ItemID, B)>);
+type MatchingGroups = Vec<(Vec<ItemID>, Vec<ItemID>)>;
+
+/// Returns every pair of items from the two [`Table`]s having equal values in
+/// `left_index` and `right_index`, ordered by the value and then by the
+/// [`ItemID`]s.
+///
+/// The indices are walked in order side by side, so an index which has not
+/// been added to its [`Table`] is first extracted from every item and sorted.
+/// Items which are null in either index are never matched. Errors if the
+/// indices have different [`DataType`](crate::DataType)s.
+pub fn join<A: Clone, IA: Index<A>, B: Clone, IB: Index<B>>(
+    left: &Table<A, IA>,
+    right: &Table<B, IB>,
+    left_index: IA,
+    right_index: IB,
+) -> Result<Vec<Joined<A, B>>, TableError<IA>> {
+    let mut out = Vec::new();
+    for (left_item_ids, right_item_ids) in matching_groups(left, right, &left_index, &right_index)?
+    {
+        for &left_item_id in left_item_ids.iter() {
+            let left_item = &left.items[&left_item_id];
+            for &right_item_id in right_item_ids.iter() {
+                let right_item = &right.items[&right_item_id];
+                out.push((
+                    left_item_id,
+                    left_item.clone(),
+                    right_item_id,
+                    right_item.clone(),
+                ));
+            }
+        }
+    }
+
+    Ok(out)
+}
+
+/// Like [`join`] but also returns the items of the `left` [`Table`] without
+/// any matching item, paired with `None`.
+///
+/// Items which are null in `left_index` are returned last in [`ItemID`]
+/// order.
+pub fn left_join<A: Clone, IA: Index<A>, B: Clone, IB: Index<B>>(
+    left: &Table<A, IA>,
+    right: &Table<B, IB>,
+    left_index: IA,
+    right_index: IB,
+) -> Result<Vec<LeftJoined<A, B>>, TableError<IA>> {
+    let mut out = Vec::new();
+    for (left_item_ids, right_item_ids) in matching_groups(left, right, &left_index, &right_index)?
+    {
+        for &left_item_id in left_item_ids.iter() {
+            let left_item = &left.items[&left_item_id];
+            if right_item_ids.is_empty() {
+                out.push((left_item_id, left_item.clone(), None));
+            }
+
+            for &right_item_id in right_item_ids.iter() {
+                let right_item = &right.items[&right_item_id];
+                out.push((
+                    left_item_id,
+                    left_item.clone(),
+                    Some((right_item_id, right_item.clone())),
+                ));
+            }
+        }
+    }
+
+    for left_item_id in left.null_item_ids(&left_index) {
+        out.push((left_item_id, left.items[&left_item_id].clone(), None));
+    }
+
+    Ok(out)
+}
+
+/// Groups the items of both [`Table`]s by their values in the indices,
+/// returning every group of the `left` [`Table`] in order together with the
+/// group of the `right` [`Table`] having the same value, which is empty if
+/// there is none.
+fn matching_groups<A, IA: Index<A>, B, IB: Index<B>>(
+    left: &Table<A, IA>,
+    right: &Table<B, IB>,
+    left_index: &IA,
+    right_index: &IB,
+) -> Result<MatchingGroups, TableError<IA>> {
+    if left_index.data_type() != right_index.data_type() {
+        return Err(TableError::TypeMismatch {
+            index: left_index.clone(),
+            expected: left_index.data_type(),
+            actual: right_index.data_type(),
+        });
+    }
+
+    let left_groups = group_by_value(left.sorted_entries(left_index));
+    let mut right_groups = group_by_value(right.sorted_entries(right_index))
+        .into_iter()
+        .peekable();
+
+    let mut out = Vec::with_capacity(left_groups.len());
+    for (value, left_item_ids) in left_groups {
+        let mut right_item_ids = Vec::new();
+        while let Some((right_value, item_ids)) =
+            right_groups.next_if(|(right_value, _)| *right_value <= value)
+        {
+            if right_value == value {
+                right_item_ids = item_ids;
+            }
+        }
+
+        out.push((left_item_ids, right_item_ids));
+    }
+
+    Ok(out)
+}
+
+fn group_by_value(entries: Vec<(Cow<'_, Value>, ItemID)>) -> Vec<(Cow<'_, Value>, Vec<ItemID>)> {
+    let mut groups: Vec<(Cow<Value>, Vec<ItemID>)> = Vec::new();
+    for (value, item_id) in entries {
+        match groups.last_mut() {
+            Some((last_value, item_ids)) if *last_value == value => item_ids.push(item_id),
+            _ => groups.push((value, vec![item_id])),
+        }
+    }
+
+    groups
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::table::tests::{user, users, User, UserIndex};
+    use crate::DataType;
+
+    #[derive(Debug, Clone, PartialEq)]
+    struct Order {
+        buyer: String,
+        item: &'static str,
+    }
+
+    #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+    struct Buyer;
+
+    impl Index<Order> for Buyer {
+        fn data_type(&self) -> DataType {
+            DataType::String
+        }
+
+        fn extract(&self, order: &Order) -> Option<Value> {
+            Some(Value::string(&order.buyer))
+        }
+
+        fn is_unique(&self) -> bool {
+            false
+        }
+    }
+
+    /// Ann has two orders and Cid one, nobody is called Zed.
+    fn orders(indexed: bool) -> Table<Order, Buyer> {
+        let mut table = if indexed {
+            Table::with_indices([Buyer])
+        } else {
+            Table::empty()
+        };
+        for (buyer, item) in [
+            ("Zed", "hat"),
+            ("Cid", "cap"),
+            ("Ann", "map"),
+            ("Ann", "pen"),
+        ] {
+            table.insert(Order {
+                buyer: buyer.to_string(),
+                item,
+            });
+        }
+
+        table
+    }
+
+    #[test]
+    fn join_one_to_many() {
+        for (indices, indexed) in [(vec![UserIndex::Name], true), (vec![], false)] {
+            let users = users(indices);
+            let joined = join(&users, &orders(indexed), UserIndex::Name, Buyer).unwrap();
+
+            let pairs: Vec<_> = joined
+                .iter()
+                .map(|(user_id, user, order_id, order)| {
+                    (user_id.0, user.name.as_str(), order_id.0, order.item)
+                })
+                .collect();
+            assert_eq!(
+                pairs,
+                [
+                    (0, "Ann", 2, "map"),
+                    (0, "Ann", 3, "pen"),
+                    (2, "Cid", 1, "cap")
+                ]
+            );
+        }
+    }
+
+    #[test]
+    fn left_join_keeps_unmatched_items() {
+        let mut users = users([UserIndex::Name, UserIndex::Email]);
+        users.insert(User {
+            email: Some("cid@example.com".to_string()),
+            ..user("Fay", 19)
+        });
+
+        let joined = left_join(&users, &orders(true), UserIndex::Name, Buyer).unwrap();
+        let rows: Vec<_> = joined
+            .iter()
+            .map(|(_, user, order)| {
+                (
+                    user.name.as_str(),
+                    order.as_ref().map(|(_, order)| order.item),
+                )
+            })
+            .collect();
+        assert_eq!(
+            rows,
+            [
+                ("Ann", Some("map")),
+                ("Ann", Some("pen")),
+                ("Bob", None),
+                ("Cid", Some("cap")),
+                ("Dan", None),
+                ("Eve", None),
+                ("Fay", None),
+            ]
+        );
+
+        // Items which are null in the index come last.
+        let mut orders = orders(false);
+        orders.insert(Order {
+            buyer: "cid@example.com".to_string(),
+            item: "mug",
+        });
+        let joined = left_join(&users, &orders, UserIndex::Email, Buyer).unwrap();
+        let rows: Vec<_> = joined
+            .iter()
+            .map(|(user_id, _, order)| (user_id.0, order.as_ref().map(|(_, order)| order.item)))
+            .collect();
+        assert_eq!(
+            rows,
+            [
+                (5, Some("mug")),
+                (0, None),
+                (1, None),
+                (2, None),
+                (3, None),
+                (4, None)
+            ]
+        );
+    }
+
+    #[test]
+    fn join_requires_matching_types() {
+        let users = users([UserIndex::Age]);
+        assert_eq!(
+            join(&users, &orders(true), UserIndex::Age, Buyer),
+            Err(TableError::TypeMismatch {
+                index: UserIndex::Age,
+                expected: DataType::Int,
+                actual: DataType::String,
+            })
+        );
+    }
+}
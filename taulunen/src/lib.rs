@@ -1,14 +1,28 @@
-#![feature(btree_cursors)]
-
+mod builder;
+mod change;
+mod concurrent;
+mod error;
 mod index_storage;
 mod item;
+mod join;
 mod query;
+#[cfg(feature = "serde")]
+mod serialized;
 mod table;
+mod transaction;
 mod value;
 
+pub use builder::QueryBuilder;
+pub(crate) use change::Subscribers;
+pub use change::{ChangeEvent, SubscriptionId};
+pub use concurrent::ConcurrentTable;
+pub use error::{ConsistencyError, ConversionError, TableError};
 pub(crate) use index_storage::{new_index_storage, IndexStorage};
-pub use item::ItemID;
-pub(crate) use item::ItemIDGenerator;
-pub use query::Query;
-pub use table::{Index, Table};
-pub use value::{DataType, Value};
+pub use item::{ItemID, ItemIDGenerator};
+pub use join::{join, left_join};
+pub use query::{Direction, Query, QueryOptions};
+#[cfg(feature = "serde")]
+pub use serialized::SerializedTable;
+pub use table::{Index, Table, TypedIndex};
+pub use transaction::Txn;
+pub use value::{DataType, DateTime, IntoValue, Uuid, Value};
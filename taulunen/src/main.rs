@@ -1,4 +1,8 @@
-use taulunen::{DataType, Index, Query, Table, Value};
+use std::ops::Bound;
+
+use taulunen::{
+    DataType, Direction, Index, IntoValue, Query, QueryOptions, Table, TypedIndex, Value,
+};
 
 #[derive(Debug, Clone)]
 struct User<'a> {
@@ -6,7 +10,7 @@ struct User<'a> {
     age: u8,
 }
 
-#[derive(Debug, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 enum UserIndex {
     Name,
     Age,
@@ -22,8 +26,8 @@ impl Index<User<'_>> for UserIndex {
 
     fn extract(&self, user: &User) -> Option<Value> {
         match self {
-            UserIndex::Name => Some(Value::String(user.name.to_string())),
-            UserIndex::Age => Some(Value::Int(user.age as i64)),
+            UserIndex::Name => user.name.into_value(),
+            UserIndex::Age => user.age.into_value(),
         }
     }
 
@@ -32,10 +36,24 @@ impl Index<User<'_>> for UserIndex {
     }
 }
 
+struct Name;
+
+impl TypedIndex<UserIndex, &'static str> for Name {
+    fn index(self) -> UserIndex {
+        UserIndex::Name
+    }
+}
+
+struct Age;
+
+impl TypedIndex<UserIndex, u8> for Age {
+    fn index(self) -> UserIndex {
+        UserIndex::Age
+    }
+}
+
 fn main() {
-    let mut user_table = Table::empty()
-        .add_index(UserIndex::Name)
-        .add_index(UserIndex::Age);
+    let mut user_table = Table::empty().add_index(UserIndex::Name);
     let max = user_table.insert(User {
         name: "Max",
         age: 29,
@@ -49,6 +67,10 @@ fn main() {
         age: 44,
     });
 
+    user_table
+        .create_index(UserIndex::Age)
+        .expect("age index is not unique");
+
     println!("user = {:?}", user_table);
     println!("max = {:?}", user_table.get(max));
 
@@ -58,15 +80,49 @@ fn main() {
     user_table.remove_if(max, |v| v.age == 29);
     println!("max = {:?}", user_table.get(max));
 
-    let results = user_table.where_eq(UserIndex::Age, Value::int(29));
+    let results = user_table.where_eq(Age, 29);
     println!("results = {:?}", results);
 
+    for (item_id, _) in results {
+        user_table.update(item_id, |v| v.age += 1);
+    }
+    println!("results = {:?}", user_table.where_eq(Age, 30));
+
     user_table.remove(max);
     println!("max = {:?}", user_table.get(max));
 
-    let q = Query::or([
-        Query::eq(UserIndex::Age, Value::int(29)),
-        Query::eq(UserIndex::Name, Value::string("Max")),
-    ]);
+    println!("len = {}", user_table.len());
+    for (item_id, user) in user_table.iter() {
+        println!("{:?} = {:?}", item_id, user);
+    }
+
+    let q = Query::or([Query::eq(Age, 29), Query::eq(Name, "Max")]);
     println!("q = {:?}", q);
+    println!("q results = {:?}", user_table.where_query(&q));
+
+    let results = user_table.where_range(
+        UserIndex::Age,
+        Bound::Included(&Value::int(30)),
+        Bound::Unbounded,
+    );
+    println!("age >= 30 = {:?}", results);
+
+    let results = user_table.order_by(UserIndex::Age, Direction::Descending);
+    println!("by age = {:?}", results);
+
+    let options = QueryOptions {
+        limit: Some(1),
+        offset: 1,
+        order_by: Some((UserIndex::Name, Direction::Ascending)),
+    };
+    let results = user_table.select(&Query::gt(Age, 0), &options);
+    println!("second by name = {:?}", results);
+
+    let results = user_table
+        .query()
+        .eq(Age, 30)
+        .or(|q| q.eq(Name, "Max"))
+        .order_by(UserIndex::Name)
+        .fetch();
+    println!("age 30 or named Max = {:?}", results);
 }
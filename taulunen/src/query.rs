@@ -1,28 +1,119 @@
-use crate::{Index, Value};
+use std::ops::{Bound, Not};
 
-#[derive(Debug)]
-pub enum Query<T, I: Index<T>> {
-    Not(Box<Query<T, I>>),
-    And(Box<Vec<Query<T, I>>>),
-    Or(Box<Vec<Query<T, I>>>),
-    Eq(I, Value),
+use crate::{TypedIndex, Value};
 
-    // TODO: how to get rid of this?
-    _Phantom(std::marker::PhantomData<T>),
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Direction {
+    Ascending,
+    Descending,
 }
 
-// pub struct Query {}
+#[derive(Debug, PartialEq)]
+pub enum Query<I> {
+    Not(Box<Query<I>>),
+    And(Box<Vec<Query<I>>>),
+    Or(Box<Vec<Query<I>>>),
+    Eq(I, Value),
+    /// Matches any of the values, matching nothing if there are none.
+    In(I, Vec<Value>),
+    /// Matches values between the bounds.
+    ///
+    /// Floats follow the ordering of [`Value`] where NaN is equal to itself
+    /// and less than every other float, so for example `lt(index, NaN)` never
+    /// matches anything while `lte(index, NaN)` matches only NaNs.
+    Range(I, Bound<Value>, Bound<Value>),
+    /// Matches [`Value::Composite`] values starting with the given values.
+    CompositePrefix(I, Vec<Value>),
+    /// Matches [`Value::String`] values starting with the given string.
+    StartsWith(I, String),
+    /// Matches items for which the index has no value.
+    IsNull(I),
+}
 
-impl<T, I: Index<T>> Query<T, I> {
-    pub fn and(children: impl IntoIterator<Item = Query<T, I>>) -> Query<T, I> {
+impl<I> Query<I> {
+    pub fn and(children: impl IntoIterator<Item = Query<I>>) -> Query<I> {
         Query::And(children.into_iter().collect::<Vec<_>>().into())
     }
 
-    pub fn or(children: impl IntoIterator<Item = Query<T, I>>) -> Query<T, I> {
+    pub fn or(children: impl IntoIterator<Item = Query<I>>) -> Query<I> {
         Query::Or(children.into_iter().collect::<Vec<_>>().into())
     }
 
-    pub fn eq(lhs: I, rhs: Value) -> Query<T, I> {
-        Query::Eq(lhs, rhs)
+    pub fn eq<K: TypedIndex<I, V>, V: Into<Value>>(lhs: K, rhs: V) -> Query<I> {
+        Query::Eq(lhs.index(), rhs.into())
+    }
+
+    pub fn in_<K: TypedIndex<I, V>, V: Into<Value>>(
+        lhs: K,
+        rhs: impl IntoIterator<Item = V>,
+    ) -> Query<I> {
+        Query::In(lhs.index(), rhs.into_iter().map(Into::into).collect())
+    }
+
+    pub fn lt<K: TypedIndex<I, V>, V: Into<Value>>(lhs: K, rhs: V) -> Query<I> {
+        Query::Range(lhs.index(), Bound::Unbounded, Bound::Excluded(rhs.into()))
+    }
+
+    pub fn lte<K: TypedIndex<I, V>, V: Into<Value>>(lhs: K, rhs: V) -> Query<I> {
+        Query::Range(lhs.index(), Bound::Unbounded, Bound::Included(rhs.into()))
+    }
+
+    pub fn gt<K: TypedIndex<I, V>, V: Into<Value>>(lhs: K, rhs: V) -> Query<I> {
+        Query::Range(lhs.index(), Bound::Excluded(rhs.into()), Bound::Unbounded)
+    }
+
+    pub fn gte<K: TypedIndex<I, V>, V: Into<Value>>(lhs: K, rhs: V) -> Query<I> {
+        Query::Range(lhs.index(), Bound::Included(rhs.into()), Bound::Unbounded)
+    }
+
+    /// Matches values between `min` and `max`, both inclusive.
+    pub fn between<K: TypedIndex<I, V>, V: Into<Value>>(lhs: K, min: V, max: V) -> Query<I> {
+        Query::Range(
+            lhs.index(),
+            Bound::Included(min.into()),
+            Bound::Included(max.into()),
+        )
+    }
+
+    pub fn composite_prefix(lhs: I, prefix: impl IntoIterator<Item = Value>) -> Query<I> {
+        Query::CompositePrefix(lhs, prefix.into_iter().collect())
+    }
+
+    pub fn starts_with(lhs: I, prefix: impl ToString) -> Query<I> {
+        Query::StartsWith(lhs, prefix.to_string())
+    }
+
+    pub fn is_null(lhs: I) -> Query<I> {
+        Query::IsNull(lhs)
+    }
+}
+
+impl<I> Not for Query<I> {
+    type Output = Query<I>;
+
+    fn not(self) -> Self::Output {
+        Query::Not(self.into())
+    }
+}
+
+/// Options for [`Table::select`](crate::Table::select).
+#[derive(Debug, PartialEq)]
+pub struct QueryOptions<I> {
+    /// Maximum number of items to return, all of them if `None`.
+    pub limit: Option<usize>,
+    /// Number of matching items to skip before returning any.
+    pub offset: usize,
+    /// Index to order the items by, in [`ItemID`](crate::ItemID) order if
+    /// `None`.
+    pub order_by: Option<(I, Direction)>,
+}
+
+impl<I> Default for QueryOptions<I> {
+    fn default() -> Self {
+        QueryOptions {
+            limit: None,
+            offset: 0,
+            order_by: None,
+        }
     }
 }
@@ -0,0 +1,143 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{Index, ItemID, ItemIDGenerator, Table, TableError};
+
+/// The items of a [`Table`] together with the state needed to keep their
+/// [`ItemID`]s stable across saving and loading the table.
+///
+/// Indices are not included, they are rebuilt from the items when loading.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SerializedTable<T> {
+    pub next_item_id: ItemID,
+    pub items: Vec<(ItemID, T)>,
+}
+
+impl<T, I: Index<T>> Table<T, I> {
+    /// Returns the items of the [`Table`] in a form which can be serialized
+    /// without cloning them.
+    pub fn to_serializable(&self) -> SerializedTable<&T> {
+        SerializedTable {
+            next_item_id: self.item_id.peek(),
+            items: self.iter().collect(),
+        }
+    }
+
+    /// Loads the items from `data`, building the given indices from them.
+    ///
+    /// Errors if the items contain duplicate [`ItemID`]s or violate any of
+    /// the indices.
+    pub fn from_serialized(
+        data: SerializedTable<T>,
+        indices: impl IntoIterator<Item = I>,
+    ) -> Result<Self, TableError<I>> {
+        let mut table = Table::with_indices(indices);
+
+        for (item_id, item) in data.items {
+            if table.contains(item_id) {
+                return Err(TableError::ItemIDExists { item_id });
+            }
+
+            table.insert_item(item_id, item)?;
+        }
+
+        // The ItemIDs of the loaded items are not generated again even if
+        // `next_item_id` is behind them.
+        table.item_id = match table.items.keys().next_back() {
+            Some(&last_item_id) if last_item_id >= data.next_item_id => {
+                ItemIDGenerator::starting_after(last_item_id)
+            }
+            _ => ItemIDGenerator::new(data.next_item_id.0),
+        };
+
+        Ok(table)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::table::tests::{contents, names, user, users, User, UserIndex};
+    use crate::{Query, Value};
+
+    fn round_trip(table: &Table<User, UserIndex>) -> Table<User, UserIndex> {
+        let json = serde_json::to_string(&table.to_serializable()).unwrap();
+        let data: SerializedTable<User> = serde_json::from_str(&json).unwrap();
+        Table::from_serialized(data, [UserIndex::Name, UserIndex::Age, UserIndex::Email]).unwrap()
+    }
+
+    #[test]
+    fn json_round_trip() {
+        let mut table = users([UserIndex::Name, UserIndex::Age, UserIndex::Email]);
+        table.update(ItemID(1), |user| {
+            user.email = Some("Bob@Example.com".to_string())
+        });
+        table.remove(ItemID(2));
+        table.remove(ItemID(4));
+
+        let mut loaded = round_trip(&table);
+        assert_eq!(contents(&loaded), contents(&table));
+        assert_eq!(loaded.verify_indices(), Ok(()));
+        assert_eq!(
+            loaded.id_by_unique(UserIndex::Email, &"bob@example.com".into()),
+            Ok(Some(ItemID(1)))
+        );
+
+        // The ItemIDs of the removed items are not handed out again.
+        assert_eq!(loaded.insert(user("Fay", 30)), ItemID(5));
+        assert_eq!(
+            names(&loaded.where_query(&Query::eq(UserIndex::Age, 30)).unwrap()),
+            ["Ann", "Fay"]
+        );
+    }
+
+    #[test]
+    fn empty_round_trip() {
+        let mut table: Table<User, UserIndex> = Table::with_indices([UserIndex::Name]);
+        let ann = table.insert(user("Ann", 30));
+        table.remove(ann);
+
+        let mut loaded = round_trip(&table);
+        assert!(loaded.is_empty());
+        assert_eq!(loaded.insert(user("Bob", 25)), ItemID(1));
+    }
+
+    #[test]
+    fn next_item_id_catches_up_with_items() {
+        let data = SerializedTable {
+            next_item_id: ItemID(0),
+            items: vec![(ItemID(7), user("Ann", 30)), (ItemID(3), user("Bob", 25))],
+        };
+
+        let mut table = Table::from_serialized(data, [UserIndex::Age]).unwrap();
+        assert_eq!(table.insert(user("Cid", 30)), ItemID(8));
+        assert_eq!(table.count_eq(UserIndex::Age, &Value::int(30)), 2);
+    }
+
+    #[test]
+    fn invalid_data_is_rejected() {
+        let duplicate_ids = SerializedTable {
+            next_item_id: ItemID(2),
+            items: vec![(ItemID(1), user("Ann", 30)), (ItemID(1), user("Bob", 25))],
+        };
+        assert_eq!(
+            Table::from_serialized(duplicate_ids, [UserIndex::Age]).unwrap_err(),
+            TableError::ItemIDExists { item_id: ItemID(1) }
+        );
+
+        let duplicate_names = SerializedTable {
+            next_item_id: ItemID(2),
+            items: vec![(ItemID(0), user("Ann", 30)), (ItemID(1), user("Ann", 25))],
+        };
+        assert_eq!(
+            Table::from_serialized(duplicate_names, [UserIndex::Name]).unwrap_err(),
+            TableError::UniqueViolation {
+                index: UserIndex::Name,
+                existing: ItemID(0),
+                conflicting: ItemID(1),
+            }
+        );
+
+        let json = r#"{"next_item_id":0,"items":[[0,{"name":"Ann"}]]}"#;
+        assert!(serde_json::from_str::<SerializedTable<User>>(json).is_err());
+    }
+}
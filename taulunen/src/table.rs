@@ -1,56 +1,172 @@
-use crate::{new_index_storage, DataType, IndexStorage, ItemID, ItemIDGenerator, Value};
+use crate::{
+    new_index_storage, ChangeEvent, ConsistencyError, DataType, Direction, IndexStorage, ItemID,
+    ItemIDGenerator, Query, QueryOptions, Subscribers, SubscriptionId, TableError, Value,
+};
 
 use std::{
-    collections::{hash_map::Entry, HashMap},
+    borrow::Cow,
+    collections::{btree_map, BTreeMap, BTreeSet, HashMap},
+    fmt::Debug,
     hash::Hash,
+    ops::{Bound, RangeBounds},
 };
 
-pub trait Index<T>: Eq + Hash {
+pub trait Index<T>: Clone + Debug + Eq + Hash {
     fn data_type(&self) -> DataType;
     fn extract(&self, item: &T) -> Option<Value>;
     fn is_unique(&self) -> bool;
 
+    /// Whether [`Index::extract`] may return `None` for an item. Items without
+    /// a value in an index which is not nullable are rejected with
+    /// [`TableError::NullNotAllowed`].
     fn is_nullable(&self) -> bool {
         false
     }
+
+    /// Whether string values are lowercased both when indexing items and
+    /// when querying the index. The items themselves are not modified.
+    fn case_insensitive(&self) -> bool {
+        false
+    }
+}
+
+/// An index whose values have the fixed Rust type `V`, allowing queries to
+/// take that type so that a value of the wrong type is rejected at compile
+/// time.
+///
+/// Every index implements it for any type convertible into a [`Value`], so
+/// queries keep accepting the index itself together with literals such as
+/// `29` or `"Max"`. A typed index is usually a unit struct standing for one
+/// variant of the index, e.g. `struct Age;` implementing
+/// `TypedIndex<UserIndex, u8>`.
+pub trait TypedIndex<I, V> {
+    fn index(self) -> I;
+}
+
+impl<I, V: Into<Value>> TypedIndex<I, V> for I {
+    fn index(self) -> I {
+        self
+    }
+}
+
+/// Extracts the value of the index from the item, normalized as described by
+/// the index.
+fn extract<T, I: Index<T>>(index: &I, item: &T) -> Option<Value> {
+    index
+        .extract(item)
+        .map(|value| normalize(index, Cow::Owned(value)).into_owned())
+}
+
+/// Normalizes a value before it is stored in or looked up from the index.
+fn normalize<'a, T, I: Index<T>>(index: &I, value: Cow<'a, Value>) -> Cow<'a, Value> {
+    match value.as_ref() {
+        Value::String(string) if index.case_insensitive() => {
+            Cow::Owned(Value::String(string.to_lowercase()))
+        }
+        _ => value,
+    }
+}
+
+/// Whether the index storage has an entry with the value, or a null entry if
+/// there is no value, for the item.
+fn has_entry(index_storage: &dyn IndexStorage, item_id: ItemID, value: Option<&Value>) -> bool {
+    match value {
+        Some(value) => index_storage
+            .range(Bound::Included(value), Bound::Included(value))
+            .any(|(_, entry_item_id)| entry_item_id == item_id),
+        None => index_storage.nulls().contains(&item_id),
+    }
 }
 
 #[derive(Debug)]
-pub struct Table<T: Clone, I: Index<T>> {
-    item_id: ItemIDGenerator,
-    items: HashMap<ItemID, T>,
-    indices: HashMap<I, Box<dyn IndexStorage>>,
+pub struct Table<T, I: Index<T>> {
+    pub(crate) item_id: ItemIDGenerator,
+    pub(crate) items: BTreeMap<ItemID, T>,
+    pub(crate) indices: HashMap<I, Box<dyn IndexStorage>>,
+    pub(crate) subscribers: Subscribers<T>,
 }
 
-impl<T: Clone, I: Index<T>> Default for Table<T, I> {
+impl<T, I: Index<T>> Default for Table<T, I> {
     fn default() -> Self {
         Table {
             item_id: ItemIDGenerator::default(),
-            items: HashMap::new(),
+            items: BTreeMap::new(),
             indices: HashMap::new(),
+            subscribers: Subscribers::default(),
+        }
+    }
+}
+
+/// Clones the items and indices into an independent [`Table`] which keeps
+/// generating [`ItemID`]s from where the original is. The handlers registered
+/// with [`Table::on_change`] are not cloned.
+impl<T: Clone, I: Index<T>> Clone for Table<T, I> {
+    fn clone(&self) -> Self {
+        Table {
+            item_id: ItemIDGenerator::new(self.item_id.peek().0),
+            items: self.items.clone(),
+            indices: self
+                .indices
+                .iter()
+                .map(|(index, index_storage)| (index.clone(), index_storage.boxed_clone()))
+                .collect(),
+            subscribers: Subscribers::default(),
         }
     }
 }
 
-impl<T: Clone, I: Index<T>> Table<T, I> {
+impl<T, I: Index<T>> Table<T, I> {
     #[must_use]
     pub fn empty() -> Self {
         Table::default()
     }
 
+    /// Adds an index to the [`Table`], building its contents from the items
+    /// already in the table.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the index is unique and the existing items contain duplicate
+    /// values for it, use [`Table::create_index`] to handle that case.
     #[must_use]
     pub fn add_index(mut self, index: I) -> Self {
-        let unique = index.is_unique();
-        match self.indices.entry(index) {
-            Entry::Occupied(_) => return self,
-            Entry::Vacant(e) => e.insert(new_index_storage(unique)),
-        };
+        if let Err(err) = self.create_index(index) {
+            panic!("failed to add index: {}", err);
+        }
+
+        self
+    }
+
+    /// Adds an index to the [`Table`], building its contents from the items
+    /// already in the table.
+    ///
+    /// Adding an index which already exists does nothing. If the index is
+    /// unique and the existing items contain duplicate values for it, or the
+    /// index extracts a value of the wrong [`DataType`] or no value while not
+    /// being nullable, the [`Table`] is left unchanged.
+    pub fn create_index(&mut self, index: I) -> Result<(), TableError<I>> {
+        if self.indices.contains_key(&index) {
+            return Ok(());
+        }
 
-        if self.items.len() == 0 {
-            return self;
+        let mut index_storage = new_index_storage(index.is_unique());
+
+        for (&item_id, item) in self.items.iter() {
+            let index_value = extract(&index, item);
+            Self::check_index_value(&index, item_id, index_value.as_ref())?;
+
+            if !index_storage.add_entry(item_id, index_value.clone()) {
+                let existing = index_storage.first(&index_value.unwrap()).unwrap();
+                return Err(TableError::UniqueViolation {
+                    index,
+                    existing,
+                    conflicting: item_id,
+                });
+            }
         }
 
-        todo!("Build the index");
+        self.indices.insert(index, index_storage);
+        Ok(())
     }
 
     #[must_use]
@@ -64,132 +180,3119 @@ impl<T: Clone, I: Index<T>> Table<T, I> {
     }
 }
 
-impl<T: Clone, I: Index<T>> Table<T, I> {
-    fn index_item(&mut self, item_id: ItemID, item: &T) {
-        for (index, index_storage) in self.indices.iter_mut() {
-            match index.extract(&item) {
-                Some(index_value) => {
-                    let index_data_type = index.data_type();
-                    if index_value.data_type() != index_data_type {
-                        todo!("Return an Err instead of panicking");
-                    }
+impl<T, I: Index<T>> Table<T, I> {
+    fn check_data_type(index: &I, value: &Value) -> Result<(), TableError<I>> {
+        if value.data_type() != index.data_type() {
+            return Err(TableError::TypeMismatch {
+                index: index.clone(),
+                expected: index.data_type(),
+                actual: value.data_type(),
+            });
+        }
 
-                    index_storage.add(item_id, index_value);
-                }
-                None => (),
-            };
+        Ok(())
+    }
+
+    /// Checks that the value extracted from the item with
+    /// [`item_id`](ItemID) can be stored in the index, `None` only being
+    /// allowed for nullable indices.
+    fn check_index_value(
+        index: &I,
+        item_id: ItemID,
+        value: Option<&Value>,
+    ) -> Result<(), TableError<I>> {
+        match value {
+            Some(value) => Self::check_data_type(index, value),
+            None if index.is_nullable() => Ok(()),
+            None => Err(TableError::NullNotAllowed {
+                index: index.clone(),
+                item_id,
+            }),
         }
     }
 
-    fn unindex_item(&mut self, item_id: ItemID, item: &T) {
+    /// Adds the item to every index, rolling back the entries already added
+    /// if any of the indices rejects it.
+    fn index_item(&mut self, item_id: ItemID, item: &T) -> Result<(), TableError<I>> {
+        let mut result = Ok(());
+        let mut indexed = 0;
         for (index, index_storage) in self.indices.iter_mut() {
-            match index.extract(&item) {
-                Some(index_value) => {
-                    let index_data_type = index.data_type();
-                    if index_value.data_type() != index_data_type {
-                        todo!("Return an Err instead of panicking");
-                    }
+            let index_value = extract(index, item);
+            if let Err(err) = Self::check_index_value(index, item_id, index_value.as_ref()) {
+                result = Err(err);
+                break;
+            }
 
-                    index_storage.remove(item_id, index_value);
-                }
-                None => (),
-            };
+            if !index_storage.add_entry(item_id, index_value.clone()) {
+                result = Err(TableError::UniqueViolation {
+                    index: index.clone(),
+                    existing: index_storage.first(&index_value.unwrap()).unwrap(),
+                    conflicting: item_id,
+                });
+                break;
+            }
+
+            indexed += 1;
         }
+
+        if result.is_err() {
+            for (index, index_storage) in self.indices.iter_mut().take(indexed) {
+                index_storage.remove_entry(item_id, extract(index, item));
+            }
+        }
+
+        result
+    }
+
+    /// Checks that the item can be removed from every index.
+    fn check_unindex_item(&self, item_id: ItemID, item: &T) -> Result<(), TableError<I>> {
+        for index in self.indices.keys() {
+            Self::check_index_value(index, item_id, extract(index, item).as_ref())?;
+        }
+
+        Ok(())
     }
 
-    fn reindex_item(&mut self, item_id: ItemID, old_item: &T, new_item: &T) {
+    /// Removes the item from every index. The values are validated before any
+    /// index is touched so that an error leaves the indices unchanged.
+    pub(crate) fn unindex_item(&mut self, item_id: ItemID, item: &T) -> Result<(), TableError<I>> {
+        self.check_unindex_item(item_id, item)?;
+
         for (index, index_storage) in self.indices.iter_mut() {
-            match (index.extract(&old_item), index.extract(&new_item)) {
-                (Some(old_index_value), Some(new_index_value)) => {
-                    if old_index_value == new_index_value {
-                        continue;
-                    } else if old_index_value.data_type() != new_index_value.data_type() {
-                        todo!("Return an Err instead of panicking");
-                    } else if old_index_value.data_type() != index.data_type() {
-                        todo!("Return an Err instead of panicking");
-                    }
+            index_storage.remove_entry(item_id, extract(index, item));
+        }
+
+        Ok(())
+    }
+
+    /// Moves the item's entries from the values of `old_item` to the values
+    /// of `new_item`, restoring the old entries if any of the indices rejects
+    /// the new values.
+    fn reindex_item(
+        &mut self,
+        item_id: ItemID,
+        old_item: &T,
+        new_item: &T,
+    ) -> Result<(), TableError<I>> {
+        let mut result = Ok(());
+        let mut reindexed = 0;
+        for (index, index_storage) in self.indices.iter_mut() {
+            let old_index_value = extract(index, old_item);
+            let new_index_value = extract(index, new_item);
+            if old_index_value == new_index_value {
+                reindexed += 1;
+                continue;
+            }
 
-                    index_storage.update(item_id, old_index_value, new_index_value);
+            if let Err(err) = Self::check_index_value(index, item_id, old_index_value.as_ref())
+                .and_then(|()| Self::check_index_value(index, item_id, new_index_value.as_ref()))
+            {
+                result = Err(err);
+                break;
+            }
+
+            index_storage.remove_entry(item_id, old_index_value.clone());
+            if !index_storage.add_entry(item_id, new_index_value.clone()) {
+                index_storage.add_entry(item_id, old_index_value);
+                result = Err(TableError::UniqueViolation {
+                    index: index.clone(),
+                    existing: index_storage.first(&new_index_value.unwrap()).unwrap(),
+                    conflicting: item_id,
+                });
+                break;
+            }
+
+            reindexed += 1;
+        }
+
+        if result.is_err() {
+            for (index, index_storage) in self.indices.iter_mut().take(reindexed) {
+                let old_index_value = extract(index, old_item);
+                let new_index_value = extract(index, new_item);
+                if old_index_value != new_index_value {
+                    index_storage.update(item_id, new_index_value, old_index_value);
                 }
-                _ => (),
-            };
+            }
+        }
+
+        result
+    }
+
+    /// Adds the item to the [`Table`] and its indices without notifying the
+    /// subscribers.
+    pub(crate) fn insert_item(&mut self, item_id: ItemID, item: T) -> Result<(), TableError<I>> {
+        self.index_item(item_id, &item)?;
+        self.items.insert(item_id, item);
+
+        Ok(())
+    }
+
+    /// Updates the item and its indices without notifying the subscribers,
+    /// returning the output of `update` together with the item as it was
+    /// before the update.
+    ///
+    /// `update` is run on a copy of the item which only replaces the item once
+    /// the indices have accepted it, so the [`Table`] is left unchanged also
+    /// if `update` panics.
+    pub(crate) fn update_item<O>(
+        &mut self,
+        item_id: ItemID,
+        update: impl FnOnce(&mut T) -> O,
+    ) -> Result<Option<(O, T)>, TableError<I>>
+    where
+        T: Clone,
+    {
+        let Some(item) = self.items.get(&item_id) else {
+            return Ok(None);
+        };
+
+        let old_item = item.clone();
+        let mut new_item = old_item.clone();
+        let out = update(&mut new_item);
+
+        self.reindex_item(item_id, &old_item, &new_item)?;
+        self.items.insert(item_id, new_item);
+
+        Ok(Some((out, old_item)))
+    }
+
+    /// Removes the item from the [`Table`] and its indices without notifying
+    /// the subscribers.
+    pub(crate) fn remove_item(&mut self, item_id: ItemID) -> Result<Option<T>, TableError<I>> {
+        let Some(item) = self.items.remove(&item_id) else {
+            return Ok(None);
+        };
+
+        match self.unindex_item(item_id, &item) {
+            Ok(()) => Ok(Some(item)),
+            Err(err) => {
+                self.items.insert(item_id, item);
+                Err(err)
+            }
         }
     }
 }
 
-impl<T: Clone, I: Index<T>> Table<T, I> {
+impl<T, I: Index<T>> Table<T, I> {
+    /// Inserts the item into the [`Table`], returning the [`ItemID`] assigned
+    /// to it.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the item violates an unique index or an index extracts a
+    /// value of the wrong [`DataType`] or no value while not being nullable,
+    /// use [`Table::try_insert`] to handle those cases.
     pub fn insert(&mut self, item: T) -> ItemID {
-        let item_id = self.item_id.next();
-        self.index_item(item_id, &item);
-        self.items.insert(item_id, item);
+        match self.try_insert(item) {
+            Ok(item_id) => item_id,
+            Err(err) => panic!("failed to insert item: {}", err),
+        }
+    }
+
+    /// Inserts the item into the [`Table`], returning the [`ItemID`] assigned
+    /// to it.
+    ///
+    /// If the item violates an unique index or an index extracts a value of
+    /// the wrong [`DataType`] or no value while not being nullable, the
+    /// [`Table`] is left unchanged.
+    pub fn try_insert(&mut self, item: T) -> Result<ItemID, TableError<I>> {
+        // Every item has a smaller ItemID than the next one, which is only
+        // taken once the item has been inserted.
+        let item_id = self.item_id.peek();
+        self.insert_with_id(item_id, item)?;
 
-        item_id
+        Ok(item_id)
     }
 
-    pub fn get(&self, item_id: ItemID) -> Option<T> {
-        self.items.get(&item_id).cloned()
+    /// Inserts the item into the [`Table`] with the given [`ItemID`] instead
+    /// of generating one. [`ItemID`]s generated afterwards are greater than
+    /// it.
+    ///
+    /// Errors if an item with the [`ItemID`] already exists and in the same
+    /// cases as [`Table::try_insert`], leaving the [`Table`] unchanged.
+    pub fn insert_with_id(&mut self, item_id: ItemID, item: T) -> Result<(), TableError<I>> {
+        if self.contains(item_id) {
+            return Err(TableError::ItemIDExists { item_id });
+        }
+
+        self.insert_item(item_id, item)?;
+        self.item_id.skip_past(item_id);
+        self.subscribers.notify(|clone_item| ChangeEvent::Inserted {
+            id: item_id,
+            item: clone_item(&self.items[&item_id]),
+        });
+
+        Ok(())
     }
 
-    pub fn update<O>(&mut self, item_id: ItemID, update: impl FnOnce(&mut T) -> O) -> Option<O> {
-        if let Some((old_item, new_item, out)) = match self.items.get_mut(&item_id) {
-            Some(item) => {
-                let old_item = item.clone();
-                let out = update(item);
-                Some((old_item, item.clone(), out))
+    /// Inserts every item into the [`Table`], returning the [`ItemID`]s
+    /// assigned to them in the same order.
+    ///
+    /// The values of every index are extracted and validated for the whole
+    /// batch before anything is inserted, so if any of the items violates an
+    /// unique index, either against the existing items or another item of the
+    /// batch, or an index extracts a value of the wrong [`DataType`] or no
+    /// value while not being nullable, the [`Table`] is left unchanged.
+    pub fn insert_many(
+        &mut self,
+        items: impl IntoIterator<Item = T>,
+    ) -> Result<Vec<ItemID>, TableError<I>> {
+        // The ItemIDs are only taken from the generator once the whole batch
+        // has been validated.
+        let first_item_id = self.item_id.peek().0;
+        let items: Vec<(ItemID, T)> = items
+            .into_iter()
+            .zip(first_item_id..)
+            .map(|(item, item_id)| (ItemID(item_id), item))
+            .collect();
+
+        let mut index_values = Vec::with_capacity(self.indices.len());
+        for (index, index_storage) in self.indices.iter() {
+            let mut batch: BTreeMap<&Value, ItemID> = BTreeMap::new();
+            let values: Vec<Option<Value>> =
+                items.iter().map(|(_, item)| extract(index, item)).collect();
+            for ((item_id, _), value) in items.iter().zip(values.iter()) {
+                Self::check_index_value(index, *item_id, value.as_ref())?;
+
+                let Some(value) = value else { continue };
+                if !index.is_unique() {
+                    continue;
+                }
+
+                let existing = index_storage
+                    .first(value)
+                    .or_else(|| batch.get(value).copied());
+                if let Some(existing) = existing {
+                    return Err(TableError::UniqueViolation {
+                        index: index.clone(),
+                        existing,
+                        conflicting: *item_id,
+                    });
+                }
+
+                batch.insert(value, *item_id);
             }
-            None => None,
-        } {
-            self.reindex_item(item_id, &old_item, &new_item);
-            Some(out)
-        } else {
-            None
+
+            index_values.push((index.clone(), values));
+        }
+
+        for (index, values) in index_values {
+            let index_storage = self.indices.get_mut(&index).unwrap();
+            for ((item_id, _), value) in items.iter().zip(values) {
+                index_storage.add_entry(*item_id, value);
+            }
+        }
+
+        let item_ids: Vec<ItemID> = items.iter().map(|(item_id, _)| *item_id).collect();
+        if let Some(last_item_id) = item_ids.last() {
+            self.item_id.skip_past(*last_item_id);
+        }
+        self.items.extend(items);
+        for item_id in item_ids.iter() {
+            self.subscribers.notify(|clone_item| ChangeEvent::Inserted {
+                id: *item_id,
+                item: clone_item(&self.items[item_id]),
+            });
+        }
+
+        Ok(item_ids)
+    }
+
+    pub fn get(&self, item_id: ItemID) -> Option<T>
+    where
+        T: Clone,
+    {
+        self.items.get(&item_id).cloned()
+    }
+
+    pub fn get_ref(&self, item_id: ItemID) -> Option<&T> {
+        self.items.get(&item_id)
+    }
+
+    /// Calls `f` with the item with [`item_id`](ItemID), returning its output
+    /// or `None` if there is no such item.
+    pub fn with<R>(&self, item_id: ItemID, f: impl FnOnce(&T) -> R) -> Option<R> {
+        self.items.get(&item_id).map(f)
+    }
+
+    /// Updates the item with [`item_id`](ItemID) in place, returning the
+    /// output of `update` or `None` if there is no such item.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the updated item violates an unique index or an index
+    /// extracts a value of the wrong [`DataType`] or no value while not being
+    /// nullable, use [`Table::try_update`] to handle those cases.
+    pub fn update<O>(&mut self, item_id: ItemID, update: impl FnOnce(&mut T) -> O) -> Option<O>
+    where
+        T: Clone,
+    {
+        match self.try_update(item_id, update) {
+            Ok(out) => out,
+            Err(err) => panic!("failed to update item: {}", err),
         }
     }
 
+    /// Updates the item with [`item_id`](ItemID) in place, returning the
+    /// output of `update` or `None` if there is no such item.
+    ///
+    /// If the updated item violates an unique index or an index extracts a
+    /// value of the wrong [`DataType`] or no value while not being nullable,
+    /// the update is reverted, leaving the [`Table`] unchanged.
+    pub fn try_update<O>(
+        &mut self,
+        item_id: ItemID,
+        update: impl FnOnce(&mut T) -> O,
+    ) -> Result<Option<O>, TableError<I>>
+    where
+        T: Clone,
+    {
+        let Some((out, old_item)) = self.update_item(item_id, update)? else {
+            return Ok(None);
+        };
+
+        self.subscribers.notify(|_| ChangeEvent::Updated {
+            id: item_id,
+            old: old_item,
+            new: self.items[&item_id].clone(),
+        });
+
+        Ok(Some(out))
+    }
+
     /// Removes the item with [`item_id`](ItemID) from the [`Table`], returning
     /// the removed item.
     ///
-    /// Will not vaccuum indices automatically potentially leaving "dangling"
-    /// ItemIDs there.
+    /// # Panics
+    ///
+    /// Panics if an index extracts a value of the wrong [`DataType`] from the
+    /// item, use [`Table::try_remove`] to handle that case.
     pub fn remove(&mut self, item_id: ItemID) -> Option<T> {
-        if let Some(out) = self.items.remove(&item_id) {
-            self.unindex_item(item_id, &out);
-            Some(out)
-        } else {
-            None
+        match self.try_remove(item_id) {
+            Ok(out) => out,
+            Err(err) => panic!("failed to remove item: {}", err),
+        }
+    }
+
+    /// Removes the item with [`item_id`](ItemID) from the [`Table`], returning
+    /// the removed item.
+    ///
+    /// If an index extracts a value of the wrong [`DataType`] from the item
+    /// the [`Table`] is left unchanged.
+    pub fn try_remove(&mut self, item_id: ItemID) -> Result<Option<T>, TableError<I>> {
+        let item = self.remove_item(item_id)?;
+        if let Some(item) = &item {
+            self.subscribers.notify(|clone_item| ChangeEvent::Removed {
+                id: item_id,
+                item: clone_item(item),
+            });
         }
+
+        Ok(item)
     }
 
+    /// Removes the item with [`item_id`](ItemID) if `remove_if` returns true
+    /// for it, returning the removed item.
+    ///
+    /// # Panics
+    ///
+    /// Panics if an index extracts a value of the wrong [`DataType`] from the
+    /// item.
     pub fn remove_if(&mut self, item_id: ItemID, remove_if: impl FnOnce(&T) -> bool) -> Option<T> {
-        match self.items.entry(item_id) {
-            Entry::Occupied(e) => {
-                if remove_if(e.get()) {
-                    let item = e.remove();
-                    self.unindex_item(item_id, &item);
-                    Some(item)
-                } else {
-                    None
+        match self.items.get(&item_id) {
+            Some(item) if remove_if(item) => self.remove(item_id),
+            _ => None,
+        }
+    }
+
+    /// Inserts the item or, if an item with the same value in the unique
+    /// `index` already exists, replaces that item with it. Returns the
+    /// [`ItemID`] of the item and whether it was inserted.
+    ///
+    /// Items for which the index has no value are always inserted. Errors if
+    /// the index is not unique or has not been added to the [`Table`], and in
+    /// the same cases as [`Table::try_insert`] and [`Table::try_update`].
+    pub fn upsert(&mut self, index: I, item: T) -> Result<(ItemID, bool), TableError<I>>
+    where
+        T: Clone,
+    {
+        if !index.is_unique() {
+            return Err(TableError::NotUnique { index });
+        }
+
+        let Some(index_storage) = self.indices.get(&index) else {
+            return Err(TableError::UnknownIndex { index });
+        };
+
+        let existing = match extract(&index, &item) {
+            Some(value) => {
+                Self::check_data_type(&index, &value)?;
+                index_storage.first(&value)
+            }
+            None => None,
+        };
+
+        match existing {
+            Some(item_id) => {
+                self.try_update(item_id, |existing| *existing = item)?;
+                Ok((item_id, false))
+            }
+            None => Ok((self.try_insert(item)?, true)),
+        }
+    }
+
+    /// Updates every item matching the [`Query`], returning how many items
+    /// were updated.
+    ///
+    /// The query is evaluated once before any item is updated, so updates
+    /// changing whether an item matches do not affect which items are
+    /// updated. If any of the updated items violates an unique index or an
+    /// index extracts a value of the wrong [`DataType`] all of the updates are
+    /// reverted, leaving the [`Table`] unchanged.
+    pub fn update_where(
+        &mut self,
+        query: &Query<I>,
+        mut update: impl FnMut(&mut T),
+    ) -> Result<usize, TableError<I>>
+    where
+        T: Clone,
+    {
+        let item_ids = self.query_item_ids(query)?;
+
+        let mut updated: Vec<(ItemID, T)> = Vec::with_capacity(item_ids.len());
+        for item_id in item_ids {
+            match self.update_item(item_id, &mut update) {
+                Ok(Some(((), old_item))) => updated.push((item_id, old_item)),
+                Ok(None) => (),
+                Err(err) => {
+                    for (item_id, old_item) in updated.into_iter().rev() {
+                        let new_item = self.items.insert(item_id, old_item.clone()).unwrap();
+                        self.reindex_item(item_id, &new_item, &old_item)
+                            .expect("reverting an update restores a valid state");
+                    }
+
+                    return Err(err);
+                }
+            }
+        }
+
+        for (item_id, old_item) in updated.iter() {
+            self.subscribers.notify(|_| ChangeEvent::Updated {
+                id: *item_id,
+                old: old_item.clone(),
+                new: self.items[item_id].clone(),
+            });
+        }
+
+        Ok(updated.len())
+    }
+
+    /// Removes every item matching the [`Query`], returning the removed items
+    /// ordered by their [`ItemID`].
+    ///
+    /// If an index extracts a value of the wrong [`DataType`] from any of the
+    /// items none of them are removed.
+    pub fn remove_where(&mut self, query: &Query<I>) -> Result<Vec<T>, TableError<I>> {
+        let item_ids = self.query_item_ids(query)?;
+
+        let mut removed = Vec::with_capacity(item_ids.len());
+        for item_id in item_ids {
+            match self.remove_item(item_id) {
+                Ok(Some(item)) => removed.push((item_id, item)),
+                Ok(None) => (),
+                Err(err) => {
+                    for (item_id, item) in removed.into_iter().rev() {
+                        self.insert_item(item_id, item)
+                            .expect("reverting a removal restores a valid state");
+                    }
+
+                    return Err(err);
                 }
             }
-            Entry::Vacant(_) => None,
         }
+
+        for (item_id, item) in removed.iter() {
+            self.subscribers.notify(|clone_item| ChangeEvent::Removed {
+                id: *item_id,
+                item: clone_item(item),
+            });
+        }
+
+        Ok(removed.into_iter().map(|(_, item)| item).collect())
+    }
+
+    /// Removes every item from the [`Table`], keeping its indices.
+    ///
+    /// The [`ItemID`]s of the removed items are never generated again, so
+    /// [`ItemID`]s held elsewhere cannot start referring to new items.
+    pub fn clear(&mut self) {
+        let items = std::mem::take(&mut self.items);
+        for (index, index_storage) in self.indices.iter_mut() {
+            *index_storage = new_index_storage(index.is_unique());
+        }
+
+        for (item_id, item) in items.iter() {
+            self.subscribers.notify(|clone_item| ChangeEvent::Removed {
+                id: *item_id,
+                item: clone_item(item),
+            });
+        }
+    }
+
+    /// Removes every item for which `keep` returns false, returning how many
+    /// items were removed.
+    ///
+    /// # Panics
+    ///
+    /// Panics if an index extracts a value of the wrong [`DataType`] from any
+    /// of the items to be removed, in which case none of them are removed.
+    pub fn retain(&mut self, mut keep: impl FnMut(ItemID, &T) -> bool) -> usize {
+        let item_ids: Vec<ItemID> = self
+            .items
+            .iter()
+            .filter(|(item_id, item)| !keep(**item_id, item))
+            .map(|(item_id, _)| *item_id)
+            .collect();
+
+        for item_id in item_ids.iter() {
+            if let Err(err) = self.check_unindex_item(*item_id, &self.items[item_id]) {
+                panic!("failed to remove item: {}", err);
+            }
+        }
+
+        let mut removed = Vec::with_capacity(item_ids.len());
+        for item_id in item_ids {
+            let item = self.items.remove(&item_id).unwrap();
+            self.unindex_item(item_id, &item)
+                .expect("checked before removing any item");
+            removed.push((item_id, item));
+        }
+
+        for (item_id, item) in removed.iter() {
+            self.subscribers.notify(|clone_item| ChangeEvent::Removed {
+                id: *item_id,
+                item: clone_item(item),
+            });
+        }
+
+        removed.len()
     }
 }
 
-impl<T: Clone, I: Index<T>> Table<T, I> {
-    pub fn where_eq(&self, index: I, value: Value) -> Vec<T> {
-        let item_ids = match self.indices.get(&index) {
-            Some(index_storage) => index_storage.get(&value),
-            None => vec![],
+impl<T, I: Index<T>> Table<T, I> {
+    /// Registers a handler which is called with every [`ChangeEvent`] made to
+    /// the items of the [`Table`].
+    ///
+    /// Handlers are called synchronously once the change and the indices have
+    /// been fully updated. Bulk operations notify the handlers one item at a
+    /// time after all of the items have been changed, and changes which are
+    /// reverted due to an error are never notified.
+    ///
+    /// Handlers must be `Send` and `Sync` so that the [`Table`] can be shared
+    /// between threads, for example through a
+    /// [`ConcurrentTable`](crate::ConcurrentTable).
+    pub fn on_change(
+        &mut self,
+        handler: impl FnMut(&ChangeEvent<T>) + Send + Sync + 'static,
+    ) -> SubscriptionId
+    where
+        T: Clone,
+    {
+        self.subscribers.subscribe(Box::new(handler))
+    }
+
+    /// Removes a handler registered with [`Table::on_change`], returning
+    /// whether it was registered.
+    pub fn unsubscribe(&mut self, subscription_id: SubscriptionId) -> bool {
+        self.subscribers.unsubscribe(subscription_id)
+    }
+}
+
+impl<T, I: Index<T>> Table<T, I> {
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    pub fn contains(&self, item_id: ItemID) -> bool {
+        self.items.contains_key(&item_id)
+    }
+
+    /// Iterates over the items in the [`Table`] in [`ItemID`] order.
+    pub fn iter(&self) -> impl Iterator<Item = (ItemID, &T)> + '_ {
+        self.items.iter().map(|(item_id, item)| (*item_id, item))
+    }
+
+    /// Iterates over the [`ItemID`]s in the [`Table`] in ascending order.
+    pub fn ids(&self) -> impl Iterator<Item = ItemID> + '_ {
+        self.items.keys().copied()
+    }
+
+    /// Returns the [`ItemID`] which the next inserted item will get, unless
+    /// one is reserved with [`Table::reserve_item_id`] first.
+    pub fn peek_item_id(&self) -> ItemID {
+        self.item_id.peek()
+    }
+
+    /// Takes an [`ItemID`] which no item inserted afterwards will get, for
+    /// inserting an item with it later using [`Table::insert_with_id`].
+    pub fn reserve_item_id(&self) -> ItemID {
+        self.item_id.next()
+    }
+
+    /// Returns a copy of the items ordered by their [`ItemID`], cheaper than
+    /// cloning the whole [`Table`] when the indices are not needed.
+    pub fn snapshot_items(&self) -> Vec<(ItemID, T)>
+    where
+        T: Clone,
+    {
+        self.items
+            .iter()
+            .map(|(item_id, item)| (*item_id, item.clone()))
+            .collect()
+    }
+}
+
+impl<T, I: Index<T>> Table<T, I> {
+    /// Returns the contents of the index ordered by value, leaving out the
+    /// items which are null in it.
+    ///
+    /// Errors if the index has not been added to the [`Table`].
+    pub fn index_entries(&self, index: I) -> Result<Vec<(Value, ItemID)>, TableError<I>> {
+        let Some(index_storage) = self.indices.get(&index) else {
+            return Err(TableError::UnknownIndex { index });
         };
 
-        let mut out = Vec::with_capacity(item_ids.len());
-        for item_id in item_ids {
-            if let Some(item) = self.get(item_id) {
-                out.push(item);
+        Ok(index_storage
+            .iter()
+            .map(|(value, item_id)| (value.clone(), item_id))
+            .collect())
+    }
+
+    /// Returns the value of every index for the item with
+    /// [`item_id`](ItemID), or an empty map if there is no such item.
+    ///
+    /// The values are extracted from the item and errors if any of them is
+    /// missing from its index. Stale entries left behind for the item are
+    /// only found by [`Table::verify_indices`].
+    pub fn indexed_values(
+        &self,
+        item_id: ItemID,
+    ) -> Result<HashMap<&I, Option<Value>>, ConsistencyError<I>> {
+        let Some(item) = self.items.get(&item_id) else {
+            return Ok(HashMap::new());
+        };
+
+        let mut out = HashMap::with_capacity(self.indices.len());
+        for (index, index_storage) in self.indices.iter() {
+            let value = extract(index, item);
+            if !has_entry(index_storage.as_ref(), item_id, value.as_ref()) {
+                return Err(ConsistencyError::MissingEntry {
+                    index: index.clone(),
+                    item_id,
+                    value,
+                });
+            }
+
+            out.insert(index, value);
+        }
+
+        Ok(out)
+    }
+
+    /// Checks every index against the items of the [`Table`], returning all of
+    /// the entries which are missing from or dangling in the indices.
+    pub fn verify_indices(&self) -> Result<(), Vec<ConsistencyError<I>>> {
+        let mut errors = Vec::new();
+        for (index, index_storage) in self.indices.iter() {
+            let entries = index_storage
+                .iter()
+                .map(|(value, item_id)| (Some(value), item_id))
+                .chain(index_storage.nulls().iter().map(|item_id| (None, *item_id)));
+            for (value, item_id) in entries {
+                let extracted = self.items.get(&item_id).map(|item| extract(index, item));
+                if extracted.as_ref().map(Option::as_ref) != Some(value) {
+                    errors.push(ConsistencyError::DanglingEntry {
+                        index: index.clone(),
+                        item_id,
+                        value: value.cloned(),
+                    });
+                }
+            }
+
+            for (&item_id, item) in self.items.iter() {
+                let value = extract(index, item);
+                if !has_entry(index_storage.as_ref(), item_id, value.as_ref()) {
+                    errors.push(ConsistencyError::MissingEntry {
+                        index: index.clone(),
+                        item_id,
+                        value,
+                    });
+                }
             }
         }
 
-        out
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+}
+
+impl<T, I: Index<T>> Table<T, I> {
+    /// Returns the items for which the value of the index equals `value`,
+    /// ordered by their [`ItemID`].
+    pub fn where_eq<K: TypedIndex<I, V>, V: Into<Value>>(
+        &self,
+        index: K,
+        value: V,
+    ) -> Vec<(ItemID, T)>
+    where
+        T: Clone,
+    {
+        let index = index.index();
+        match self.indices.get(&index) {
+            Some(index_storage) => {
+                self.get_all(index_storage.get(&normalize(&index, Cow::Owned(value.into()))))
+            }
+            None => vec![],
+        }
+    }
+
+    /// Like [`Table::where_eq`] but borrows the items instead of cloning them.
+    pub fn where_eq_ref<K: TypedIndex<I, V>, V: Into<Value>>(
+        &self,
+        index: K,
+        value: V,
+    ) -> Vec<(ItemID, &T)> {
+        let index = index.index();
+        match self.indices.get(&index) {
+            Some(index_storage) => {
+                self.get_all_ref(index_storage.get(&normalize(&index, Cow::Owned(value.into()))))
+            }
+            None => vec![],
+        }
+    }
+
+    /// Returns the item with `value` in the unique `index`, if any.
+    ///
+    /// Errors if the index is not unique or has not been added to the
+    /// [`Table`].
+    pub fn get_by_unique(
+        &self,
+        index: I,
+        value: &Value,
+    ) -> Result<Option<(ItemID, T)>, TableError<I>>
+    where
+        T: Clone,
+    {
+        let item_id = self.id_by_unique(index, value)?;
+        Ok(item_id.and_then(|item_id| Some((item_id, self.get(item_id)?))))
+    }
+
+    /// Returns the [`ItemID`] of the item with `value` in the unique `index`,
+    /// if any.
+    ///
+    /// Errors if the index is not unique or has not been added to the
+    /// [`Table`].
+    pub fn id_by_unique(&self, index: I, value: &Value) -> Result<Option<ItemID>, TableError<I>> {
+        if !index.is_unique() {
+            return Err(TableError::NotUnique { index });
+        }
+
+        let Some(index_storage) = self.indices.get(&index) else {
+            return Err(TableError::UnknownIndex { index });
+        };
+
+        Self::check_data_type(&index, value)?;
+        Ok(index_storage.first(&normalize(&index, Cow::Borrowed(value))))
+    }
+}
+
+impl<T, I: Index<T>> Table<T, I> {
+    /// Evaluates the [`Query`] and returns the matching items ordered by their
+    /// [`ItemID`].
+    ///
+    /// Comparisons against an index which has not been added to the [`Table`]
+    /// fall back to a full scan using [`Index::extract`]. Comparing an index
+    /// to a value of a different [`DataType`] is an error.
+    pub fn where_query(&self, query: &Query<I>) -> Result<Vec<(ItemID, T)>, TableError<I>>
+    where
+        T: Clone,
+    {
+        Ok(self.get_all(self.query_item_ids(query)?))
+    }
+
+    /// Like [`Table::where_query`] but borrows the items instead of cloning
+    /// them.
+    pub fn where_query_ref(&self, query: &Query<I>) -> Result<Vec<(ItemID, &T)>, TableError<I>> {
+        Ok(self.get_all_ref(self.query_item_ids(query)?))
+    }
+
+    /// Evaluates the [`Query`] like [`Table::where_query`] but returns the
+    /// matching items ordered by the value of the `order_by` index.
+    ///
+    /// Items with equal values are ordered by their [`ItemID`], reversed along
+    /// with the values when the direction is descending. Items for which the
+    /// index has no value are placed last in ascending [`ItemID`] order.
+    pub fn where_query_ordered(
+        &self,
+        query: &Query<I>,
+        order_by: I,
+        direction: Direction,
+    ) -> Result<Vec<(ItemID, T)>, TableError<I>>
+    where
+        T: Clone,
+    {
+        let item_ids = self.query_item_ids(query)?;
+        Ok(self.get_all(self.order_item_ids(item_ids, &order_by, direction, None)))
+    }
+
+    /// Returns all of the items ordered by the value of the index, see
+    /// [`Table::where_query_ordered`] for how ties and missing values are
+    /// ordered.
+    pub fn order_by(&self, index: I, direction: Direction) -> Vec<(ItemID, T)>
+    where
+        T: Clone,
+    {
+        let item_ids = self.items.keys().copied().collect();
+        self.get_all(self.order_item_ids(item_ids, &index, direction, None))
+    }
+
+    /// Evaluates the [`Query`] and returns a page of the matching items as
+    /// described by the [`QueryOptions`].
+    ///
+    /// When there is a limit and the items are ordered by an index which has
+    /// been added to the [`Table`], the index is walked in order and each item
+    /// is tested against the query until the requested page is filled,
+    /// instead of finding every matching item first.
+    pub fn select(
+        &self,
+        query: &Query<I>,
+        options: &QueryOptions<I>,
+    ) -> Result<Vec<(ItemID, T)>, TableError<I>>
+    where
+        T: Clone,
+    {
+        let end = options
+            .limit
+            .map(|limit| options.offset.saturating_add(limit));
+
+        if let (Some((index, direction)), Some(end)) = (&options.order_by, end) {
+            if let Some(index_storage) = self.indices.get(index) {
+                Self::check_query(query)?;
+
+                let entries = match direction {
+                    Direction::Ascending => index_storage.iter(),
+                    Direction::Descending => Box::new(index_storage.iter().rev()),
+                };
+                let item_ids = entries
+                    .map(|(_, item_id)| item_id)
+                    .chain(index_storage.nulls().iter().copied())
+                    .filter(|item_id| Self::matches(query, &self.items[item_id]))
+                    .take(end);
+
+                return Ok(self.get_all(item_ids.skip(options.offset)));
+            }
+        }
+
+        let item_ids = self.query_item_ids(query)?;
+        let item_ids = match &options.order_by {
+            Some((index, direction)) => self.order_item_ids(item_ids, index, *direction, end),
+            None => item_ids
+                .into_iter()
+                .take(end.unwrap_or(usize::MAX))
+                .collect(),
+        };
+
+        Ok(self.get_all(item_ids.into_iter().skip(options.offset)))
+    }
+
+    /// Returns the items for which the value of the index is within the
+    /// bounds, ordered by their [`ItemID`].
+    pub fn where_range(
+        &self,
+        index: I,
+        min: Bound<&Value>,
+        max: Bound<&Value>,
+    ) -> Result<Vec<(ItemID, T)>, TableError<I>>
+    where
+        T: Clone,
+    {
+        Ok(self.get_all(self.range_item_ids(&index, min, max)?))
+    }
+
+    /// Returns the items for which the value of the composite index starts
+    /// with `prefix`, ordered by their [`ItemID`].
+    pub fn where_composite_prefix(
+        &self,
+        index: I,
+        prefix: &[Value],
+    ) -> Result<Vec<(ItemID, T)>, TableError<I>>
+    where
+        T: Clone,
+    {
+        Ok(self.get_all(self.composite_prefix_item_ids(&index, prefix)?))
+    }
+
+    /// Returns the items for which the value of the string index starts with
+    /// `prefix`, ordered by their [`ItemID`].
+    pub fn where_prefix(&self, index: I, prefix: &str) -> Result<Vec<(ItemID, T)>, TableError<I>>
+    where
+        T: Clone,
+    {
+        Ok(self.get_all(self.string_prefix_item_ids(&index, prefix)?))
+    }
+
+    /// Returns the items for which the index has no value, ordered by their
+    /// [`ItemID`].
+    pub fn where_null(&self, index: I) -> Vec<(ItemID, T)>
+    where
+        T: Clone,
+    {
+        self.get_all(self.null_item_ids(&index))
+    }
+
+    /// Returns how many items match the [`Query`] without cloning them.
+    pub fn count_where(&self, query: &Query<I>) -> Result<usize, TableError<I>> {
+        Ok(self.query_item_ids(query)?.len())
+    }
+
+    /// Returns how many items have `value` as the value of the index.
+    pub fn count_eq(&self, index: I, value: &Value) -> usize {
+        match self.indices.get(&index) {
+            Some(index_storage) => {
+                let value = normalize(&index, Cow::Borrowed(value));
+                index_storage
+                    .range(Bound::Included(&value), Bound::Included(&value))
+                    .count()
+            }
+            None => 0,
+        }
+    }
+
+    /// Returns the smallest value in the index together with the item having
+    /// it, the one with the smallest [`ItemID`] if there are several.
+    ///
+    /// The value is returned as stored in the index, so strings of case
+    /// insensitive indices are lowercased.
+    pub fn min_by_index(&self, index: I) -> Option<(Value, ItemID)> {
+        let (value, item_id) = self.indices.get(&index)?.iter().next()?;
+        Some((value.clone(), item_id))
+    }
+
+    /// Returns the largest value in the index together with the item having
+    /// it, the one with the largest [`ItemID`] if there are several.
+    ///
+    /// The value is returned as stored in the index, so strings of case
+    /// insensitive indices are lowercased.
+    pub fn max_by_index(&self, index: I) -> Option<(Value, ItemID)> {
+        let (value, item_id) = self.indices.get(&index)?.iter().next_back()?;
+        Some((value.clone(), item_id))
+    }
+
+    fn get_all(&self, item_ids: impl IntoIterator<Item = ItemID>) -> Vec<(ItemID, T)>
+    where
+        T: Clone,
+    {
+        item_ids
+            .into_iter()
+            .filter_map(|item_id| Some((item_id, self.get(item_id)?)))
+            .collect()
+    }
+
+    /// Returns the non-null entries of the index ordered by value, extracting
+    /// them from the items if the index has not been added to the [`Table`].
+    pub(crate) fn sorted_entries(&self, index: &I) -> Vec<(Cow<'_, Value>, ItemID)> {
+        match self.indices.get(index) {
+            Some(index_storage) => index_storage
+                .iter()
+                .map(|(value, item_id)| (Cow::Borrowed(value), item_id))
+                .collect(),
+            None => {
+                let mut entries = self
+                    .items
+                    .iter()
+                    .filter_map(|(item_id, item)| {
+                        Some((Cow::Owned(extract(index, item)?), *item_id))
+                    })
+                    .collect::<Vec<_>>();
+                entries.sort_unstable();
+                entries
+            }
+        }
+    }
+
+    fn get_all_ref(&self, item_ids: impl IntoIterator<Item = ItemID>) -> Vec<(ItemID, &T)> {
+        item_ids
+            .into_iter()
+            .filter_map(|item_id| Some((item_id, self.items.get(&item_id)?)))
+            .collect()
+    }
+
+    /// Orders the ItemIDs by the value of the index, returning at most
+    /// `limit` of them.
+    fn order_item_ids(
+        &self,
+        mut item_ids: BTreeSet<ItemID>,
+        index: &I,
+        direction: Direction,
+        limit: Option<usize>,
+    ) -> Vec<ItemID> {
+        let limit = limit.unwrap_or(usize::MAX);
+        let mut out = Vec::with_capacity(item_ids.len().min(limit));
+
+        match self.indices.get(index) {
+            Some(index_storage) => {
+                let entries = match direction {
+                    Direction::Ascending => index_storage.iter(),
+                    Direction::Descending => Box::new(index_storage.iter().rev()),
+                };
+
+                for (_, item_id) in entries {
+                    if out.len() == limit {
+                        return out;
+                    }
+
+                    if item_ids.remove(&item_id) {
+                        out.push(item_id);
+                    }
+                }
+            }
+            None => {
+                let mut values = Vec::with_capacity(item_ids.len());
+                item_ids.retain(|item_id| match extract(index, &self.items[item_id]) {
+                    Some(value) => {
+                        values.push((value, *item_id));
+                        false
+                    }
+                    None => true,
+                });
+
+                values.sort_unstable();
+                if direction == Direction::Descending {
+                    values.reverse();
+                }
+
+                out.extend(values.into_iter().take(limit).map(|(_, item_id)| item_id));
+            }
+        }
+
+        let remaining = limit - out.len();
+        out.extend(item_ids.into_iter().take(remaining));
+        out
+    }
+
+    /// Checks that the values in the [`Query`] have the data types of their
+    /// indices, failing like evaluating the query would.
+    fn check_query(query: &Query<I>) -> Result<(), TableError<I>> {
+        match query {
+            Query::Not(child) => Self::check_query(child),
+            Query::And(children) | Query::Or(children) => {
+                children.iter().try_for_each(Self::check_query)
+            }
+            Query::Eq(index, value) => Self::check_data_type(index, value),
+            Query::In(index, values) => values
+                .iter()
+                .try_for_each(|value| Self::check_data_type(index, value)),
+            Query::Range(index, min, max) => {
+                [min, max].into_iter().try_for_each(|bound| match bound {
+                    Bound::Included(value) | Bound::Excluded(value) => {
+                        Self::check_data_type(index, value)
+                    }
+                    Bound::Unbounded => Ok(()),
+                })
+            }
+            Query::CompositePrefix(index, _) if index.data_type() != DataType::Composite => {
+                Err(TableError::TypeMismatch {
+                    index: index.clone(),
+                    expected: index.data_type(),
+                    actual: DataType::Composite,
+                })
+            }
+            Query::StartsWith(index, prefix) => {
+                Self::check_data_type(index, &Value::string(prefix.as_str()))
+            }
+            Query::CompositePrefix(..) | Query::IsNull(_) => Ok(()),
+        }
+    }
+
+    /// Whether the item matches the [`Query`], evaluated against the values
+    /// extracted from the item instead of the index storages. The query must
+    /// have passed [`Table::check_query`].
+    fn matches(query: &Query<I>, item: &T) -> bool {
+        let value_of = |index: &I| extract(index, item);
+        let normalized = |index: &I, value: &Value| normalize(index, Cow::Owned(value.clone()));
+
+        match query {
+            Query::Not(child) => !Self::matches(child, item),
+            Query::And(children) => children.iter().all(|child| Self::matches(child, item)),
+            Query::Or(children) => children.iter().any(|child| Self::matches(child, item)),
+            Query::Eq(index, value) => {
+                value_of(index).is_some_and(|extracted| extracted == *normalized(index, value))
+            }
+            Query::In(index, values) => value_of(index).is_some_and(|extracted| {
+                values
+                    .iter()
+                    .any(|value| extracted == *normalized(index, value))
+            }),
+            Query::Range(index, min, max) => {
+                let min = min.as_ref().map(|value| normalized(index, value));
+                let max = max.as_ref().map(|value| normalized(index, value));
+                let bounds = (
+                    min.as_ref().map(AsRef::as_ref),
+                    max.as_ref().map(AsRef::as_ref),
+                );
+                value_of(index).is_some_and(|extracted| bounds.contains(&&extracted))
+            }
+            Query::CompositePrefix(index, prefix) => {
+                value_of(index).is_some_and(|extracted| extracted.has_prefix(prefix))
+            }
+            Query::StartsWith(index, prefix) => {
+                let prefix = normalize(index, Cow::Owned(Value::string(prefix.as_str())));
+                match (value_of(index), prefix.as_ref()) {
+                    (Some(Value::String(string)), Value::String(prefix)) => {
+                        string.starts_with(prefix.as_str())
+                    }
+                    _ => false,
+                }
+            }
+            Query::IsNull(index) => value_of(index).is_none(),
+        }
+    }
+
+    fn query_item_ids(&self, query: &Query<I>) -> Result<BTreeSet<ItemID>, TableError<I>> {
+        Ok(match query {
+            Query::Not(child) => {
+                let excluded = self.query_item_ids(child)?;
+                self.items
+                    .keys()
+                    .filter(|item_id| !excluded.contains(item_id))
+                    .copied()
+                    .collect()
+            }
+            Query::And(children) => {
+                let mut children = children.iter();
+                let mut out = match children.next() {
+                    Some(child) => self.query_item_ids(child)?,
+                    None => return Ok(self.items.keys().copied().collect()),
+                };
+
+                for child in children {
+                    let item_ids = self.query_item_ids(child)?;
+                    out.retain(|item_id| item_ids.contains(item_id));
+                }
+
+                out
+            }
+            Query::Or(children) => {
+                let mut out = BTreeSet::new();
+                for child in children.iter() {
+                    out.append(&mut self.query_item_ids(child)?);
+                }
+
+                out
+            }
+            Query::Eq(index, value) => self.eq_item_ids(index, value)?,
+            Query::In(index, values) => self.in_item_ids(index, values)?,
+            Query::Range(index, min, max) => {
+                self.range_item_ids(index, min.as_ref(), max.as_ref())?
+            }
+            Query::CompositePrefix(index, prefix) => {
+                self.composite_prefix_item_ids(index, prefix)?
+            }
+            Query::StartsWith(index, prefix) => self.string_prefix_item_ids(index, prefix)?,
+            Query::IsNull(index) => self.null_item_ids(index),
+        })
+    }
+
+    pub(crate) fn null_item_ids(&self, index: &I) -> BTreeSet<ItemID> {
+        match self.indices.get(index) {
+            Some(index_storage) => index_storage.nulls().clone(),
+            None => self
+                .items
+                .iter()
+                .filter(|(_, item)| extract(index, item).is_none())
+                .map(|(item_id, _)| *item_id)
+                .collect(),
+        }
+    }
+
+    fn eq_item_ids(&self, index: &I, value: &Value) -> Result<BTreeSet<ItemID>, TableError<I>> {
+        Self::check_data_type(index, value)?;
+
+        let value = normalize(index, Cow::Borrowed(value));
+        Ok(match self.indices.get(index) {
+            Some(index_storage) => index_storage.get(&value).into_iter().collect(),
+            None => self
+                .items
+                .iter()
+                .filter(|(_, item)| extract(index, item).as_ref() == Some(&value))
+                .map(|(item_id, _)| *item_id)
+                .collect(),
+        })
+    }
+
+    fn in_item_ids(&self, index: &I, values: &[Value]) -> Result<BTreeSet<ItemID>, TableError<I>> {
+        for value in values {
+            Self::check_data_type(index, value)?;
+        }
+
+        let mut values = values
+            .iter()
+            .map(|value| normalize(index, Cow::Borrowed(value)))
+            .collect::<Vec<_>>();
+        values.sort_unstable();
+        values.dedup();
+
+        Ok(match self.indices.get(index) {
+            Some(index_storage) => values
+                .iter()
+                .flat_map(|value| index_storage.get(value))
+                .collect(),
+            None => self
+                .items
+                .iter()
+                .filter(|(_, item)| {
+                    extract(index, item).is_some_and(|value| {
+                        values
+                            .binary_search_by(|probe| probe.as_ref().cmp(&value))
+                            .is_ok()
+                    })
+                })
+                .map(|(item_id, _)| *item_id)
+                .collect(),
+        })
+    }
+
+    fn range_item_ids(
+        &self,
+        index: &I,
+        min: Bound<&Value>,
+        max: Bound<&Value>,
+    ) -> Result<BTreeSet<ItemID>, TableError<I>> {
+        for bound in [min, max] {
+            if let Bound::Included(value) | Bound::Excluded(value) = bound {
+                Self::check_data_type(index, value)?;
+            }
+        }
+
+        let min = min.map(|value| normalize(index, Cow::Borrowed(value)));
+        let max = max.map(|value| normalize(index, Cow::Borrowed(value)));
+        let (min, max) = (
+            min.as_ref().map(AsRef::as_ref),
+            max.as_ref().map(AsRef::as_ref),
+        );
+
+        Ok(match self.indices.get(index) {
+            Some(index_storage) => index_storage
+                .range(min, max)
+                .map(|(_, item_id)| item_id)
+                .collect(),
+            None => self
+                .items
+                .iter()
+                .filter(|(_, item)| {
+                    extract(index, item).is_some_and(|value| (min, max).contains(&&value))
+                })
+                .map(|(item_id, _)| *item_id)
+                .collect(),
+        })
+    }
+
+    fn composite_prefix_item_ids(
+        &self,
+        index: &I,
+        prefix: &[Value],
+    ) -> Result<BTreeSet<ItemID>, TableError<I>> {
+        if index.data_type() != DataType::Composite {
+            return Err(TableError::TypeMismatch {
+                index: index.clone(),
+                expected: index.data_type(),
+                actual: DataType::Composite,
+            });
+        }
+
+        let min = Value::Composite(prefix.to_vec());
+        Ok(match self.indices.get(index) {
+            Some(index_storage) => index_storage
+                .range(Bound::Included(&min), Bound::Unbounded)
+                .take_while(|(value, _)| value.has_prefix(prefix))
+                .map(|(_, item_id)| item_id)
+                .collect(),
+            None => self
+                .items
+                .iter()
+                .filter(|(_, item)| {
+                    extract(index, item).is_some_and(|value| value.has_prefix(prefix))
+                })
+                .map(|(item_id, _)| *item_id)
+                .collect(),
+        })
+    }
+
+    fn string_prefix_item_ids(
+        &self,
+        index: &I,
+        prefix: &str,
+    ) -> Result<BTreeSet<ItemID>, TableError<I>> {
+        let prefix = normalize(index, Cow::Owned(Value::string(prefix)));
+        Self::check_data_type(index, &prefix)?;
+
+        let Value::String(prefix_string) = prefix.as_ref() else {
+            unreachable!("normalizing a string results in a string");
+        };
+        let has_prefix = |value: &Value| match value {
+            Value::String(string) => string.starts_with(prefix_string.as_str()),
+            _ => false,
+        };
+
+        Ok(match self.indices.get(index) {
+            Some(index_storage) => index_storage
+                .range(Bound::Included(&prefix), Bound::Unbounded)
+                .take_while(|(value, _)| has_prefix(value))
+                .map(|(_, item_id)| item_id)
+                .collect(),
+            None => self
+                .items
+                .iter()
+                .filter(|(_, item)| extract(index, item).is_some_and(|value| has_prefix(&value)))
+                .map(|(item_id, _)| *item_id)
+                .collect(),
+        })
+    }
+}
+
+impl<T, I: Index<T>> IntoIterator for Table<T, I> {
+    type Item = (ItemID, T);
+    type IntoIter = btree_map::IntoIter<ItemID, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.items.into_iter()
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::cell::Cell;
+
+    use super::*;
+    use crate::{DateTime, Uuid};
+
+    #[derive(Debug, Clone, PartialEq)]
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+    pub(crate) struct User {
+        pub name: String,
+        pub age: i64,
+        pub email: Option<String>,
+    }
+
+    pub(crate) fn user(name: &str, age: i64) -> User {
+        User {
+            name: name.to_string(),
+            age,
+            email: None,
+        }
+    }
+
+    /// `Name` is unique while `Age` is not, and `Email` is unique, nullable and
+    /// case insensitive.
+    #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+    pub(crate) enum UserIndex {
+        Name,
+        Age,
+        Email,
+    }
+
+    impl Index<User> for UserIndex {
+        fn data_type(&self) -> DataType {
+            match self {
+                UserIndex::Name | UserIndex::Email => DataType::String,
+                UserIndex::Age => DataType::Int,
+            }
+        }
+
+        fn extract(&self, user: &User) -> Option<Value> {
+            match self {
+                UserIndex::Name => Some(Value::string(&user.name)),
+                UserIndex::Age => Some(Value::int(user.age)),
+                UserIndex::Email => user.email.as_ref().map(Value::string),
+            }
+        }
+
+        fn is_unique(&self) -> bool {
+            matches!(self, UserIndex::Name | UserIndex::Email)
+        }
+
+        fn is_nullable(&self) -> bool {
+            matches!(self, UserIndex::Email)
+        }
+
+        fn case_insensitive(&self) -> bool {
+            matches!(self, UserIndex::Email)
+        }
+    }
+
+    pub(crate) struct Name;
+
+    impl TypedIndex<UserIndex, &'static str> for Name {
+        fn index(self) -> UserIndex {
+            UserIndex::Name
+        }
+    }
+
+    pub(crate) struct Age;
+
+    impl TypedIndex<UserIndex, i64> for Age {
+        fn index(self) -> UserIndex {
+            UserIndex::Age
+        }
+    }
+
+    /// A table with the users Ann 30, Bob 25, Cid 30, Dan 41 and Eve 25 in
+    /// that order, indexed by `indices`.
+    pub(crate) fn users(indices: impl IntoIterator<Item = UserIndex>) -> Table<User, UserIndex> {
+        let mut table = Table::with_indices(indices);
+        for (name, age) in [
+            ("Ann", 30),
+            ("Bob", 25),
+            ("Cid", 30),
+            ("Dan", 41),
+            ("Eve", 25),
+        ] {
+            table.insert(user(name, age));
+        }
+
+        table
+    }
+
+    /// The name of an index with its entries and null entries.
+    type IndexContents = (String, Vec<(Value, ItemID)>, BTreeSet<ItemID>);
+
+    /// Everything observable about a table, for checking that a failed
+    /// operation left it unchanged.
+    #[derive(Debug, PartialEq)]
+    pub(crate) struct Contents<T> {
+        pub next_item_id: ItemID,
+        pub items: Vec<(ItemID, T)>,
+        pub indices: Vec<IndexContents>,
+    }
+
+    pub(crate) fn contents<T: Clone, I: Index<T>>(table: &Table<T, I>) -> Contents<T> {
+        let mut indices: Vec<_> = table
+            .indices
+            .iter()
+            .map(|(index, index_storage)| {
+                (
+                    format!("{:?}", index),
+                    table.index_entries(index.clone()).unwrap(),
+                    index_storage.nulls().clone(),
+                )
+            })
+            .collect();
+        indices.sort_unstable_by(|a, b| a.0.cmp(&b.0));
+
+        Contents {
+            next_item_id: table.item_id.peek(),
+            items: table.snapshot_items(),
+            indices,
+        }
+    }
+
+    pub(crate) fn names<'a>(items: impl IntoIterator<Item = &'a (ItemID, User)>) -> Vec<String> {
+        items
+            .into_iter()
+            .map(|(_, user)| user.name.clone())
+            .collect()
+    }
+
+    #[test]
+    fn where_query_nested() {
+        let table = users([UserIndex::Name, UserIndex::Age]);
+
+        // (Ann or Bob or Dan) and not 30
+        let query = Query::and([
+            Query::or([
+                Query::eq(UserIndex::Name, "Ann"),
+                Query::or([
+                    Query::eq(UserIndex::Name, "Bob"),
+                    Query::eq(UserIndex::Name, "Dan"),
+                ]),
+            ]),
+            !Query::eq(UserIndex::Age, 30),
+        ]);
+        assert_eq!(names(&table.where_query(&query).unwrap()), ["Bob", "Dan"]);
+
+        // not (25 or (30 and not Cid))
+        let query = !Query::or([
+            Query::eq(UserIndex::Age, 25),
+            Query::and([
+                Query::eq(UserIndex::Age, 30),
+                !Query::eq(UserIndex::Name, "Cid"),
+            ]),
+        ]);
+        assert_eq!(names(&table.where_query(&query).unwrap()), ["Cid", "Dan"]);
+    }
+
+    #[test]
+    fn where_query_empty_groups() {
+        let table = users([UserIndex::Name]);
+
+        let all = table.where_query(&Query::and([])).unwrap();
+        assert_eq!(names(&all), ["Ann", "Bob", "Cid", "Dan", "Eve"]);
+        assert!(table.where_query(&Query::or([])).unwrap().is_empty());
+        assert!(table.where_query(&!Query::and([])).unwrap().is_empty());
+    }
+
+    #[test]
+    fn where_query_without_index_scans_items() {
+        let indexed = users([UserIndex::Name, UserIndex::Age]);
+        let unindexed = users([]);
+
+        let queries = [
+            Query::eq(UserIndex::Age, 30),
+            Query::eq(UserIndex::Age, 99),
+            Query::and([
+                Query::eq(UserIndex::Age, 25),
+                !Query::eq(UserIndex::Name, "Bob"),
+            ]),
+            Query::or([
+                Query::eq(UserIndex::Name, "Dan"),
+                Query::eq(UserIndex::Age, 30),
+            ]),
+        ];
+        for query in queries.iter() {
+            assert_eq!(
+                indexed.where_query(query).unwrap(),
+                unindexed.where_query(query).unwrap(),
+                "{:?}",
+                query
+            );
+        }
+
+        let thirty = unindexed
+            .where_query(&Query::eq(UserIndex::Age, 30))
+            .unwrap();
+        assert_eq!(names(&thirty), ["Ann", "Cid"]);
+    }
+
+    #[test]
+    fn create_index_backfills_existing_items() {
+        let mut table = users([]);
+        table.create_index(UserIndex::Age).unwrap();
+        table.insert(user("Fay", 30));
+
+        assert_eq!(
+            names(&table.where_eq(UserIndex::Age, 30)),
+            ["Ann", "Cid", "Fay"]
+        );
+        assert_eq!(table.count_eq(UserIndex::Age, &25.into()), 2);
+        assert_eq!(table.verify_indices(), Ok(()));
+
+        // Adding it again keeps the entries.
+        table.create_index(UserIndex::Age).unwrap();
+        assert_eq!(table.count_eq(UserIndex::Age, &30.into()), 3);
+    }
+
+    /// Index over the age which claims to be unique.
+    #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+    struct UniqueAge;
+
+    impl Index<User> for UniqueAge {
+        fn data_type(&self) -> DataType {
+            DataType::Int
+        }
+
+        fn extract(&self, user: &User) -> Option<Value> {
+            Some(Value::int(user.age))
+        }
+
+        fn is_unique(&self) -> bool {
+            true
+        }
+    }
+
+    #[test]
+    fn create_unique_index_over_duplicates() {
+        let mut table = Table::empty();
+        let ann = table.insert(user("Ann", 30));
+        table.insert(user("Bob", 25));
+        let cid = table.insert(user("Cid", 30));
+
+        assert_eq!(
+            table.create_index(UniqueAge),
+            Err(TableError::UniqueViolation {
+                index: UniqueAge,
+                existing: ann,
+                conflicting: cid,
+            })
+        );
+        assert!(table.indices.is_empty());
+        assert_eq!(table.len(), 3);
+
+        // The index can be added once the duplicate is gone.
+        table.remove(cid);
+        table.create_index(UniqueAge).unwrap();
+        assert_eq!(table.id_by_unique(UniqueAge, &30.into()), Ok(Some(ann)));
+    }
+
+    #[test]
+    #[should_panic(expected = "failed to add index")]
+    fn add_unique_index_over_duplicates_panics() {
+        let mut table = Table::empty();
+        table.insert(user("Ann", 30));
+        table.insert(user("Cid", 30));
+        let _ = table.add_index(UniqueAge);
+    }
+
+    #[test]
+    fn failed_try_insert_leaves_table_unchanged() {
+        let mut table = users([UserIndex::Name, UserIndex::Age]);
+        let before = contents(&table);
+
+        let err = table.try_insert(user("Cid", 99)).unwrap_err();
+        assert!(matches!(err, TableError::UniqueViolation { .. }));
+        assert_eq!(contents(&table), before);
+
+        // The ItemID which would have been used is still the next one.
+        let fay = table.try_insert(user("Fay", 99)).unwrap();
+        assert_eq!(fay, before.next_item_id);
+    }
+
+    #[test]
+    fn failed_try_update_leaves_table_unchanged() {
+        let mut table = users([UserIndex::Name, UserIndex::Age]);
+        let bob = table.where_eq(UserIndex::Name, "Bob")[0].0;
+        let before = contents(&table);
+
+        let result = table.try_update(bob, |user| {
+            user.age = 99;
+            user.name = "Dan".to_string();
+        });
+        assert!(matches!(result, Err(TableError::UniqueViolation { .. })));
+        assert_eq!(contents(&table), before);
+        assert!(table.where_eq(UserIndex::Age, 99).is_empty());
+
+        assert_eq!(table.try_update(bob, |user| user.age = 26), Ok(Some(())));
+        assert_eq!(names(&table.where_eq(UserIndex::Age, 26)), ["Bob"]);
+    }
+
+    #[test]
+    fn range_bounds() {
+        for table in [users([UserIndex::Age]), users([])] {
+            let query = |query: Query<UserIndex>| names(&table.where_query(&query).unwrap());
+
+            assert_eq!(query(Query::lt(UserIndex::Age, 30)), ["Bob", "Eve"]);
+            assert_eq!(
+                query(Query::lte(UserIndex::Age, 30)),
+                ["Ann", "Bob", "Cid", "Eve"]
+            );
+            assert_eq!(query(Query::gt(UserIndex::Age, 30)), ["Dan"]);
+            assert_eq!(query(Query::gte(UserIndex::Age, 30)), ["Ann", "Cid", "Dan"]);
+            assert_eq!(
+                query(Query::between(UserIndex::Age, 25, 30)),
+                ["Ann", "Bob", "Cid", "Eve"]
+            );
+            assert_eq!(
+                query(Query::between(UserIndex::Age, 30, 30)),
+                ["Ann", "Cid"]
+            );
+        }
+    }
+
+    #[test]
+    fn empty_ranges() {
+        for table in [users([UserIndex::Age]), users([])] {
+            let range = |min: Bound<Value>, max: Bound<Value>| {
+                table
+                    .where_range(UserIndex::Age, min.as_ref(), max.as_ref())
+                    .unwrap()
+            };
+
+            assert!(range(Bound::Included(31.into()), Bound::Included(30.into())).is_empty());
+            assert!(range(Bound::Excluded(30.into()), Bound::Excluded(30.into())).is_empty());
+            assert!(range(Bound::Included(30.into()), Bound::Excluded(30.into())).is_empty());
+            assert!(range(Bound::Excluded(30.into()), Bound::Included(30.into())).is_empty());
+            assert!(range(Bound::Excluded(26.into()), Bound::Excluded(30.into())).is_empty());
+            assert!(range(Bound::Unbounded, Bound::Excluded(25.into())).is_empty());
+            assert!(range(Bound::Excluded(41.into()), Bound::Unbounded).is_empty());
+            assert_eq!(range(Bound::Unbounded, Bound::Unbounded).len(), 5);
+        }
+    }
+
+    #[test]
+    fn range_over_duplicates() {
+        let mut table = users([UserIndex::Age]);
+        let fay = table.insert(user("Fay", 25));
+
+        let young = table
+            .where_range(
+                UserIndex::Age,
+                Bound::Unbounded,
+                Bound::Included(&25.into()),
+            )
+            .unwrap();
+        assert_eq!(names(&young), ["Bob", "Eve", "Fay"]);
+
+        table.remove(fay);
+        let young = table
+            .where_range(
+                UserIndex::Age,
+                Bound::Unbounded,
+                Bound::Included(&25.into()),
+            )
+            .unwrap();
+        assert_eq!(names(&young), ["Bob", "Eve"]);
+    }
+
+    /// Claims to hold integers but extracts the name.
+    #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+    struct MistypedName;
+
+    impl Index<User> for MistypedName {
+        fn data_type(&self) -> DataType {
+            DataType::Int
+        }
+
+        fn extract(&self, user: &User) -> Option<Value> {
+            Some(Value::string(&user.name))
+        }
+
+        fn is_unique(&self) -> bool {
+            false
+        }
+    }
+
+    const MISTYPED: TableError<MistypedName> = TableError::TypeMismatch {
+        index: MistypedName,
+        expected: DataType::Int,
+        actual: DataType::String,
+    };
+
+    #[test]
+    fn mistyped_index_rejects_items() {
+        let mut table = Table::with_indices([MistypedName]);
+        assert_eq!(table.try_insert(user("Ann", 30)), Err(MISTYPED));
+        assert!(table.is_empty());
+
+        let mut table = Table::empty();
+        table.insert(user("Ann", 30));
+        assert_eq!(table.create_index(MistypedName), Err(MISTYPED));
+        assert!(table.indices.is_empty());
+    }
+
+    #[test]
+    fn query_with_mistyped_value() {
+        let mismatch = TableError::TypeMismatch {
+            index: UserIndex::Age,
+            expected: DataType::Int,
+            actual: DataType::String,
+        };
+
+        for table in [users([UserIndex::Age]), users([])] {
+            let queries = [
+                Query::Eq(UserIndex::Age, "30".into()),
+                Query::In(UserIndex::Age, vec![30.into(), "30".into()]),
+                Query::Range(
+                    UserIndex::Age,
+                    Bound::Unbounded,
+                    Bound::Excluded("30".into()),
+                ),
+                Query::starts_with(UserIndex::Age, "3"),
+                !Query::and([
+                    Query::eq(UserIndex::Age, 30),
+                    Query::Eq(UserIndex::Age, "30".into()),
+                ]),
+            ];
+            for query in queries.iter() {
+                assert_eq!(
+                    table.where_query(query),
+                    Err(mismatch.clone()),
+                    "{:?}",
+                    query
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn iter_after_removes() {
+        let mut table = users([UserIndex::Name]);
+        let ids: Vec<ItemID> = table.ids().collect();
+        assert_eq!(table.len(), 5);
+
+        table.remove(ids[1]);
+        table.remove(ids[3]);
+        assert_eq!(table.remove(ids[3]), None);
+
+        let remaining: Vec<_> = table
+            .iter()
+            .map(|(item_id, user)| (item_id, user.name.as_str()))
+            .collect();
+        assert_eq!(
+            remaining,
+            [(ids[0], "Ann"), (ids[2], "Cid"), (ids[4], "Eve")]
+        );
+        assert_eq!(table.len(), 3);
+        assert_eq!(table.len(), table.iter().count());
+        assert_eq!(table.ids().collect::<Vec<_>>(), [ids[0], ids[2], ids[4]]);
+        assert!(!table.contains(ids[1]));
+        assert!(table.contains(ids[2]));
+
+        let fay = table.insert(user("Fay", 20));
+        assert_eq!(table.ids().last(), Some(fay));
+        assert_eq!(table.len(), 4);
+
+        for item_id in table.ids().collect::<Vec<_>>() {
+            table.remove(item_id);
+        }
+        assert!(table.is_empty());
+        assert_eq!(table.iter().count(), 0);
+        assert_eq!(table.into_iter().count(), 0);
+    }
+
+    #[test]
+    fn where_eq_ids_lead_to_the_items() {
+        let mut table = users([UserIndex::Name, UserIndex::Age]);
+
+        let thirty = table.where_eq(UserIndex::Age, 30);
+        for (item_id, user) in thirty.iter() {
+            assert_eq!(table.get(*item_id).as_ref(), Some(user));
+        }
+
+        let (cid, _) = thirty[1].clone();
+        table.update(cid, |user| user.age = 31);
+
+        assert_eq!(names(&table.where_eq(UserIndex::Age, 30)), ["Ann"]);
+        assert_eq!(table.where_eq(UserIndex::Age, 31), [(cid, user("Cid", 31))]);
+        assert_eq!(table.where_eq(UserIndex::Name, "Cid")[0].0, cid);
+    }
+
+    /// Gives Ann, Bob and Dan an email, setting them in reverse alphabetical
+    /// order so that the index is not filled in [`ItemID`] order.
+    fn with_emails(mut table: Table<User, UserIndex>) -> Table<User, UserIndex> {
+        for (item_id, name) in [(3, "dan"), (1, "bob"), (0, "ann")] {
+            table.update(ItemID(item_id), |user| {
+                user.email = Some(format!("{}@example.com", name))
+            });
+        }
+
+        table
+    }
+
+    #[test]
+    fn order_by_value_with_ties() {
+        for table in [users([UserIndex::Age]), users([])] {
+            let ascending = table.order_by(UserIndex::Age, Direction::Ascending);
+            assert_eq!(names(&ascending), ["Bob", "Eve", "Ann", "Cid", "Dan"]);
+
+            let descending = table.order_by(UserIndex::Age, Direction::Descending);
+            assert_eq!(names(&descending), ["Dan", "Cid", "Ann", "Eve", "Bob"]);
+
+            let filtered = table
+                .where_query_ordered(
+                    &!Query::eq(UserIndex::Age, 41),
+                    UserIndex::Age,
+                    Direction::Descending,
+                )
+                .unwrap();
+            assert_eq!(names(&filtered), ["Cid", "Ann", "Eve", "Bob"]);
+        }
+    }
+
+    #[test]
+    fn order_by_places_missing_values_last() {
+        for table in [
+            with_emails(users([UserIndex::Email])),
+            with_emails(users([])),
+        ] {
+            let ascending = table.order_by(UserIndex::Email, Direction::Ascending);
+            assert_eq!(names(&ascending), ["Ann", "Bob", "Dan", "Cid", "Eve"]);
+
+            let descending = table.order_by(UserIndex::Email, Direction::Descending);
+            assert_eq!(names(&descending), ["Dan", "Bob", "Ann", "Cid", "Eve"]);
+        }
+    }
+
+    #[test]
+    fn select_pages() {
+        let orders = [
+            None,
+            Some((UserIndex::Age, Direction::Ascending)),
+            Some((UserIndex::Name, Direction::Descending)),
+        ];
+        for table in [users([UserIndex::Name, UserIndex::Age]), users([])] {
+            for order_by in orders.iter() {
+                let all = table
+                    .select(
+                        &Query::and([]),
+                        &QueryOptions {
+                            order_by: order_by.clone(),
+                            ..QueryOptions::default()
+                        },
+                    )
+                    .unwrap();
+                assert_eq!(all.len(), 5);
+
+                let page = |limit, offset| {
+                    let options = QueryOptions {
+                        limit,
+                        offset,
+                        order_by: order_by.clone(),
+                    };
+                    table.select(&Query::and([]), &options).unwrap()
+                };
+
+                // Pages of two cover every item once, the last one being short.
+                assert_eq!(page(Some(2), 0), all[0..2]);
+                assert_eq!(page(Some(2), 2), all[2..4]);
+                assert_eq!(page(Some(2), 4), all[4..5]);
+                assert_eq!(page(Some(2), 5), []);
+
+                assert_eq!(page(None, 3), all[3..]);
+                assert_eq!(page(Some(0), 0), []);
+                assert_eq!(page(None, 100), []);
+                assert_eq!(page(Some(10), 100), []);
+                assert_eq!(page(Some(usize::MAX), 1), all[1..]);
+
+                for limit in 0..=6 {
+                    let truncated: Vec<_> = all.iter().take(limit).cloned().collect();
+                    assert_eq!(page(Some(limit), 0), truncated, "{:?}", order_by);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn select_filters_before_paging() {
+        let table = users([UserIndex::Age]);
+        let options = QueryOptions {
+            limit: Some(2),
+            offset: 1,
+            order_by: Some((UserIndex::Age, Direction::Descending)),
+        };
+
+        let page = table
+            .select(&Query::lte(UserIndex::Age, 30), &options)
+            .unwrap();
+        assert_eq!(names(&page), ["Ann", "Eve"]);
+    }
+
+    #[test]
+    fn select_walking_the_index_matches_the_query() {
+        let table = users([UserIndex::Name, UserIndex::Age, UserIndex::Email]);
+        let queries = [
+            Query::and([]),
+            Query::or([]),
+            Query::starts_with(UserIndex::Name, "a"),
+            Query::in_(UserIndex::Age, [41, 25]),
+            !Query::eq(UserIndex::Name, "Bob"),
+            Query::between(UserIndex::Age, 25, 30),
+            Query::or([
+                Query::gt(UserIndex::Age, 30),
+                Query::starts_with(UserIndex::Name, "C"),
+            ]),
+            Query::is_null(UserIndex::Email),
+        ];
+
+        for query in queries.iter() {
+            for order_by in [UserIndex::Age, UserIndex::Name, UserIndex::Email] {
+                for direction in [Direction::Ascending, Direction::Descending] {
+                    let all = table
+                        .where_query_ordered(query, order_by.clone(), direction)
+                        .unwrap();
+                    for (limit, offset) in [(0, 0), (2, 0), (2, 1), (10, 3)] {
+                        let options = QueryOptions {
+                            limit: Some(limit),
+                            offset,
+                            order_by: Some((order_by.clone(), direction)),
+                        };
+                        let expected: Vec<_> =
+                            all.iter().skip(offset).take(limit).cloned().collect();
+                        assert_eq!(
+                            table.select(query, &options).unwrap(),
+                            expected,
+                            "{:?} {:?}",
+                            query,
+                            options
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    thread_local! {
+        static EXTRACTED: Cell<usize> = const { Cell::new(0) };
+    }
+
+    /// Indices over numbers which count how many values they extract.
+    #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+    enum CountingIndex {
+        Number,
+        IsEven,
+    }
+
+    impl Index<i64> for CountingIndex {
+        fn data_type(&self) -> DataType {
+            match self {
+                CountingIndex::Number => DataType::Int,
+                CountingIndex::IsEven => DataType::Bool,
+            }
+        }
+
+        fn extract(&self, number: &i64) -> Option<Value> {
+            EXTRACTED.with(|extracted| extracted.set(extracted.get() + 1));
+            Some(match self {
+                CountingIndex::Number => Value::int(*number),
+                CountingIndex::IsEven => Value::bool(number % 2 == 0),
+            })
+        }
+
+        fn is_unique(&self) -> bool {
+            false
+        }
+    }
+
+    #[test]
+    fn select_stops_once_the_page_is_filled() {
+        let mut table = Table::with_indices([CountingIndex::Number]);
+        for number in 0..100 {
+            table.insert(number);
+        }
+
+        let options = QueryOptions {
+            limit: Some(3),
+            offset: 2,
+            order_by: Some((CountingIndex::Number, Direction::Descending)),
+        };
+        EXTRACTED.with(|extracted| extracted.set(0));
+        let page = table
+            .select(&Query::eq(CountingIndex::IsEven, true), &options)
+            .unwrap();
+        let numbers: Vec<_> = page.into_iter().map(|(_, number)| number).collect();
+        assert_eq!(numbers, [94, 92, 90]);
+
+        // Only the numbers from 99 down to 90 are tested against the query.
+        assert_eq!(EXTRACTED.with(Cell::get), 10);
+
+        // A type mismatch is still reported.
+        assert!(matches!(
+            table.select(&Query::eq(CountingIndex::IsEven, 1), &options),
+            Err(TableError::TypeMismatch { .. })
+        ));
+    }
+
+    /// Unique index over the age and the name of the user.
+    #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+    struct AgeName;
+
+    impl Index<User> for AgeName {
+        fn data_type(&self) -> DataType {
+            DataType::Composite
+        }
+
+        fn extract(&self, user: &User) -> Option<Value> {
+            Some(Value::composite([
+                Value::int(user.age),
+                Value::string(&user.name),
+            ]))
+        }
+
+        fn is_unique(&self) -> bool {
+            true
+        }
+    }
+
+    fn composite_tables() -> [Table<User, AgeName>; 2] {
+        let tables = [Table::with_indices([AgeName]), Table::empty()];
+        tables.map(|mut table| {
+            for (_, user) in users([]) {
+                table.insert(user);
+            }
+
+            table
+        })
+    }
+
+    #[test]
+    fn composite_equality() {
+        for table in composite_tables() {
+            let key = |age: i64, name: &str| Value::composite([age.into(), name.into()]);
+
+            let eq = |key| names(&table.where_query(&Query::eq(AgeName, key)).unwrap());
+
+            assert_eq!(eq(key(30, "Cid")), ["Cid"]);
+            assert!(eq(key(30, "Bob")).is_empty());
+            assert!(eq(Value::composite([30.into()])).is_empty());
+
+            let query = Query::in_(AgeName, [key(25, "Eve"), key(41, "Dan"), key(41, "Eve")]);
+            assert_eq!(names(&table.where_query(&query).unwrap()), ["Dan", "Eve"]);
+        }
+    }
+
+    #[test]
+    fn composite_prefix() {
+        for table in composite_tables() {
+            let prefix =
+                |prefix: &[Value]| names(&table.where_composite_prefix(AgeName, prefix).unwrap());
+
+            assert_eq!(prefix(&[30.into()]), ["Ann", "Cid"]);
+            assert_eq!(prefix(&[41.into()]), ["Dan"]);
+            assert_eq!(prefix(&[30.into(), "Ann".into()]), ["Ann"]);
+            assert!(prefix(&[30.into(), "Bob".into()]).is_empty());
+            assert!(prefix(&[31.into()]).is_empty());
+            assert!(prefix(&[30.into(), "Ann".into(), true.into()]).is_empty());
+            assert_eq!(prefix(&[]).len(), 5);
+        }
+
+        let table = users([UserIndex::Age]);
+        assert!(matches!(
+            table.where_composite_prefix(UserIndex::Age, &[30.into()]),
+            Err(TableError::TypeMismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn composite_uniqueness() {
+        let [mut table, _] = composite_tables();
+
+        // Only the whole tuple has to be unique.
+        table.insert(user("Ann", 31));
+        table.insert(user("Abe", 30));
+
+        let ann = table.where_eq(AgeName, Value::composite([30.into(), "Ann".into()]))[0].0;
+        let err = table.try_insert(user("Ann", 30)).unwrap_err();
+        assert_eq!(
+            err,
+            TableError::UniqueViolation {
+                index: AgeName,
+                existing: ann,
+                conflicting: table.item_id.peek(),
+            }
+        );
+        assert_eq!(table.len(), 7);
+    }
+
+    #[test]
+    fn prefix_over_unicode() {
+        for indices in [vec![UserIndex::Name], vec![]] {
+            let mut table = users(indices);
+            for name in ["Äiti", "Äijä", "Ämpäri", "Aino", "日本", "日曜日"] {
+                table.insert(user(name, 50));
+            }
+
+            let prefix = |prefix| names(&table.where_prefix(UserIndex::Name, prefix).unwrap());
+            assert_eq!(prefix("Äi"), ["Äiti", "Äijä"]);
+            assert_eq!(prefix("Ä"), ["Äiti", "Äijä", "Ämpäri"]);
+            assert_eq!(prefix("A"), ["Ann", "Aino"]);
+            assert_eq!(prefix("日"), ["日本", "日曜日"]);
+            assert_eq!(prefix("日曜日"), ["日曜日"]);
+            assert!(prefix("äi").is_empty());
+            assert!(prefix("Äitii").is_empty());
+            assert_eq!(prefix("").len(), 11);
+        }
+    }
+
+    #[test]
+    fn case_insensitive_prefix() {
+        for indices in [vec![UserIndex::Email], vec![]] {
+            let mut table = users(indices);
+            let names_and_emails = [("Ann", "Ann@Example.com"), ("Cid", "ÄITI@example.com")];
+            for (item_id, (_, email)) in [0, 2].into_iter().zip(names_and_emails) {
+                table.update(ItemID(item_id), |user| user.email = Some(email.to_string()));
+            }
+
+            let prefix = |prefix| names(&table.where_prefix(UserIndex::Email, prefix).unwrap());
+            assert_eq!(prefix("ann@"), ["Ann"]);
+            assert_eq!(prefix("ANN@EXAMPLE"), ["Ann"]);
+            assert_eq!(prefix("äiti"), ["Cid"]);
+            assert_eq!(prefix("Äi"), ["Cid"]);
+            assert_eq!(prefix(""), ["Ann", "Cid"]);
+            assert!(prefix("bob").is_empty());
+
+            assert_eq!(
+                names(
+                    &table
+                        .where_query(&Query::eq(UserIndex::Email, "ann@example.COM"))
+                        .unwrap()
+                ),
+                ["Ann"]
+            );
+
+            // Only the index is lowercased.
+            for (name, email) in names_and_emails {
+                let (_, user) = table.iter().find(|(_, user)| user.name == name).unwrap();
+                assert_eq!(user.email.as_deref(), Some(email));
+            }
+        }
+
+        let table = users([UserIndex::Name]);
+        assert!(table
+            .where_prefix(UserIndex::Name, "ann")
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn in_values() {
+        for table in [users([UserIndex::Name, UserIndex::Age]), users([])] {
+            let query = |query: Query<UserIndex>| names(&table.where_query(&query).unwrap());
+            let ages = |ages: &[i64]| Query::in_(UserIndex::Age, ages.iter().copied());
+
+            assert!(query(ages(&[])).is_empty());
+            assert_eq!(query(!ages(&[])).len(), 5);
+            assert_eq!(query(ages(&[41, 25])), ["Bob", "Dan", "Eve"]);
+            assert_eq!(query(ages(&[25, 25, 41, 25])), ["Bob", "Dan", "Eve"]);
+            assert!(query(ages(&[99, 100])).is_empty());
+
+            assert_eq!(
+                query(Query::and([
+                    ages(&[25, 30]),
+                    !Query::in_(UserIndex::Name, ["Ann", "Eve"]),
+                ])),
+                ["Bob", "Cid"]
+            );
+            assert_eq!(
+                query(Query::or([
+                    ages(&[41]),
+                    Query::in_(UserIndex::Name, ["Ann"])
+                ])),
+                ["Ann", "Dan"]
+            );
+        }
+    }
+
+    #[test]
+    fn update_where_updates_matching_items() {
+        let mut table = users([UserIndex::Name, UserIndex::Age]);
+
+        // Matching is decided before updating, so the items are updated once.
+        let query = Query::gte(UserIndex::Age, 30);
+        assert_eq!(table.update_where(&query, |user| user.age += 10), Ok(3));
+        assert_eq!(
+            names(&table.where_query(&Query::gte(UserIndex::Age, 40)).unwrap()),
+            ["Ann", "Cid", "Dan"]
+        );
+        assert_eq!(table.where_eq(UserIndex::Age, 51)[0].1.name, "Dan");
+
+        let none = Query::eq(UserIndex::Age, 99);
+        assert_eq!(table.update_where(&none, |user| user.age = 0), Ok(0));
+        assert_eq!(table.verify_indices(), Ok(()));
+    }
+
+    #[test]
+    fn update_where_conflict_reverts_every_update() {
+        let mut table = users([UserIndex::Name, UserIndex::Age]);
+        let before = contents(&table);
+
+        // Bob is renamed first, then Eve conflicts with him.
+        let query = Query::eq(UserIndex::Age, 25);
+        let result = table.update_where(&query, |user| {
+            user.name = "Zed".to_string();
+            user.age = 26;
+        });
+        assert_eq!(
+            result,
+            Err(TableError::UniqueViolation {
+                index: UserIndex::Name,
+                existing: ItemID(1),
+                conflicting: ItemID(4),
+            })
+        );
+        assert_eq!(contents(&table), before);
+        assert_eq!(table.verify_indices(), Ok(()));
+    }
+
+    #[test]
+    fn remove_where_removes_matching_items() {
+        let mut table = users([UserIndex::Age]);
+
+        let removed = table
+            .remove_where(&Query::in_(UserIndex::Age, [41, 25]))
+            .unwrap();
+        let removed: Vec<_> = removed.iter().map(|user| user.name.as_str()).collect();
+        assert_eq!(removed, ["Bob", "Dan", "Eve"]);
+        assert_eq!(names(&table.snapshot_items()), ["Ann", "Cid"]);
+        assert_eq!(table.count_eq(UserIndex::Age, &25.into()), 0);
+
+        assert_eq!(table.remove_where(&Query::or([])), Ok(vec![]));
+        assert_eq!(table.len(), 2);
+        assert_eq!(table.verify_indices(), Ok(()));
+    }
+
+    #[test]
+    fn upsert_inserts_or_replaces() {
+        let mut table = users([UserIndex::Name, UserIndex::Age]);
+
+        let (fay, inserted) = table.upsert(UserIndex::Name, user("Fay", 19)).unwrap();
+        assert!(inserted);
+        assert_eq!(table.get(fay), Some(user("Fay", 19)));
+        assert_eq!(table.len(), 6);
+
+        let (bob, inserted) = table.upsert(UserIndex::Name, user("Bob", 26)).unwrap();
+        assert!(!inserted);
+        assert_eq!(bob, ItemID(1));
+        assert_eq!(table.get(bob), Some(user("Bob", 26)));
+        assert_eq!(table.len(), 6);
+        assert_eq!(names(&table.where_eq(UserIndex::Age, 25)), ["Eve"]);
+        assert_eq!(table.verify_indices(), Ok(()));
+    }
+
+    #[test]
+    fn upsert_without_value_inserts() {
+        let mut table = users([UserIndex::Name, UserIndex::Email]);
+
+        let (_, inserted) = table.upsert(UserIndex::Email, user("Fay", 19)).unwrap();
+        assert!(inserted);
+        assert_eq!(table.len(), 6);
+
+        // Replacing Ann with an item named Bob conflicts with the name index.
+        let email = Some("ann@example.com".to_string());
+        table.update(ItemID(0), |user| user.email = email.clone());
+        let before = contents(&table);
+        let replacement = User {
+            email,
+            ..user("Bob", 1)
+        };
+        assert!(matches!(
+            table.upsert(UserIndex::Email, replacement),
+            Err(TableError::UniqueViolation { .. })
+        ));
+        assert_eq!(contents(&table), before);
+    }
+
+    #[test]
+    fn upsert_requires_unique_index() {
+        let mut table = users([UserIndex::Age]);
+
+        assert_eq!(
+            table.upsert(UserIndex::Age, user("Fay", 30)),
+            Err(TableError::NotUnique {
+                index: UserIndex::Age
+            })
+        );
+        assert_eq!(
+            table.upsert(UserIndex::Name, user("Fay", 30)),
+            Err(TableError::UnknownIndex {
+                index: UserIndex::Name
+            })
+        );
+        assert_eq!(table.len(), 5);
+    }
+
+    #[test]
+    fn get_by_unique_hit_and_miss() {
+        let table = with_emails(users([UserIndex::Name, UserIndex::Age, UserIndex::Email]));
+
+        assert_eq!(
+            table.get_by_unique(UserIndex::Name, &"Cid".into()),
+            Ok(Some((ItemID(2), user("Cid", 30))))
+        );
+        assert_eq!(
+            table.id_by_unique(UserIndex::Name, &"Cid".into()),
+            Ok(Some(ItemID(2)))
+        );
+        assert_eq!(
+            table.get_by_unique(UserIndex::Name, &"cid".into()),
+            Ok(None)
+        );
+        assert_eq!(table.id_by_unique(UserIndex::Name, &"Zed".into()), Ok(None));
+
+        // Case insensitive indices normalize the looked up value.
+        assert_eq!(
+            table.id_by_unique(UserIndex::Email, &"BOB@example.com".into()),
+            Ok(Some(ItemID(1)))
+        );
+    }
+
+    #[test]
+    fn get_by_unique_misuse() {
+        let table = users([UserIndex::Name, UserIndex::Age]);
+
+        assert_eq!(
+            table.get_by_unique(UserIndex::Age, &30.into()),
+            Err(TableError::NotUnique {
+                index: UserIndex::Age
+            })
+        );
+        assert_eq!(
+            table.id_by_unique(UserIndex::Email, &"ann@example.com".into()),
+            Err(TableError::UnknownIndex {
+                index: UserIndex::Email
+            })
+        );
+        assert_eq!(
+            table.id_by_unique(UserIndex::Name, &30.into()),
+            Err(TableError::TypeMismatch {
+                index: UserIndex::Name,
+                expected: DataType::String,
+                actual: DataType::Int,
+            })
+        );
+    }
+
+    #[test]
+    fn aggregates_match_brute_force() {
+        let mut table = users([UserIndex::Name, UserIndex::Age]);
+        for i in 0..200_i64 {
+            table.insert(user(&format!("User {}", i), (i * 37) % 23));
+        }
+        table.retain(|item_id, _| item_id.0 % 7 != 3);
+
+        let brute_count = |matches: &dyn Fn(&User) -> bool| {
+            table.iter().filter(|(_, user)| matches(user)).count()
+        };
+
+        for age in -1..25 {
+            let expected = brute_count(&|user| user.age == age);
+            assert_eq!(table.count_eq(UserIndex::Age, &age.into()), expected);
+            assert_eq!(
+                table.count_where(&Query::eq(UserIndex::Age, age)),
+                Ok(expected)
+            );
+            assert_eq!(
+                table.count_where(&Query::lt(UserIndex::Age, age)),
+                Ok(brute_count(&|user| user.age < age))
+            );
+        }
+
+        let query = Query::and([
+            Query::between(UserIndex::Age, 5, 15),
+            !Query::starts_with(UserIndex::Name, "User 1"),
+        ]);
+        assert_eq!(
+            table.count_where(&query),
+            Ok(brute_count(&|user| {
+                (5..=15).contains(&user.age) && !user.name.starts_with("User 1")
+            }))
+        );
+
+        let (min_age, min_id) = table
+            .iter()
+            .map(|(item_id, user)| (user.age, item_id))
+            .min()
+            .unwrap();
+        assert_eq!(
+            table.min_by_index(UserIndex::Age),
+            Some((min_age.into(), min_id))
+        );
+
+        let (max_age, max_id) = table
+            .iter()
+            .map(|(item_id, user)| (user.age, item_id))
+            .max()
+            .unwrap();
+        assert_eq!(
+            table.max_by_index(UserIndex::Age),
+            Some((max_age.into(), max_id))
+        );
+
+        let max_name = table.iter().map(|(_, user)| &user.name).max().unwrap();
+        assert_eq!(
+            table.max_by_index(UserIndex::Name).unwrap().0,
+            Value::string(max_name)
+        );
+    }
+
+    #[test]
+    fn aggregates_of_empty_or_unknown_index() {
+        let mut table = users([UserIndex::Age]);
+        assert_eq!(table.min_by_index(UserIndex::Name), None);
+        assert_eq!(table.count_eq(UserIndex::Name, &"Ann".into()), 0);
+
+        table.clear();
+        assert_eq!(table.min_by_index(UserIndex::Age), None);
+        assert_eq!(table.max_by_index(UserIndex::Age), None);
+        assert_eq!(table.count_where(&Query::and([])), Ok(0));
+    }
+
+    fn set_email(table: &mut Table<User, UserIndex>, item_id: u64, email: Option<&str>) {
+        table.update(ItemID(item_id), |user| {
+            user.email = email.map(str::to_string)
+        });
+    }
+
+    #[test]
+    fn nullable_transitions() {
+        for indices in [vec![UserIndex::Email], vec![]] {
+            let mut table = users(indices);
+            let nulls = |table: &Table<User, UserIndex>| names(&table.where_null(UserIndex::Email));
+            assert_eq!(nulls(&table), ["Ann", "Bob", "Cid", "Dan", "Eve"]);
+
+            // None to None
+            set_email(&mut table, 0, None);
+            assert_eq!(nulls(&table), ["Ann", "Bob", "Cid", "Dan", "Eve"]);
+
+            // None to Some
+            set_email(&mut table, 0, Some("ann@example.com"));
+            set_email(&mut table, 1, Some("bob@example.com"));
+            assert_eq!(nulls(&table), ["Cid", "Dan", "Eve"]);
+
+            // Some to Some
+            set_email(&mut table, 0, Some("ann@example.org"));
+            assert_eq!(nulls(&table), ["Cid", "Dan", "Eve"]);
+
+            // Some to None
+            set_email(&mut table, 1, None);
+            assert_eq!(nulls(&table), ["Bob", "Cid", "Dan", "Eve"]);
+
+            let query = Query::and([
+                Query::is_null(UserIndex::Email),
+                Query::eq(UserIndex::Age, 25),
+            ]);
+            assert_eq!(names(&table.where_query(&query).unwrap()), ["Bob", "Eve"]);
+            assert_eq!(
+                names(
+                    &table
+                        .where_query(&!Query::is_null(UserIndex::Email))
+                        .unwrap()
+                ),
+                ["Ann"]
+            );
+            assert_eq!(table.verify_indices(), Ok(()));
+        }
+    }
+
+    /// Index over the email which does not allow users without one.
+    #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+    struct RequiredEmail;
+
+    impl Index<User> for RequiredEmail {
+        fn data_type(&self) -> DataType {
+            DataType::String
+        }
+
+        fn extract(&self, user: &User) -> Option<Value> {
+            user.email.as_ref().map(Value::string)
+        }
+
+        fn is_unique(&self) -> bool {
+            false
+        }
+    }
+
+    #[test]
+    fn not_nullable_rejects_missing_values() {
+        let mut table = Table::with_indices([RequiredEmail]);
+        let ann = User {
+            email: Some("ann@example.com".to_string()),
+            ..user("Ann", 30)
+        };
+        let ann = table.insert(ann);
+
+        assert_eq!(
+            table.try_insert(user("Bob", 25)),
+            Err(TableError::NullNotAllowed {
+                index: RequiredEmail,
+                item_id: ItemID(1),
+            })
+        );
+        assert_eq!(
+            table.try_update(ann, |user| user.email = None),
+            Err(TableError::NullNotAllowed {
+                index: RequiredEmail,
+                item_id: ann,
+            })
+        );
+        assert!(table.get(ann).unwrap().email.is_some());
+        assert_eq!(table.len(), 1);
+    }
+
+    #[test]
+    fn where_eq_follows_updates_to_and_from_null() {
+        let mut table = users([UserIndex::Name, UserIndex::Email]);
+        let email = |table: &Table<User, UserIndex>, email: &str| {
+            names(&table.where_eq(UserIndex::Email, email))
+        };
+
+        set_email(&mut table, 0, Some("ann@example.com"));
+        assert_eq!(email(&table, "ann@example.com"), ["Ann"]);
+
+        // The value freed by going to null can be taken by another item.
+        set_email(&mut table, 0, None);
+        assert!(email(&table, "ann@example.com").is_empty());
+        set_email(&mut table, 1, Some("ann@example.com"));
+        assert_eq!(email(&table, "ann@example.com"), ["Bob"]);
+
+        // Failed updates keep the item where it was in the index, both when
+        // leaving and when entering the null state.
+        let result = table.try_update(ItemID(1), |user| {
+            user.email = None;
+            user.name = "Cid".to_string();
+        });
+        assert!(result.is_err());
+        assert_eq!(email(&table, "ann@example.com"), ["Bob"]);
+
+        let result = table.try_update(ItemID(2), |user| {
+            user.email = Some("cid@example.com".to_string());
+            user.name = "Dan".to_string();
+        });
+        assert!(result.is_err());
+        assert!(email(&table, "cid@example.com").is_empty());
+        assert_eq!(
+            names(&table.where_null(UserIndex::Email)),
+            ["Ann", "Cid", "Dan", "Eve"]
+        );
+        assert_eq!(table.verify_indices(), Ok(()));
+    }
+
+    #[test]
+    fn index_entries_and_indexed_values() {
+        let mut table = users([UserIndex::Age, UserIndex::Email]);
+        set_email(&mut table, 3, Some("Dan@Example.com"));
+
+        let entries = table.index_entries(UserIndex::Age).unwrap();
+        let expected: Vec<(Value, ItemID)> = [(25, 1), (25, 4), (30, 0), (30, 2), (41, 3)]
+            .into_iter()
+            .map(|(age, item_id)| (age.into(), ItemID(item_id)))
+            .collect();
+        assert_eq!(entries, expected);
+        assert_eq!(
+            table.index_entries(UserIndex::Name),
+            Err(TableError::UnknownIndex {
+                index: UserIndex::Name
+            })
+        );
+
+        let values = table.indexed_values(ItemID(3)).unwrap();
+        assert_eq!(values.len(), 2);
+        assert_eq!(values[&UserIndex::Age], Some(41.into()));
+        assert_eq!(values[&UserIndex::Email], Some("dan@example.com".into()));
+        assert_eq!(
+            table.indexed_values(ItemID(0)).unwrap()[&UserIndex::Email],
+            None
+        );
+        assert!(table.indexed_values(ItemID(99)).unwrap().is_empty());
+    }
+
+    #[test]
+    fn verify_indices_reports_broken_entries() {
+        let mut table = users([UserIndex::Age, UserIndex::Email]);
+        assert_eq!(table.verify_indices(), Ok(()));
+
+        let ages = table.indices.get_mut(&UserIndex::Age).unwrap();
+        ages.add(ItemID(99), 7.into());
+        assert_eq!(
+            table.verify_indices(),
+            Err(vec![ConsistencyError::DanglingEntry {
+                index: UserIndex::Age,
+                item_id: ItemID(99),
+                value: Some(7.into()),
+            }])
+        );
+        table
+            .indices
+            .get_mut(&UserIndex::Age)
+            .unwrap()
+            .remove(ItemID(99), 7.into());
+
+        // Changing an item behind the back of the indices leaves its old entry
+        // dangling and the new one missing.
+        table.items.get_mut(&ItemID(2)).unwrap().age = 31;
+        table.items.get_mut(&ItemID(4)).unwrap().email = Some("eve@example.com".to_string());
+        let mut errors = table.verify_indices().unwrap_err();
+        errors.sort_by_key(|error| format!("{:?}", error));
+        assert_eq!(
+            errors,
+            [
+                ConsistencyError::DanglingEntry {
+                    index: UserIndex::Age,
+                    item_id: ItemID(2),
+                    value: Some(30.into()),
+                },
+                ConsistencyError::DanglingEntry {
+                    index: UserIndex::Email,
+                    item_id: ItemID(4),
+                    value: None,
+                },
+                ConsistencyError::MissingEntry {
+                    index: UserIndex::Age,
+                    item_id: ItemID(2),
+                    value: Some(31.into()),
+                },
+                ConsistencyError::MissingEntry {
+                    index: UserIndex::Email,
+                    item_id: ItemID(4),
+                    value: Some("eve@example.com".into()),
+                },
+            ]
+        );
+        assert!(matches!(
+            table.indexed_values(ItemID(2)),
+            Err(ConsistencyError::MissingEntry { .. })
+        ));
+    }
+
+    /// An item which can not be cloned.
+    #[derive(Debug, PartialEq)]
+    struct Handle {
+        name: String,
+        fd: i64,
+    }
+
+    #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+    struct Fd;
+
+    impl Index<Handle> for Fd {
+        fn data_type(&self) -> DataType {
+            DataType::Int
+        }
+
+        fn extract(&self, handle: &Handle) -> Option<Value> {
+            Some(Value::int(handle.fd))
+        }
+
+        fn is_unique(&self) -> bool {
+            true
+        }
+    }
+
+    #[test]
+    fn items_without_clone() {
+        let handle = |name: &str, fd| Handle {
+            name: name.to_string(),
+            fd,
+        };
+
+        let mut table = Table::with_indices([Fd]);
+        let stdin = table.insert(handle("stdin", 0));
+        table.insert(handle("stdout", 1));
+        table.insert(handle("stderr", 2));
+        assert!(table.try_insert(handle("copy", 1)).is_err());
+
+        assert_eq!(table.get_ref(stdin), Some(&handle("stdin", 0)));
+        assert_eq!(table.with(stdin, |handle| handle.name.len()), Some(5));
+        assert_eq!(table.where_eq_ref(Fd, 2)[0].1.name, "stderr");
+
+        let query = Query::gt(Fd, 0);
+        let names: Vec<_> = table
+            .where_query_ref(&query)
+            .unwrap()
+            .into_iter()
+            .map(|(_, handle)| handle.name.as_str())
+            .collect();
+        assert_eq!(names, ["stdout", "stderr"]);
+        assert_eq!(table.count_where(&query), Ok(2));
+
+        assert_eq!(table.remove(stdin), Some(handle("stdin", 0)));
+        assert_eq!(table.iter().count(), 2);
+        assert_eq!(table.id_by_unique(Fd, &0.into()), Ok(None));
+    }
+
+    #[test]
+    fn insert_with_id_interleaved() {
+        let mut table = Table::with_indices([UserIndex::Name]);
+        assert_eq!(table.insert(user("Ann", 30)), ItemID(0));
+
+        // The generator catches up with larger ItemIDs but not smaller ones.
+        table.insert_with_id(ItemID(10), user("Bob", 25)).unwrap();
+        assert_eq!(table.insert(user("Cid", 30)), ItemID(11));
+        table.insert_with_id(ItemID(5), user("Dan", 41)).unwrap();
+        assert_eq!(table.insert(user("Eve", 25)), ItemID(12));
+
+        let ids: Vec<_> = table.ids().map(|item_id| item_id.0).collect();
+        assert_eq!(ids, [0, 5, 10, 11, 12]);
+        assert_eq!(table.get(ItemID(5)), Some(user("Dan", 41)));
+        assert_eq!(
+            table.id_by_unique(UserIndex::Name, &"Bob".into()),
+            Ok(Some(ItemID(10)))
+        );
+    }
+
+    #[test]
+    fn reserve_item_ids_through_a_shared_table() {
+        let mut table = users([UserIndex::Name]);
+        assert_eq!(table.peek_item_id(), ItemID(5));
+
+        let shared = &table;
+        let reserved = [shared.reserve_item_id(), shared.reserve_item_id()];
+        assert_eq!(reserved, [ItemID(5), ItemID(6)]);
+        assert_eq!(shared.peek_item_id(), ItemID(7));
+
+        // Inserted items skip the reserved ItemIDs, which stay free for
+        // inserting with them later.
+        assert_eq!(table.insert(user("Fay", 19)), ItemID(7));
+        table.insert_with_id(reserved[1], user("Gus", 19)).unwrap();
+        assert_eq!(table.get(ItemID(6)), Some(user("Gus", 19)));
+        assert!(!table.contains(reserved[0]));
+        assert_eq!(table.peek_item_id(), ItemID(8));
+    }
+
+    #[test]
+    fn insert_with_id_collisions() {
+        let mut table = users([UserIndex::Name]);
+        let before = contents(&table);
+
+        assert_eq!(
+            table.insert_with_id(ItemID(2), user("Fay", 19)),
+            Err(TableError::ItemIDExists { item_id: ItemID(2) })
+        );
+        assert_eq!(
+            table.insert_with_id(ItemID(20), user("Ann", 19)),
+            Err(TableError::UniqueViolation {
+                index: UserIndex::Name,
+                existing: ItemID(0),
+                conflicting: ItemID(20),
+            })
+        );
+        assert_eq!(contents(&table), before);
+
+        // A removed ItemID can be used again explicitly.
+        table.remove(ItemID(2));
+        table.insert_with_id(ItemID(2), user("Fay", 19)).unwrap();
+        assert_eq!(table.insert(user("Gus", 19)), ItemID(5));
+    }
+
+    #[derive(Debug, Clone, PartialEq)]
+    struct Event {
+        id: Uuid,
+        at: DateTime,
+    }
+
+    #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+    enum EventIndex {
+        Id,
+        At,
+    }
+
+    impl Index<Event> for EventIndex {
+        fn data_type(&self) -> DataType {
+            match self {
+                EventIndex::Id => DataType::Uuid,
+                EventIndex::At => DataType::DateTime,
+            }
+        }
+
+        fn extract(&self, event: &Event) -> Option<Value> {
+            match self {
+                EventIndex::Id => Some(event.id.into()),
+                EventIndex::At => Some(event.at.into()),
+            }
+        }
+
+        fn is_unique(&self) -> bool {
+            matches!(self, EventIndex::Id)
+        }
+    }
+
+    #[test]
+    fn between_time_window() {
+        let minute =
+            |minute: i64| Value::datetime(DateTime::from_secs(1_700_000_000 + minute * 60));
+
+        for indices in [vec![EventIndex::Id, EventIndex::At], vec![]] {
+            let mut table = Table::with_indices(indices);
+            for (i, at) in [0, 5, 10, 10, 15, 20, -5].into_iter().enumerate() {
+                table.insert(Event {
+                    id: Uuid::from_u128(i as u128),
+                    at: DateTime::from_secs(1_700_000_000 + at * 60),
+                });
+            }
+
+            let events = |query: Query<EventIndex>| {
+                table
+                    .where_query_ordered(&query, EventIndex::At, Direction::Ascending)
+                    .unwrap()
+                    .into_iter()
+                    .map(|(_, event)| event.id.as_u128())
+                    .collect::<Vec<_>>()
+            };
+            let between =
+                |from, to| events(Query::between(EventIndex::At, minute(from), minute(to)));
+
+            assert_eq!(between(5, 15), [1, 2, 3, 4]);
+            assert_eq!(between(-10, 0), [6, 0]);
+            assert_eq!(between(10, 10), [2, 3]);
+            assert!(between(21, 30).is_empty());
+            assert!(between(15, 5).is_empty());
+            assert_eq!(
+                events(Query::and([
+                    Query::gt(EventIndex::At, minute(5)),
+                    Query::lt(EventIndex::At, minute(15)),
+                ])),
+                [2, 3]
+            );
+
+            assert_eq!(events(Query::eq(EventIndex::Id, Value::uuid(4))), [4]);
+            assert!(matches!(
+                table.where_query(&Query::between(EventIndex::At, 0, 1)),
+                Err(TableError::TypeMismatch { .. })
+            ));
+        }
+    }
+
+    #[test]
+    fn insert_many_assigns_consecutive_ids() {
+        let mut table = users([UserIndex::Name, UserIndex::Age]);
+        table.remove(ItemID(4));
+
+        let item_ids = table
+            .insert_many([user("Fay", 19), user("Gus", 25), user("Hal", 19)])
+            .unwrap();
+        assert_eq!(item_ids, [ItemID(5), ItemID(6), ItemID(7)]);
+        assert_eq!(names(&table.where_eq(UserIndex::Age, 19)), ["Fay", "Hal"]);
+        assert_eq!(table.insert(user("Ida", 1)), ItemID(8));
+
+        assert_eq!(table.insert_many([]), Ok(vec![]));
+        assert_eq!(table.insert(user("Jan", 1)), ItemID(9));
+        assert_eq!(table.verify_indices(), Ok(()));
+    }
+
+    #[test]
+    fn failed_insert_many_leaves_table_unchanged() {
+        let mut table = users([UserIndex::Name, UserIndex::Age, UserIndex::Email]);
+        let before = contents(&table);
+
+        // Duplicate within the batch.
+        let result = table.insert_many([user("Fay", 19), user("Gus", 25), user("Fay", 20)]);
+        assert_eq!(
+            result,
+            Err(TableError::UniqueViolation {
+                index: UserIndex::Name,
+                existing: ItemID(5),
+                conflicting: ItemID(7),
+            })
+        );
+        assert_eq!(contents(&table), before);
+
+        // Duplicate of an existing item.
+        let result = table.insert_many([user("Fay", 19), user("Eve", 25)]);
+        assert_eq!(
+            result,
+            Err(TableError::UniqueViolation {
+                index: UserIndex::Name,
+                existing: ItemID(4),
+                conflicting: ItemID(6),
+            })
+        );
+        assert_eq!(contents(&table), before);
+
+        let case_duplicates = ["Fay@example.com", "fay@EXAMPLE.com"].map(|email| User {
+            email: Some(email.to_string()),
+            ..user(email, 19)
+        });
+        assert!(table.insert_many(case_duplicates).is_err());
+        assert_eq!(contents(&table), before);
+
+        assert_eq!(table.insert(user("Fay", 19)), ItemID(5));
+    }
+
+    #[test]
+    fn clear_keeps_indices() {
+        let mut table = users([UserIndex::Name, UserIndex::Age]);
+        let events = crate::change::tests::record(&mut table);
+
+        table.clear();
+        assert!(table.is_empty());
+        assert_eq!(table.index_entries(UserIndex::Age), Ok(vec![]));
+        assert_eq!(events.lock().unwrap().len(), 5);
+
+        // The names are free again but the ItemIDs are not.
+        assert_eq!(table.insert(user("Ann", 30)), ItemID(5));
+        assert_eq!(names(&table.where_eq(UserIndex::Age, 30)), ["Ann"]);
+        assert_eq!(table.verify_indices(), Ok(()));
+
+        let mut empty = users([]);
+        empty.clear();
+        empty.clear();
+        assert!(empty.is_empty());
+    }
+
+    #[test]
+    fn retain_none_some_or_all() {
+        let mut table = users([UserIndex::Name, UserIndex::Age]);
+        let events = crate::change::tests::record(&mut table);
+        let before = contents(&table);
+
+        assert_eq!(table.retain(|_, _| true), 0);
+        assert_eq!(contents(&table), before);
+        assert!(events.lock().unwrap().is_empty());
+
+        assert_eq!(
+            table.retain(|item_id, user| item_id.0 != 0 && user.age != 25),
+            3
+        );
+        assert_eq!(names(&table.snapshot_items()), ["Cid", "Dan"]);
+        assert_eq!(events.lock().unwrap().len(), 3);
+        assert_eq!(table.verify_indices(), Ok(()));
+
+        assert_eq!(table.retain(|_, _| false), 2);
+        assert!(table.is_empty());
+        assert_eq!(table.index_entries(UserIndex::Name), Ok(vec![]));
+        assert_eq!(table.retain(|_, _| false), 0);
+    }
+
+    /// The age of the user, except for negative ages where it mistakenly
+    /// extracts the name.
+    #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+    struct SometimesMistyped;
+
+    impl Index<User> for SometimesMistyped {
+        fn data_type(&self) -> DataType {
+            DataType::Int
+        }
+
+        fn extract(&self, user: &User) -> Option<Value> {
+            if user.age < 0 {
+                Some(Value::string(&user.name))
+            } else {
+                Some(Value::int(user.age))
+            }
+        }
+
+        fn is_unique(&self) -> bool {
+            false
+        }
+    }
+
+    #[test]
+    fn failed_retain_removes_nothing() {
+        let mut table = Table::with_indices([SometimesMistyped]);
+        table.insert(user("Ann", 30));
+        table.insert(user("Bob", 25));
+        // Placed directly, as it could not be inserted.
+        table.items.insert(ItemID(2), user("Cid", -1));
+        let before = contents(&table);
+
+        let result =
+            std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| table.retain(|_, _| false)));
+        assert!(result.is_err());
+        assert_eq!(contents(&table), before);
+    }
+
+    #[test]
+    fn clones_diverge() {
+        let mut table = users([UserIndex::Name, UserIndex::Age]);
+        let events = crate::change::tests::record(&mut table);
+        let snapshot = table.clone();
+        let before = contents(&table);
+        assert_eq!(contents(&snapshot), before);
+        assert_eq!(snapshot.snapshot_items(), table.snapshot_items());
+
+        table.insert(user("Fay", 30));
+        table.update(ItemID(0), |user| user.age = 31);
+        table.remove(ItemID(1));
+
+        assert_eq!(contents(&snapshot), before);
+        assert_eq!(names(&table.where_eq(Age, 30)), ["Cid", "Fay"]);
+        assert_eq!(names(&snapshot.where_eq(Age, 30)), ["Ann", "Cid"]);
+        assert_eq!(names(&snapshot.where_eq(Name, "Bob")), ["Bob"]);
+        assert_eq!(snapshot.verify_indices(), Ok(()));
+
+        // The clone hands out its own ItemIDs and does not notify the
+        // handlers of the original.
+        let mut snapshot = snapshot;
+        assert_eq!(snapshot.insert(user("Fay", 41)), ItemID(5));
+        snapshot.remove(ItemID(3));
+        assert_eq!(events.lock().unwrap().len(), 3);
+        assert_eq!(names(&table.where_eq(Age, 41)), ["Dan"]);
+        assert_eq!(names(&snapshot.where_eq(Age, 41)), ["Fay"]);
+        assert_eq!(table.verify_indices(), Ok(()));
+        assert_eq!(snapshot.verify_indices(), Ok(()));
     }
 }
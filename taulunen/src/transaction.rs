@@ -0,0 +1,289 @@
+use std::collections::HashSet;
+
+use crate::{ChangeEvent, Index, ItemID, Table, TableError};
+
+/// A set of changes to a [`Table`] which are either all kept or all discarded,
+/// see [`Table::transaction`].
+///
+/// Every change is validated against the indices as it is made, taking both
+/// the items in the [`Table`] and the earlier changes of the transaction into
+/// account. The changes are discarded when the [`Txn`] is dropped without
+/// being committed, which also happens while unwinding from a panic.
+#[derive(Debug)]
+pub struct Txn<'a, T: Clone, I: Index<T>> {
+    table: &'a mut Table<T, I>,
+    /// The items touched by the transaction as they were before it, in the
+    /// order they were first touched.
+    originals: Vec<(ItemID, Option<T>)>,
+    touched: HashSet<ItemID>,
+}
+
+impl<'a, T: Clone, I: Index<T>> Txn<'a, T, I> {
+    /// Inserts the item, returning the [`ItemID`] assigned to it.
+    ///
+    /// The [`ItemID`] is allocated immediately and is not reused even if the
+    /// transaction is discarded.
+    pub fn insert(&mut self, item: T) -> Result<ItemID, TableError<I>> {
+        let item_id = self.table.item_id.next();
+        self.table.insert_item(item_id, item)?;
+        self.touch(item_id, None);
+
+        Ok(item_id)
+    }
+
+    /// Updates the item with [`item_id`](ItemID) in place, returning the
+    /// output of `update` or `None` if there is no such item.
+    pub fn update<O>(
+        &mut self,
+        item_id: ItemID,
+        update: impl FnOnce(&mut T) -> O,
+    ) -> Result<Option<O>, TableError<I>> {
+        let Some((out, old_item)) = self.table.update_item(item_id, update)? else {
+            return Ok(None);
+        };
+
+        self.touch(item_id, Some(old_item));
+        Ok(Some(out))
+    }
+
+    /// Removes the item with [`item_id`](ItemID), returning the removed item.
+    pub fn remove(&mut self, item_id: ItemID) -> Result<Option<T>, TableError<I>> {
+        let Some(item) = self.table.remove_item(item_id)? else {
+            return Ok(None);
+        };
+
+        self.touch(item_id, Some(item.clone()));
+        Ok(Some(item))
+    }
+
+    /// Returns the item with [`item_id`](ItemID) including the changes made
+    /// by the transaction so far.
+    pub fn get(&self, item_id: ItemID) -> Option<T> {
+        self.table.get(item_id)
+    }
+
+    pub fn contains(&self, item_id: ItemID) -> bool {
+        self.table.contains(item_id)
+    }
+
+    fn touch(&mut self, item_id: ItemID, original: Option<T>) {
+        if self.touched.insert(item_id) {
+            self.originals.push((item_id, original));
+        }
+    }
+
+    /// Keeps the changes, notifying the subscribers of the [`Table`] once per
+    /// touched item.
+    fn commit(&mut self) {
+        self.touched.clear();
+        for (item_id, original) in std::mem::take(&mut self.originals) {
+            let table = &mut *self.table;
+            match (original, table.items.get(&item_id)) {
+                (None, Some(item)) => table.subscribers.notify(|_| ChangeEvent::Inserted {
+                    id: item_id,
+                    item: item.clone(),
+                }),
+                (Some(old), Some(new)) => table.subscribers.notify(|_| ChangeEvent::Updated {
+                    id: item_id,
+                    old,
+                    new: new.clone(),
+                }),
+                (Some(item), None) => table
+                    .subscribers
+                    .notify(|_| ChangeEvent::Removed { id: item_id, item }),
+                (None, None) => (),
+            }
+        }
+    }
+
+    /// Discards the changes, restoring the touched items as they were before
+    /// the transaction.
+    fn rollback(&mut self) {
+        self.touched.clear();
+        let originals = std::mem::take(&mut self.originals);
+
+        // Everything touched is removed before restoring any of the originals
+        // so that restoring an item can not conflict with a value it had been
+        // given by the transaction.
+        for (item_id, _) in originals.iter() {
+            self.table
+                .remove_item(*item_id)
+                .expect("items in the table have valid index values");
+        }
+
+        for (item_id, original) in originals {
+            if let Some(item) = original {
+                self.table
+                    .insert_item(item_id, item)
+                    .expect("reverting a transaction restores a valid state");
+            }
+        }
+    }
+}
+
+impl<'a, T: Clone, I: Index<T>> Drop for Txn<'a, T, I> {
+    /// Discards the changes which have not been committed.
+    fn drop(&mut self) {
+        self.rollback();
+    }
+}
+
+impl<T: Clone, I: Index<T>> Table<T, I> {
+    /// Runs `f` with a [`Txn`] through which it can change the [`Table`]. If
+    /// `f` returns `Ok` the changes are kept, otherwise all of them are
+    /// discarded and the [`Table`] is left unchanged. The changes are also
+    /// discarded if `f` panics.
+    ///
+    /// Subscribers registered with [`Table::on_change`] are notified once
+    /// per item after the changes have been kept, with a single event
+    /// describing the difference between the item before and after the
+    /// transaction.
+    pub fn transaction<R, E>(
+        &mut self,
+        f: impl FnOnce(&mut Txn<'_, T, I>) -> Result<R, E>,
+    ) -> Result<R, E> {
+        let mut txn = Txn {
+            table: self,
+            originals: Vec::new(),
+            touched: HashSet::new(),
+        };
+
+        let result = f(&mut txn);
+        if result.is_ok() {
+            txn.commit();
+        }
+
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::panic::{self, AssertUnwindSafe};
+
+    use super::*;
+    use crate::change::tests::record;
+    use crate::table::tests::{contents, user, users, UserIndex};
+
+    #[test]
+    fn failed_transaction_leaves_table_unchanged() {
+        let mut table = users([UserIndex::Name, UserIndex::Age]);
+        let events = record(&mut table);
+        let before = contents(&table);
+
+        let result = table.transaction(|txn| {
+            txn.update(ItemID(0), |user| user.name = "Zed".to_string())?;
+            txn.remove(ItemID(1))?;
+            txn.insert(user("Ann", 1))?;
+            txn.insert(user("Bob", 1))?;
+            txn.update(ItemID(2), |user| user.name = "Zed".to_string())
+        });
+        assert!(matches!(result, Err(TableError::UniqueViolation { .. })));
+
+        let result = table.transaction(|txn| {
+            txn.remove(ItemID(3)).unwrap();
+            Err::<(), _>("changed my mind")
+        });
+        assert_eq!(result, Err("changed my mind"));
+
+        // The ItemIDs given out by the transactions are not reused.
+        let mut after = contents(&table);
+        assert_eq!(after.next_item_id, ItemID(7));
+        after.next_item_id = before.next_item_id;
+        assert_eq!(after, before);
+        assert_eq!(table.verify_indices(), Ok(()));
+        assert!(events.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn panicking_transaction_leaves_table_unchanged() {
+        let mut table = users([UserIndex::Name, UserIndex::Age]);
+        let events = record(&mut table);
+        let mut before = contents(&table);
+
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            table.transaction(|txn| {
+                txn.update(ItemID(0), |user| user.age = 99)?;
+                txn.remove(ItemID(1))?;
+                txn.insert(user("Fay", 19))?;
+                panic!("in the middle of a transaction");
+
+                #[allow(unreachable_code)]
+                Ok::<(), TableError<UserIndex>>(())
+            })
+        }));
+        assert!(result.is_err());
+
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            table.transaction(|txn| {
+                txn.update(ItemID(2), |user| {
+                    user.age = 99;
+                    panic!("in the middle of an update");
+                })
+            })
+        }));
+        assert!(result.is_err());
+
+        before.next_item_id = ItemID(6);
+        assert_eq!(contents(&table), before);
+        assert_eq!(table.verify_indices(), Ok(()));
+        assert!(table.where_eq(UserIndex::Age, 99).is_empty());
+        assert!(events.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn committed_transaction_notifies_once_per_item() {
+        let mut table = users([UserIndex::Name, UserIndex::Age]);
+        let events = record(&mut table);
+
+        let fay = table
+            .transaction(|txn| {
+                // Swapping the unique names of Ann and Bob passes through a
+                // third name.
+                txn.update(ItemID(0), |user| user.name = "Tmp".to_string())?;
+                txn.update(ItemID(1), |user| user.name = "Ann".to_string())?;
+                txn.update(ItemID(0), |user| user.name = "Bob".to_string())?;
+                txn.update(ItemID(0), |user| user.age = 31)?;
+
+                let fay = txn.insert(user("Fay", 19))?;
+                txn.update(fay, |user| user.age = 20)?;
+                txn.remove(ItemID(2))?;
+
+                // Touched and restored items are notified as well.
+                let gus = txn.insert(user("Gus", 50))?;
+                txn.remove(gus)?;
+
+                Ok::<_, TableError<UserIndex>>(fay)
+            })
+            .unwrap();
+
+        assert_eq!(table.get(ItemID(0)), Some(user("Bob", 31)));
+        assert_eq!(table.get(ItemID(1)), Some(user("Ann", 25)));
+        assert_eq!(table.get(fay), Some(user("Fay", 20)));
+        assert_eq!(table.verify_indices(), Ok(()));
+
+        assert_eq!(
+            *events.lock().unwrap(),
+            [
+                ChangeEvent::Updated {
+                    id: ItemID(0),
+                    old: user("Ann", 30),
+                    new: user("Bob", 31),
+                },
+                ChangeEvent::Updated {
+                    id: ItemID(1),
+                    old: user("Bob", 25),
+                    new: user("Ann", 25),
+                },
+                ChangeEvent::Inserted {
+                    id: fay,
+                    item: user("Fay", 20),
+                },
+                ChangeEvent::Removed {
+                    id: ItemID(2),
+                    item: user("Cid", 30),
+                },
+            ]
+        );
+    }
+}
@@ -1,4 +1,10 @@
-use std::cmp::Ordering;
+use std::{
+    cmp::Ordering,
+    fmt::{self, Display},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::ConversionError;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
 pub enum DataType {
@@ -7,6 +13,9 @@ pub enum DataType {
     Float,
     Int,
     Bool,
+    Composite,
+    DateTime,
+    Uuid,
 }
 
 #[derive(Debug, Clone)]
@@ -16,6 +25,100 @@ pub enum Value {
     Float(f64),
     Int(i64),
     Bool(bool),
+    /// Multiple values ordered lexicographically, used by indices over
+    /// several fields. The types of the individual values are not checked
+    /// against the index.
+    Composite(Vec<Value>),
+    DateTime(DateTime),
+    Uuid(Uuid),
+}
+
+/// A point in time as microseconds since the Unix epoch in UTC.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct DateTime(i64);
+
+impl DateTime {
+    pub fn from_micros(micros: i64) -> Self {
+        DateTime(micros)
+    }
+
+    pub fn from_millis(millis: i64) -> Self {
+        DateTime(millis.saturating_mul(1_000))
+    }
+
+    pub fn from_secs(secs: i64) -> Self {
+        DateTime(secs.saturating_mul(1_000_000))
+    }
+
+    pub fn now() -> Self {
+        SystemTime::now().into()
+    }
+
+    pub fn as_micros(self) -> i64 {
+        self.0
+    }
+}
+
+impl From<SystemTime> for DateTime {
+    fn from(time: SystemTime) -> Self {
+        let micros = match time.duration_since(UNIX_EPOCH) {
+            Ok(duration) => i64::try_from(duration.as_micros()).unwrap_or(i64::MAX),
+            Err(err) => i64::try_from(err.duration().as_micros())
+                .map(|micros| -micros)
+                .unwrap_or(i64::MIN),
+        };
+
+        DateTime(micros)
+    }
+}
+
+/// An UUID stored as its 16 bytes, ordered bytewise.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Uuid([u8; 16]);
+
+impl Uuid {
+    pub fn from_bytes(bytes: [u8; 16]) -> Self {
+        Uuid(bytes)
+    }
+
+    pub fn from_u128(value: u128) -> Self {
+        Uuid(value.to_be_bytes())
+    }
+
+    pub fn as_bytes(&self) -> &[u8; 16] {
+        &self.0
+    }
+
+    pub fn as_u128(self) -> u128 {
+        u128::from_be_bytes(self.0)
+    }
+}
+
+impl From<[u8; 16]> for Uuid {
+    fn from(bytes: [u8; 16]) -> Self {
+        Uuid(bytes)
+    }
+}
+
+impl From<u128> for Uuid {
+    fn from(value: u128) -> Self {
+        Uuid::from_u128(value)
+    }
+}
+
+/// Formats the UUID in the hyphenated form, e.g.
+/// `67e55044-10b1-426f-9247-bb680e5fe0c8`.
+impl Display for Uuid {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, byte) in self.0.iter().enumerate() {
+            if matches!(i, 4 | 6 | 8 | 10) {
+                write!(f, "-")?;
+            }
+            write!(f, "{:02x}", byte)?;
+        }
+
+        Ok(())
+    }
 }
 
 impl Value {
@@ -26,6 +129,9 @@ impl Value {
             Value::Float(_) => DataType::Float,
             Value::Int(_) => DataType::Int,
             Value::Bool(_) => DataType::Bool,
+            Value::Composite(_) => DataType::Composite,
+            Value::DateTime(_) => DataType::DateTime,
+            Value::Uuid(_) => DataType::Uuid,
         }
     }
 
@@ -48,6 +154,233 @@ impl Value {
     pub fn bool(data: impl Into<bool>) -> Self {
         Value::Bool(data.into())
     }
+
+    pub fn composite(data: impl IntoIterator<Item = Value>) -> Self {
+        Value::Composite(data.into_iter().collect())
+    }
+
+    pub fn datetime(data: impl Into<DateTime>) -> Self {
+        Value::DateTime(data.into())
+    }
+
+    pub fn uuid(data: impl Into<Uuid>) -> Self {
+        Value::Uuid(data.into())
+    }
+
+    /// Returns true if the value is a [`Value::Composite`] whose first values
+    /// equal `prefix`.
+    pub fn has_prefix(&self, prefix: &[Value]) -> bool {
+        match self {
+            Value::Composite(values) => values.starts_with(prefix),
+            _ => false,
+        }
+    }
+}
+
+impl From<Vec<u8>> for Value {
+    fn from(value: Vec<u8>) -> Self {
+        Value::Blob(value)
+    }
+}
+
+impl From<String> for Value {
+    fn from(value: String) -> Self {
+        Value::String(value)
+    }
+}
+
+impl From<&str> for Value {
+    fn from(value: &str) -> Self {
+        Value::String(value.to_owned())
+    }
+}
+
+impl From<f64> for Value {
+    fn from(value: f64) -> Self {
+        Value::Float(value)
+    }
+}
+
+impl From<f32> for Value {
+    fn from(value: f32) -> Self {
+        Value::Float(value.into())
+    }
+}
+
+impl From<i64> for Value {
+    fn from(value: i64) -> Self {
+        Value::Int(value)
+    }
+}
+
+impl From<i32> for Value {
+    fn from(value: i32) -> Self {
+        Value::Int(value.into())
+    }
+}
+
+impl From<i16> for Value {
+    fn from(value: i16) -> Self {
+        Value::Int(value.into())
+    }
+}
+
+impl From<i8> for Value {
+    fn from(value: i8) -> Self {
+        Value::Int(value.into())
+    }
+}
+
+impl From<u32> for Value {
+    fn from(value: u32) -> Self {
+        Value::Int(value.into())
+    }
+}
+
+impl From<u16> for Value {
+    fn from(value: u16) -> Self {
+        Value::Int(value.into())
+    }
+}
+
+impl From<u8> for Value {
+    fn from(value: u8) -> Self {
+        Value::Int(value.into())
+    }
+}
+
+impl From<bool> for Value {
+    fn from(value: bool) -> Self {
+        Value::Bool(value)
+    }
+}
+
+impl From<DateTime> for Value {
+    fn from(value: DateTime) -> Self {
+        Value::DateTime(value)
+    }
+}
+
+impl From<Uuid> for Value {
+    fn from(value: Uuid) -> Self {
+        Value::Uuid(value)
+    }
+}
+
+impl TryFrom<Value> for Vec<u8> {
+    type Error = ConversionError;
+
+    fn try_from(value: Value) -> Result<Self, Self::Error> {
+        match value {
+            Value::Blob(value) => Ok(value),
+            value => Err(ConversionError {
+                expected: DataType::Blob,
+                actual: value.data_type(),
+            }),
+        }
+    }
+}
+
+impl TryFrom<Value> for String {
+    type Error = ConversionError;
+
+    fn try_from(value: Value) -> Result<Self, Self::Error> {
+        match value {
+            Value::String(value) => Ok(value),
+            value => Err(ConversionError {
+                expected: DataType::String,
+                actual: value.data_type(),
+            }),
+        }
+    }
+}
+
+impl TryFrom<Value> for f64 {
+    type Error = ConversionError;
+
+    fn try_from(value: Value) -> Result<Self, Self::Error> {
+        match value {
+            Value::Float(value) => Ok(value),
+            value => Err(ConversionError {
+                expected: DataType::Float,
+                actual: value.data_type(),
+            }),
+        }
+    }
+}
+
+impl TryFrom<Value> for i64 {
+    type Error = ConversionError;
+
+    fn try_from(value: Value) -> Result<Self, Self::Error> {
+        match value {
+            Value::Int(value) => Ok(value),
+            value => Err(ConversionError {
+                expected: DataType::Int,
+                actual: value.data_type(),
+            }),
+        }
+    }
+}
+
+impl TryFrom<Value> for bool {
+    type Error = ConversionError;
+
+    fn try_from(value: Value) -> Result<Self, Self::Error> {
+        match value {
+            Value::Bool(value) => Ok(value),
+            value => Err(ConversionError {
+                expected: DataType::Bool,
+                actual: value.data_type(),
+            }),
+        }
+    }
+}
+
+impl TryFrom<Value> for DateTime {
+    type Error = ConversionError;
+
+    fn try_from(value: Value) -> Result<Self, Self::Error> {
+        match value {
+            Value::DateTime(value) => Ok(value),
+            value => Err(ConversionError {
+                expected: DataType::DateTime,
+                actual: value.data_type(),
+            }),
+        }
+    }
+}
+
+impl TryFrom<Value> for Uuid {
+    type Error = ConversionError;
+
+    fn try_from(value: Value) -> Result<Self, Self::Error> {
+        match value {
+            Value::Uuid(value) => Ok(value),
+            value => Err(ConversionError {
+                expected: DataType::Uuid,
+                actual: value.data_type(),
+            }),
+        }
+    }
+}
+
+/// Conversion into the value extracted by an [`Index`](crate::Index), `None`
+/// meaning that the item has no value in the index.
+pub trait IntoValue {
+    fn into_value(self) -> Option<Value>;
+}
+
+impl<T: Into<Value>> IntoValue for T {
+    fn into_value(self) -> Option<Value> {
+        Some(self.into())
+    }
+}
+
+impl<T: Into<Value>> IntoValue for Option<T> {
+    fn into_value(self) -> Option<Value> {
+        self.map(Into::into)
+    }
 }
 
 impl PartialEq for Value {
@@ -64,6 +397,9 @@ impl PartialEq for Value {
             }
             (Value::Int(a), Value::Int(b)) => a == b,
             (Value::Bool(a), Value::Bool(b)) => a == b,
+            (Value::Composite(a), Value::Composite(b)) => a == b,
+            (Value::DateTime(a), Value::DateTime(b)) => a == b,
+            (Value::Uuid(a), Value::Uuid(b)) => a == b,
             _ => false,
         }
     }
@@ -84,6 +420,9 @@ impl PartialOrd for Value {
             },
             (Value::Int(a), Value::Int(b)) => a.partial_cmp(b),
             (Value::Bool(a), Value::Bool(b)) => a.partial_cmp(b),
+            (Value::Composite(a), Value::Composite(b)) => a.partial_cmp(b),
+            (Value::DateTime(a), Value::DateTime(b)) => a.partial_cmp(b),
+            (Value::Uuid(a), Value::Uuid(b)) => a.partial_cmp(b),
             (a, b) => a.data_type().partial_cmp(&b.data_type()),
         }
     }
@@ -94,3 +433,121 @@ impl Ord for Value {
         self.partial_cmp(other).unwrap()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn from_rust_types() {
+        assert_eq!(Value::from(vec![1_u8, 2]), Value::Blob(vec![1, 2]));
+        assert_eq!(Value::from("a"), Value::String("a".to_string()));
+        assert_eq!(Value::from("a".to_string()), Value::String("a".to_string()));
+        assert_eq!(Value::from(1.5_f64), Value::Float(1.5));
+        assert_eq!(Value::from(1.5_f32), Value::Float(1.5));
+        assert_eq!(Value::from(true), Value::Bool(true));
+
+        assert_eq!(Value::from(i64::MIN), Value::Int(i64::MIN));
+        assert_eq!(Value::from(i32::MIN), Value::Int(i32::MIN.into()));
+        assert_eq!(Value::from(-3_i16), Value::Int(-3));
+        assert_eq!(Value::from(-3_i8), Value::Int(-3));
+        assert_eq!(Value::from(u32::MAX), Value::Int(u32::MAX.into()));
+        assert_eq!(Value::from(u16::MAX), Value::Int(65535));
+        assert_eq!(Value::from(u8::MAX), Value::Int(255));
+
+        let time = DateTime::from_secs(2);
+        assert_eq!(
+            Value::from(time),
+            Value::DateTime(DateTime::from_micros(2_000_000))
+        );
+        assert_eq!(
+            Value::from(Uuid::from_u128(7)),
+            Value::Uuid(Uuid::from_u128(7))
+        );
+    }
+
+    #[test]
+    fn try_from_round_trips() {
+        assert_eq!(Vec::<u8>::try_from(Value::from(vec![1_u8])), Ok(vec![1]));
+        assert_eq!(String::try_from(Value::from("a")), Ok("a".to_string()));
+        assert_eq!(f64::try_from(Value::from(0.25)), Ok(0.25));
+        assert_eq!(i64::try_from(Value::from(-7)), Ok(-7));
+        assert_eq!(bool::try_from(Value::from(false)), Ok(false));
+        assert_eq!(
+            DateTime::try_from(Value::from(DateTime::from_millis(5))),
+            Ok(DateTime::from_micros(5_000))
+        );
+        assert_eq!(
+            Uuid::try_from(Value::from(Uuid::from_u128(9))),
+            Ok(Uuid::from_u128(9))
+        );
+    }
+
+    #[test]
+    fn try_from_wrong_type() {
+        let error = |expected, actual| ConversionError { expected, actual };
+
+        assert_eq!(
+            Vec::<u8>::try_from(Value::from("a")),
+            Err(error(DataType::Blob, DataType::String))
+        );
+        assert_eq!(
+            String::try_from(Value::from(vec![97_u8])),
+            Err(error(DataType::String, DataType::Blob))
+        );
+        // Numbers are not converted between integers and floats.
+        assert_eq!(
+            f64::try_from(Value::from(1)),
+            Err(error(DataType::Float, DataType::Int))
+        );
+        assert_eq!(
+            i64::try_from(Value::from(1.0)),
+            Err(error(DataType::Int, DataType::Float))
+        );
+        assert_eq!(
+            bool::try_from(Value::from(1)),
+            Err(error(DataType::Bool, DataType::Int))
+        );
+        assert_eq!(
+            DateTime::try_from(Value::from(1)),
+            Err(error(DataType::DateTime, DataType::Int))
+        );
+        assert_eq!(
+            Uuid::try_from(Value::composite([])),
+            Err(error(DataType::Uuid, DataType::Composite))
+        );
+        assert_eq!(
+            i64::try_from(Value::from("1")).unwrap_err().to_string(),
+            "expected a value of type Int but got String"
+        );
+    }
+
+    #[test]
+    fn into_value() {
+        assert_eq!(3_u8.into_value(), Some(Value::Int(3)));
+        assert_eq!("a".into_value(), Some(Value::String("a".to_string())));
+        assert_eq!(Some(true).into_value(), Some(Value::Bool(true)));
+        assert_eq!(None::<&str>.into_value(), None);
+    }
+
+    #[test]
+    fn datetime_units_and_order() {
+        assert_eq!(DateTime::from_secs(3), DateTime::from_millis(3_000));
+        assert_eq!(DateTime::from_millis(3), DateTime::from_micros(3_000));
+        assert_eq!(DateTime::from_secs(i64::MAX).as_micros(), i64::MAX);
+        assert!(Value::datetime(DateTime::from_secs(-1)) < Value::datetime(DateTime::from_secs(0)));
+
+        let before_epoch = UNIX_EPOCH - std::time::Duration::from_millis(1);
+        assert_eq!(DateTime::from(before_epoch), DateTime::from_millis(-1));
+        assert!(DateTime::now() > DateTime::from_secs(1_700_000_000));
+    }
+
+    #[test]
+    fn uuid_bytes_order_and_display() {
+        let uuid = Uuid::from_u128(0x67e55044_10b1_426f_9247_bb680e5fe0c8);
+        assert_eq!(uuid.to_string(), "67e55044-10b1-426f-9247-bb680e5fe0c8");
+        assert_eq!(Uuid::from_bytes(*uuid.as_bytes()), uuid);
+        assert_eq!(uuid.as_bytes()[0], 0x67);
+        assert!(Value::uuid(1) < Value::uuid(u128::MAX));
+    }
+}
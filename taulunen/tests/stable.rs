@@ -0,0 +1,27 @@
+use std::{fs, path::Path};
+
+/// The crate must build on stable Rust, so none of its sources may enable a
+/// nightly feature.
+#[test]
+fn no_feature_attributes() {
+    let mut dirs = vec![Path::new(env!("CARGO_MANIFEST_DIR")).join("src")];
+    let mut checked = 0;
+    while let Some(dir) = dirs.pop() {
+        for entry in fs::read_dir(dir).unwrap() {
+            let path = entry.unwrap().path();
+            if path.is_dir() {
+                dirs.push(path);
+            } else if path.extension().is_some_and(|extension| extension == "rs") {
+                let source = fs::read_to_string(&path).unwrap();
+                assert!(
+                    !source.contains("#![feature"),
+                    "{} enables a nightly feature",
+                    path.display()
+                );
+                checked += 1;
+            }
+        }
+    }
+
+    assert!(checked > 0, "no sources found");
+}
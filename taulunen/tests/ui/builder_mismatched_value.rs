@@ -0,0 +1,38 @@
+use taulunen::{DataType, Index, IntoValue, Table, TypedIndex, Value};
+
+#[derive(Clone)]
+struct User {
+    age: u8,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+enum UserIndex {
+    Age,
+}
+
+impl Index<User> for UserIndex {
+    fn data_type(&self) -> DataType {
+        DataType::Int
+    }
+
+    fn extract(&self, user: &User) -> Option<Value> {
+        user.age.into_value()
+    }
+
+    fn is_unique(&self) -> bool {
+        false
+    }
+}
+
+struct Age;
+
+impl TypedIndex<UserIndex, u8> for Age {
+    fn index(self) -> UserIndex {
+        UserIndex::Age
+    }
+}
+
+fn main() {
+    let table: Table<User, UserIndex> = Table::empty();
+    let _ = table.query().eq(Age, "thirty").fetch();
+}